@@ -0,0 +1,375 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use road_intersection::constants::{MIN_CYCLE_TIME, MIN_LINK_LENGTH, TICKS_PER_SECOND};
+use road_intersection::models::junction::JunctionGeometry;
+use road_intersection::models::route::Route;
+use road_intersection::models::traffic_light::TrafficLightState;
+use road_intersection::simulation::coordination::SignalPlan;
+use road_intersection::simulation::intersection::Control;
+use road_intersection::simulation::network::{Network, NetworkLayout, RoadGraph};
+use road_intersection::simulation::replay::{parse_grid, InputEvent, Recorder, Replay};
+use road_intersection::simulation::roundabout::Roundabout;
+use road_intersection::simulation::stop_control::{StopControl, StopRule};
+use road_intersection::simulation::sumo;
+
+// Simulation driven from Python
+//
+// A scenario is either described by the same settings as the command line
+// options of the viewer, or read from a replay file, whose inputs are then
+// applied as their ticks come up. Per-vehicle and per-tick figures come back
+// as dicts of equal-length lists, one per column, which `numpy.asarray` and
+// `pandas.DataFrame` take as they are. Inputs are recorded, so a session can
+// be saved and opened in the viewer.
+#[pyclass(unsendable)]
+struct Simulation {
+    network: Network,
+    recorder: Recorder,
+    replay: Option<Replay>,
+}
+
+#[pymethods]
+impl Simulation {
+    #[new]
+    #[pyo3(signature = (seed=0, grid="1x1", link_length=None, legs=None, junction="signals", map=None, cycle=None, replay=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        seed: u64,
+        grid: &str,
+        link_length: Option<f32>,
+        legs: Option<&str>,
+        junction: &str,
+        map: Option<String>,
+        cycle: Option<f32>,
+        replay: Option<&str>,
+    ) -> PyResult<Self> {
+        let replay = replay.map(Replay::load).transpose().map_err(PyValueError::new_err)?;
+        let (seed, layout) = match &replay {
+            Some(replay) => (replay.seed, replay.layout.clone()),
+            None => (seed, layout(grid, link_length, legs, junction, map)?),
+        };
+
+        // Only a cycle given by the caller is rejected when too short; a SUMO
+        // network's own signal program is clamped to the shortest cycle
+        let min_cycle = MIN_CYCLE_TIME as f32 / 1000.0;
+        let mut cycle = cycle;
+        if let Some(cycle) = cycle.filter(|&cycle| replay.is_none() && cycle < min_cycle) {
+            return Err(PyValueError::new_err(format!("Invalid cycle {}, expected at least {} s", cycle, min_cycle)));
+        }
+        if let Some(path) = layout.map.as_deref().filter(|path| sumo::is_network(path)) {
+            let (_, net) = sumo::import(path).map_err(PyValueError::new_err)?;
+            cycle = cycle.or(net.cycle.map(|cycle| cycle.max(min_cycle)));
+        }
+        let graph = RoadGraph::of(&layout).map_err(PyValueError::new_err)?;
+        let mut network = Network::from_graph(seed, layout.clone(), graph);
+
+        let plan = match replay.as_ref().and_then(|replay| replay.plan.clone()) {
+            Some(plan) => plan,
+            None => {
+                let mut plan = SignalPlan::uncoordinated(network.intersections.len());
+                if let Some(cycle) = cycle {
+                    plan.cycle_ticks = (cycle.max(min_cycle) * TICKS_PER_SECOND as f32).round() as u64;
+                }
+                plan
+            }
+        };
+        network.apply_signal_plan(&plan);
+
+        Ok(Simulation {
+            network,
+            recorder: Recorder::new(seed, layout, plan),
+            replay,
+        })
+    }
+
+    // Ticks simulated so far, 60 per second
+    #[getter]
+    fn tick(&self) -> u64 {
+        self.network.tick
+    }
+
+    // Seconds simulated so far
+    #[getter]
+    fn time(&self) -> f32 {
+        self.network.tick as f32 / TICKS_PER_SECOND as f32
+    }
+
+    #[getter]
+    fn intersections(&self) -> usize {
+        self.network.intersections.len()
+    }
+
+    // Advance by a number of ticks, returning the new tick
+    #[pyo3(signature = (ticks=1))]
+    fn step(&mut self, ticks: u64) -> u64 {
+        for _ in 0..ticks {
+            self.tick_once();
+        }
+        self.network.tick
+    }
+
+    // Advance by a number of ticks, sampling the network-wide metrics and
+    // the vehicles approaching each leg every `every` ticks
+    //
+    // Queue columns are named `queue_<intersection>_<leg>`.
+    #[pyo3(signature = (ticks, every=TICKS_PER_SECOND))]
+    fn run<'py>(&mut self, py: Python<'py>, ticks: u64, every: u64) -> PyResult<Bound<'py, PyDict>> {
+        if every == 0 {
+            return Err(PyValueError::new_err("every must be at least one tick"));
+        }
+        let approaches: Vec<(usize, usize)> = self
+            .network
+            .intersections
+            .iter()
+            .enumerate()
+            .flat_map(|(index, intersection)| (0..intersection.geometry.legs.len()).map(move |leg| (index, leg)))
+            .collect();
+        let (mut tick, mut time, mut spawned, mut exited, mut delay, mut throughput) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut queues: Vec<Vec<usize>> = vec![Vec::new(); approaches.len()];
+        for _ in 0..ticks {
+            self.tick_once();
+            if self.network.tick.is_multiple_of(every) {
+                let metrics = &self.network.metrics;
+                tick.push(self.network.tick);
+                time.push(self.time());
+                spawned.push(metrics.spawned);
+                exited.push(metrics.exited);
+                delay.push(metrics.average_delay());
+                throughput.push(metrics.throughput(self.network.tick));
+                for (queue, &(index, leg)) in queues.iter_mut().zip(&approaches) {
+                    queue.push(self.network.intersections[index].approach_count(leg));
+                }
+            }
+        }
+
+        let columns = PyDict::new(py);
+        columns.set_item("tick", tick)?;
+        columns.set_item("time", time)?;
+        columns.set_item("spawned", spawned)?;
+        columns.set_item("exited", exited)?;
+        columns.set_item("average_delay", delay)?;
+        columns.set_item("throughput", throughput)?;
+        for (queue, (index, leg)) in queues.into_iter().zip(approaches) {
+            columns.set_item(format!("queue_{}_{}", index, leg), queue)?;
+        }
+        Ok(columns)
+    }
+
+    // Every vehicle, one column per attribute
+    fn vehicles<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let vehicles: Vec<_> = self
+            .network
+            .intersections
+            .iter()
+            .enumerate()
+            .flat_map(|(index, intersection)| intersection.vehicles.iter().map(move |vehicle| (index, vehicle)))
+            .collect();
+        let columns = PyDict::new(py);
+        columns.set_item("id", vehicles.iter().map(|(_, vehicle)| vehicle.id).collect::<Vec<_>>())?;
+        columns.set_item("x", vehicles.iter().map(|(_, vehicle)| vehicle.position.0).collect::<Vec<_>>())?;
+        columns.set_item("y", vehicles.iter().map(|(_, vehicle)| vehicle.position.1).collect::<Vec<_>>())?;
+        columns.set_item("heading", vehicles.iter().map(|(_, vehicle)| vehicle.heading).collect::<Vec<_>>())?;
+        columns.set_item("speed", vehicles.iter().map(|(_, vehicle)| vehicle.speed()).collect::<Vec<_>>())?;
+        columns.set_item("intersection", vehicles.iter().map(|&(index, _)| index).collect::<Vec<_>>())?;
+        columns.set_item("from_leg", vehicles.iter().map(|(_, vehicle)| vehicle.path.movement.from).collect::<Vec<_>>())?;
+        columns.set_item("to_leg", vehicles.iter().map(|(_, vehicle)| vehicle.path.movement.to).collect::<Vec<_>>())?;
+        columns.set_item("route", vehicles.iter().map(|(_, vehicle)| vehicle.route.name()).collect::<Vec<_>>())?;
+        columns.set_item("stopped", vehicles.iter().map(|(_, vehicle)| vehicle.is_stopped()).collect::<Vec<_>>())?;
+        columns.set_item(
+            "waiting",
+            vehicles
+                .iter()
+                .map(|(_, vehicle)| vehicle.waiting_ticks as f32 / TICKS_PER_SECOND as f32)
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(columns)
+    }
+
+    // Control and signal state of every intersection, one dict each
+    fn lights<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.network
+            .intersections
+            .iter()
+            .map(|intersection| {
+                let lights = PyDict::new(py);
+                lights.set_item("control", intersection.control.name())?;
+                lights.set_item("phase", intersection.phase())?;
+                lights.set_item("phases", intersection.phases.clone())?;
+                lights.set_item(
+                    "remaining",
+                    intersection.phase_remaining_ticks() as f32 / TICKS_PER_SECOND as f32,
+                )?;
+                lights.set_item("held", intersection.phase_held)?;
+                // Whether each leg's light is green, in leg order
+                let mut green = vec![false; intersection.geometry.legs.len()];
+                for light in &intersection.traffic_lights {
+                    green[light.leg] = light.state == TrafficLightState::Green;
+                }
+                lights.set_item("green", green)?;
+                Ok(lights)
+            })
+            .collect()
+    }
+
+    // Network-wide counters
+    fn metrics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let metrics = &self.network.metrics;
+        let result = PyDict::new(py);
+        result.set_item("tick", self.network.tick)?;
+        result.set_item("spawned", metrics.spawned)?;
+        result.set_item("exited", metrics.exited)?;
+        result.set_item("in_network", self.network.vehicles().count())?;
+        result.set_item("average_delay", metrics.average_delay())?;
+        result.set_item("throughput", metrics.throughput(self.network.tick))?;
+        Ok(result)
+    }
+
+    // Vehicles approaching each leg, one list per intersection
+    fn queues(&self) -> Vec<Vec<usize>> {
+        self.network
+            .intersections
+            .iter()
+            .map(|intersection| {
+                (0..intersection.geometry.legs.len())
+                    .map(|leg| intersection.approach_count(leg))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Give green to a phase now
+    #[pyo3(signature = (phase, intersection=0))]
+    fn set_phase(&mut self, phase: usize, intersection: usize) -> PyResult<()> {
+        let phases = self.intersection(intersection)?.phases.len();
+        if phase >= phases {
+            return Err(PyValueError::new_err(format!("Intersection {} has no phase {}", intersection, phase)));
+        }
+        self.apply(InputEvent::SelectPhase(intersection, phase));
+        Ok(())
+    }
+
+    // Keep an intersection's current phase until released, or release it
+    #[pyo3(signature = (held=true, intersection=0))]
+    fn hold_phase(&mut self, held: bool, intersection: usize) -> PyResult<()> {
+        if self.intersection(intersection)?.phase_held != held {
+            self.apply(InputEvent::HoldPhase(intersection));
+        }
+        Ok(())
+    }
+
+    // Spawn a vehicle entering by a leg at the edge of the network, taking a
+    // route or a random one; the new vehicle's id, or None if there was no room
+    #[pyo3(signature = (leg, intersection=0, route=None))]
+    fn spawn(&mut self, leg: usize, intersection: usize, route: Option<&str>) -> PyResult<Option<u32>> {
+        let route = match route {
+            None => None,
+            Some("left") => Some(Route::Left),
+            Some("straight") => Some(Route::Straight),
+            Some("right") => Some(Route::Right),
+            Some(route) => return Err(PyValueError::new_err(format!("Unknown route '{}'", route))),
+        };
+        Ok(self.spawn_with(InputEvent::SpawnAt { entry: intersection, leg, route }))
+    }
+
+    // Spawn a vehicle entering by a leg and leaving that intersection by a
+    // given exit; the new vehicle's id, or None if there was no room
+    #[pyo3(signature = (leg, exit, intersection=0))]
+    fn spawn_to(&mut self, leg: usize, exit: usize, intersection: usize) -> Option<u32> {
+        self.spawn_with(InputEvent::SpawnTo { entry: intersection, leg, exit })
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.apply(InputEvent::Remove(id));
+    }
+
+    // Save the inputs so far as a replay file for the viewer
+    fn save(&self, path: &str) -> PyResult<()> {
+        self.recorder
+            .save(path)
+            .map_err(|e| PyIOError::new_err(format!("Failed to save recording to {}: {}", path, e)))
+    }
+}
+
+impl Simulation {
+    // One tick, after the replayed inputs due on it
+    fn tick_once(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            while let Some(input) = replay.next_due(self.network.tick) {
+                if input.changes_simulation() {
+                    self.recorder.record(self.network.tick, input);
+                    self.network.apply_input(input);
+                }
+            }
+        }
+        self.network.update();
+    }
+
+    fn apply(&mut self, input: InputEvent) {
+        self.recorder.record(self.network.tick, input);
+        self.network.apply_input(input);
+    }
+
+    fn spawn_with(&mut self, input: InputEvent) -> Option<u32> {
+        let next_id = self.network.next_vehicle_id;
+        self.apply(input);
+        (self.network.next_vehicle_id > next_id).then_some(next_id)
+    }
+
+    fn intersection(&self, index: usize) -> PyResult<&road_intersection::simulation::intersection::Intersection> {
+        self.network
+            .intersections
+            .get(index)
+            .ok_or_else(|| PyValueError::new_err(format!("No intersection {}", index)))
+    }
+}
+
+// Network layout from the scenario settings, checked as the command line
+// options are
+fn layout(grid: &str, link_length: Option<f32>, legs: Option<&str>, junction: &str, map: Option<String>) -> PyResult<NetworkLayout> {
+    let (rows, columns) = parse_grid(grid).map_err(PyValueError::new_err)?;
+    let mut layout = NetworkLayout {
+        rows,
+        columns,
+        ..NetworkLayout::default()
+    };
+    if let Some(length) = link_length {
+        if length < MIN_LINK_LENGTH {
+            return Err(PyValueError::new_err(format!(
+                "Invalid link length {}, expected at least {} m",
+                length, MIN_LINK_LENGTH
+            )));
+        }
+        layout.link_length = length;
+    }
+    layout.control = match junction {
+        "signals" => Control::Signals,
+        "roundabout" => Control::Roundabout(Roundabout::default()),
+        "all-way-stop" => Control::Stop(StopControl::new(StopRule::AllWay)),
+        "two-way-stop" => Control::Stop(StopControl::new(StopRule::TwoWay)),
+        junction => {
+            return Err(PyValueError::new_err(format!(
+                "Unknown junction '{}', expected signals, roundabout, all-way-stop or two-way-stop",
+                junction
+            )))
+        }
+    };
+    if let Some(legs) = legs {
+        if rows * columns > 1 {
+            return Err(PyValueError::new_err("legs are only supported on a single junction, not on a grid"));
+        }
+        layout.legs = Some(JunctionGeometry::parse(legs, MIN_LINK_LENGTH).map_err(PyValueError::new_err)?.degrees());
+    }
+    if map.is_some() && (layout.legs.is_some() || rows * columns > 1) {
+        return Err(PyValueError::new_err("Imported maps replace the grid and can not be combined with grid or legs"));
+    }
+    layout.map = map;
+    Ok(layout)
+}
+
+#[pymodule]
+#[pyo3(name = "road_intersection")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Simulation>()
+}
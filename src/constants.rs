@@ -1,19 +1,82 @@
-// Constants for window size
+// Constants for the initial window size, in pixels
 pub const WINDOW_WIDTH: u32 = 800;
 pub const WINDOW_HEIGHT: u32 = 800;
 
-// Constants for road dimensions
-pub const ROAD_WIDTH: u32 = 100;
-pub const LANE_WIDTH: u32 = ROAD_WIDTH / 2;
+// World coordinates are in metres with the intersection center (or the
+// center of a grid of intersections) at the origin, x pointing east and y
+// pointing south
 
-// Constants for traffic light dimensions
-pub const TRAFFIC_LIGHT_SIZE: u32 = 20;
+// Constants for road dimensions, in metres
+pub const ROAD_WIDTH: f32 = 7.0;
+pub const LANE_WIDTH: f32 = ROAD_WIDTH / 2.0;
+pub const LANE_MARKING_WIDTH: f32 = 0.3;
+pub const APPROACH_LENGTH: f32 = 60.0; // From the intersection center to the end of each road
+pub const MIN_LINK_LENGTH: f32 = 40.0; // Between neighbouring intersections in a grid, center to center
 
-// Constants for vehicle dimensions
-pub const VEHICLE_WIDTH: u32 = 30;
-pub const VEHICLE_HEIGHT: u32 = 20;
-pub const VEHICLE_SPEED: f32 = 2.0;
-pub const VEHICLE_SAFE_DISTANCE: f32 = 40.0;
+// Constants for junction geometry
+pub const MIN_LEGS: usize = 3;
+pub const MAX_LEGS: usize = 6;
+pub const MIN_LEG_ANGLE: f32 = 30.0; // Degrees between neighbouring legs
+
+// Constants for traffic light dimensions, in metres
+pub const TRAFFIC_LIGHT_SIZE: f32 = 2.0;
+
+// Constants for vehicle dimensions, in metres
+pub const VEHICLE_LENGTH: f32 = 4.5;
+pub const VEHICLE_WIDTH: f32 = 2.0;
+pub const VEHICLE_SPEED: f32 = 12.0; // Metres per second
+pub const VEHICLE_SAFE_DISTANCE: f32 = 7.0;
 
 // Constants for traffic light timing
-pub const TRAFFIC_LIGHT_CYCLE_TIME: u64 = 5000; // 5 seconds
+pub const TRAFFIC_LIGHT_CYCLE_TIME: u64 = 5000; // 5 seconds of green per phase
+pub const MIN_CYCLE_TIME: u64 = 4000; // Shortest shared cycle, both phases
+
+// Constants for roundabouts
+pub const ROUNDABOUT_RADIUS: f32 = 12.0; // Middle of the circulating lane
+pub const ROUNDABOUT_CRITICAL_GAP: u64 = 4000; // 4 seconds
+pub const ROUNDABOUT_FOLLOW_UP_HEADWAY: u64 = 2500; // 2.5 seconds
+
+// Constants for stop-controlled junctions
+pub const STOP_FULL_STOP_TIME: u64 = 1000; // Standing at the line before moving off, 1 second
+pub const STOP_CRITICAL_GAP: u64 = 6500; // Minor-road gap to major-road traffic, 6.5 seconds
+pub const STOP_BAR_WIDTH: f32 = 0.5; // Metres
+pub const STOP_SIGN_SIZE: f32 = 2.0; // Metres
+
+// Constants for driver behaviour
+pub const RED_RUNNING_TIME: u64 = 2000; // Longest a light has been red that a non-compliant driver still runs it
+pub const LATE_BRAKING_MARGIN: f32 = 1.5; // Metres of the safe distance the most aggressive driver gives up
+pub const CAR_FOLLOWING_RANGE: f32 = 20.0; // Metres ahead within which a driver keeps to a slower leader's speed
+
+// Constants for simulation timing
+pub const TICKS_PER_SECOND: u64 = 60;
+pub const TICK_DURATION: f32 = 1.0 / TICKS_PER_SECOND as f32; // Seconds
+pub const VEHICLE_SPAWN_COOLDOWN: u64 = 1000; // 1 second
+
+// Constants for the timeline
+pub const HISTORY_CAPACITY: usize = 3600; // 1 minute of ticks
+pub const TIMELINE_HEIGHT: u32 = 12;
+
+// Constants for metrics
+pub const THROUGHPUT_WINDOW: u64 = 60000; // 1 minute
+
+// Constants for the time-space diagram
+pub const TIME_SPACE_SAMPLE_TICKS: u64 = 6; // 0.1 seconds
+
+// Constants for trajectory export
+pub const TRAJECTORY_SAMPLE_TICKS: u64 = 6; // 0.1 seconds, unless given on the command line
+
+// Constants for headless runs
+pub const HEADLESS_DRAIN_TIME: u64 = 600000; // 10 minutes for the last vehicles to leave
+
+// Constants for saturation flow measurement
+pub const SATURATION_GREENS: usize = 10; // Saturated greens averaged over for each movement
+
+// Constants for the HUD
+pub const HUD_SCALE: u32 = 2;
+pub const HUD_MARGIN: u32 = 8;
+
+// Constants for mouse interaction
+pub const CLICK_DRAG_THRESHOLD: i32 = 4; // Pixels moved before a click becomes a drag
+pub const ZOOM_STEP: f32 = 1.1;
+pub const MIN_ZOOM: f32 = 0.25; // Relative to fitting the scene in the window
+pub const MAX_ZOOM: f32 = 12.0;
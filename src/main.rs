@@ -1,121 +1,642 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use std::time::Duration;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use rand::Rng;
-use road_intersection::constants::{WINDOW_HEIGHT, WINDOW_WIDTH};
-use road_intersection::models::direction::Direction;
-use road_intersection::simulation::intersection::Intersection;
+use road_intersection::constants::{
+    HEADLESS_DRAIN_TIME, HISTORY_CAPACITY, MIN_CYCLE_TIME, MIN_LINK_LENGTH, SATURATION_GREENS, TICKS_PER_SECOND,
+    TRAJECTORY_SAMPLE_TICKS,
+};
+use road_intersection::models::driver::DriverPopulation;
+use road_intersection::models::junction::JunctionGeometry;
+use road_intersection::simulation::coordination::{bandwidth, optimise_offsets, Corridor, SignalPlan};
+use road_intersection::simulation::dashboard::Dashboard;
+use road_intersection::simulation::history::History;
+use road_intersection::simulation::intersection::Control;
+use road_intersection::simulation::network::{Network, NetworkLayout, RoadGraph};
+use road_intersection::simulation::replay::{parse_grid, InputEvent, Recorder, Replay};
+use road_intersection::simulation::roundabout::Roundabout;
+use road_intersection::simulation::safety::{SafetyMonitor, SafetyThresholds};
+use road_intersection::simulation::saturation::{self, SaturationStudy};
+use road_intersection::simulation::server::Server;
+use road_intersection::simulation::stop_control::{StopControl, StopRule};
+use road_intersection::simulation::sumo::{self, DemandLog};
+use road_intersection::simulation::time_space::TimeSpaceDiagram;
+use road_intersection::simulation::trajectory::{TrajectoryFormat, TrajectoryWriter};
 
-fn main() -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-
-    let window = video_subsystem
-        .window("Road Intersection", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
-    let mut event_pump = sdl_context.event_pump()?;
-
-    let mut intersection = Intersection::new();
-
-    // Enhancement variables
-    let mut is_paused = false;
-    let mut simulation_speed = 1.0f32;
-    let mut debug_mode = false;
-    let mut confirm_exit = false;
-
-    'running: loop {
-        // Handle events
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => {
-                    if confirm_exit {
-                        break 'running;
-                    } else {
-                        confirm_exit = true;
-                        println!("Press Escape again to exit");
-                    }
-                },
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    if confirm_exit {
-                        break 'running;
-                    } else {
-                        confirm_exit = true;
-                        println!("Press Escape again to exit");
+#[cfg(feature = "sdl")]
+mod window;
+
+// Command line options
+struct Options {
+    seed: Option<u64>,
+    record: Option<String>,
+    replay: Option<String>,
+    layout: NetworkLayout,
+    cycle: Option<f32>,        // Seconds
+    offsets: Option<Vec<f32>>, // Seconds, one per intersection
+    green_wave: Option<f32>,   // Progression speed in metres per second
+    time_space: Option<String>,
+    critical_gap: Option<f32>, // Seconds
+    follow_up: Option<f32>,    // Seconds
+    sumo_routes: Option<String>,
+    sumo_export: Option<String>, // Path prefix of the .net.xml and .rou.xml files
+    serve: Option<String>,       // Address to accept remote control connections on
+    dashboard: Option<String>,   // Address to serve the dashboard on
+    headless: bool,              // Run the demand through without the viewer
+    trajectories: Option<String>,
+    trajectory_every: Option<u64>, // Ticks between trajectory samples
+    conflicts: Option<String>,     // CSV file to log conflicts between vehicles to
+    saturation: Option<String>,    // Approach to measure saturation flow on instead of running
+    drivers: Option<DriverPopulation>,
+    speed: Option<f32>, // Simulated seconds per second of real time
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        seed: None,
+        record: None,
+        replay: None,
+        layout: NetworkLayout::default(),
+        cycle: None,
+        offsets: None,
+        green_wave: None,
+        time_space: None,
+        critical_gap: None,
+        follow_up: None,
+        sumo_routes: None,
+        sumo_export: None,
+        serve: None,
+        dashboard: None,
+        headless: false,
+        trajectories: None,
+        trajectory_every: None,
+        conflicts: None,
+        saturation: None,
+        drivers: None,
+        speed: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--seed" => {
+                let seed = value()?;
+                options.seed = Some(seed.parse().map_err(|_| format!("Invalid seed '{}'", seed))?);
+            }
+            "--record" => options.record = Some(value()?),
+            "--replay" => options.replay = Some(value()?),
+            "--grid" => {
+                let (rows, columns) = parse_grid(&value()?)?;
+                options.layout.rows = rows;
+                options.layout.columns = columns;
+            }
+            "--link-length" => {
+                let length = value()?;
+                options.layout.link_length = length
+                    .parse()
+                    .ok()
+                    .filter(|&length| length >= MIN_LINK_LENGTH)
+                    .ok_or(format!("Invalid link length '{}', expected at least {} m", length, MIN_LINK_LENGTH))?;
+            }
+            "--cycle" => {
+                let cycle = value()?;
+                let min_cycle = MIN_CYCLE_TIME as f32 / 1000.0;
+                options.cycle = Some(
+                    cycle
+                        .parse()
+                        .ok()
+                        .filter(|&cycle| cycle >= min_cycle)
+                        .ok_or(format!("Invalid cycle '{}', expected at least {} s", cycle, min_cycle))?,
+                );
+            }
+            "--offsets" => {
+                let offsets = value()?;
+                options.offsets = Some(
+                    offsets
+                        .split(',')
+                        .map(|offset| offset.parse().ok().filter(|&offset: &f32| offset >= 0.0))
+                        .collect::<Option<Vec<f32>>>()
+                        .ok_or(format!("Invalid offsets '{}'", offsets))?,
+                );
+            }
+            "--green-wave" => {
+                let speed = value()?;
+                options.green_wave = Some(
+                    speed
+                        .parse()
+                        .ok()
+                        .filter(|&speed: &f32| speed > 0.0)
+                        .ok_or(format!("Invalid progression speed '{}'", speed))?,
+                );
+            }
+            "--time-space" => options.time_space = Some(value()?),
+            "--junction" => {
+                options.layout.control = match value()?.as_str() {
+                    "signals" => Control::Signals,
+                    "roundabout" => Control::Roundabout(Roundabout::default()),
+                    "all-way-stop" => Control::Stop(StopControl::new(StopRule::AllWay)),
+                    "two-way-stop" => Control::Stop(StopControl::new(StopRule::TwoWay)),
+                    junction => {
+                        return Err(format!(
+                            "Unknown junction '{}', expected signals, roundabout, all-way-stop or two-way-stop",
+                            junction
+                        ))
                     }
                 }
-                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
-                    match keycode {
-                        Keycode::Up => intersection.spawn_vehicle(Direction::South),
-                        Keycode::Down => intersection.spawn_vehicle(Direction::North),
-                        Keycode::Left => intersection.spawn_vehicle(Direction::East),
-                        Keycode::Right => intersection.spawn_vehicle(Direction::West),
-                        Keycode::R => {
-                            let mut rng = rand::thread_rng();
-                            let direction = match rng.gen_range(0..4) {
-                                0 => Direction::North,
-                                1 => Direction::South,
-                                2 => Direction::East,
-                                _ => Direction::West,
-                            };
-                            intersection.spawn_vehicle(direction);
-                        },
-                        // Enhancement: Pause/Resume
-                        Keycode::Space => {
-                            is_paused = !is_paused;
-                            println!("Simulation {}", if is_paused { "PAUSED" } else { "RESUMED" });
-                        },
-                        // Enhancement: Debug Mode
-                        Keycode::D => {
-                            debug_mode = !debug_mode;
-                            println!("Debug Mode {}", if debug_mode { "ON" } else { "OFF" });
-                        },
-                        // Enhancement: Speed Control
-                        Keycode::Equals | Keycode::KpPlus => {
-                            simulation_speed = f32::min(simulation_speed * 1.5, 5.0);
-                            println!("Speed: {:.1}x", simulation_speed);
-                        },
-                        Keycode::Minus | Keycode::KpMinus => {
-                            simulation_speed = f32::max(simulation_speed / 1.5, 0.25);
-                            println!("Speed: {:.1}x", simulation_speed);
-                        },
-                        _ => {}
-                    }
-                    confirm_exit = false; // Reset exit confirmation on any other key press
+            }
+            "--critical-gap" => options.critical_gap = Some(parse_seconds(&value()?)?),
+            "--follow-up" => options.follow_up = Some(parse_seconds(&value()?)?),
+            "--osm" => options.layout.map = Some(value()?),
+            "--sumo-net" => {
+                let path = value()?;
+                if !sumo::is_network(&path) {
+                    return Err(format!("Expected a .net.xml file, got '{}'", path));
                 }
-                _ => {}
+                options.layout.map = Some(path);
+            }
+            "--sumo-routes" => options.sumo_routes = Some(value()?),
+            "--sumo-export" => options.sumo_export = Some(value()?),
+            "--serve" => options.serve = Some(value()?),
+            "--dashboard" => options.dashboard = Some(value()?),
+            "--headless" => options.headless = true,
+            "--trajectories" => {
+                let path = value()?;
+                TrajectoryFormat::of(&path)?;
+                options.trajectories = Some(path);
+            }
+            "--conflicts" => options.conflicts = Some(value()?),
+            "--trajectory-every" => {
+                let ticks = value()?;
+                options.trajectory_every = Some(
+                    ticks
+                        .parse()
+                        .ok()
+                        .filter(|&ticks| ticks > 0)
+                        .ok_or(format!("Invalid trajectory interval '{}', expected a number of ticks", ticks))?,
+                );
             }
+            "--legs" => {
+                let geometry = JunctionGeometry::parse(&value()?, MIN_LINK_LENGTH)?;
+                options.layout.legs = Some(geometry.degrees());
+            }
+            "--drivers" => options.drivers = Some(DriverPopulation::parse(&value()?)?),
+            "--saturation" => options.saturation = Some(value()?),
+            "--speed" => {
+                let speed = value()?;
+                options.speed = Some(
+                    speed
+                        .parse()
+                        .ok()
+                        .filter(|&speed: &f32| speed.is_finite() && speed > 0.0)
+                        .ok_or(format!("Invalid speed '{}', expected a positive multiplier", speed))?,
+                );
+            }
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+    }
+
+    let to_ticks = |seconds: f32| (seconds * TICKS_PER_SECOND as f32).round() as u64;
+    if let Some(gap) = options.critical_gap {
+        match &mut options.layout.control {
+            Control::Roundabout(roundabout) => roundabout.critical_gap = to_ticks(gap),
+            Control::Stop(stop) if stop.rule == StopRule::TwoWay => stop.critical_gap = to_ticks(gap),
+            _ => return Err("--critical-gap needs --junction roundabout or two-way-stop".to_string()),
+        }
+    }
+    if let Some(follow_up) = options.follow_up {
+        let Control::Roundabout(roundabout) = &mut options.layout.control else {
+            return Err("--follow-up needs --junction roundabout".to_string());
+        };
+        roundabout.follow_up_headway = to_ticks(follow_up);
+    }
+
+    if options.layout.legs.is_some() && options.layout.rows * options.layout.columns > 1 {
+        return Err("--legs is only supported on a single junction, not with --grid".to_string());
+    }
+    if options.layout.map.is_some() {
+        if options.layout.legs.is_some() || options.layout.rows * options.layout.columns > 1 {
+            return Err("Imported maps replace the grid and can not be combined with --grid or --legs".to_string());
+        }
+        if options.green_wave.is_some() || options.time_space.is_some() {
+            return Err("--green-wave and --time-space need a grid corridor, not an imported map".to_string());
+        }
+    }
+    if options.sumo_routes.is_some() {
+        if !options.layout.map.as_deref().is_some_and(sumo::is_network) {
+            return Err("--sumo-routes needs the network it was written for, given with --sumo-net".to_string());
+        }
+        if options.replay.is_some() {
+            return Err("--sumo-routes can not be combined with --replay, which brings its own demand".to_string());
+        }
+    }
+    if options.serve.is_some()
+        && (options.replay.is_some()
+            || options.time_space.is_some()
+            || options.sumo_routes.is_some()
+            || options.sumo_export.is_some()
+            || options.trajectories.is_some()
+            || options.conflicts.is_some())
+    {
+        return Err("--serve runs without the viewer and can not be combined with --replay, --time-space, --sumo-routes, --sumo-export, --trajectories or --conflicts".to_string());
+    }
+    if options.trajectory_every.is_some() && options.trajectories.is_none() {
+        return Err("--trajectory-every needs --trajectories".to_string());
+    }
+    if options.headless {
+        if options.serve.is_some() {
+            return Err("--headless and --serve are two different ways to run without the viewer, use one".to_string());
+        }
+        if options.replay.is_none() && options.sumo_routes.is_none() {
+            return Err("--headless needs demand to run, from --replay or --sumo-routes".to_string());
+        }
+    }
+    if options.saturation.is_some()
+        && (options.record.is_some()
+            || options.time_space.is_some()
+            || options.sumo_routes.is_some()
+            || options.sumo_export.is_some()
+            || options.serve.is_some()
+            || options.dashboard.is_some()
+            || options.headless
+            || options.trajectories.is_some()
+            || options.conflicts.is_some())
+    {
+        return Err("--saturation prints its results and exits, and can only be combined with options setting up the network, signals and drivers".to_string());
+    }
+    if options.speed.is_some()
+        && (options.serve.is_some() || options.saturation.is_some() || options.headless && options.dashboard.is_none())
+    {
+        return Err("--speed paces the viewer and headless runs with a dashboard; other runs go as fast as they can".to_string());
+    }
+    if options.sumo_export.is_some() {
+        if options.layout.rows * options.layout.columns > 1 {
+            return Err("--sumo-export writes a single junction, not a grid".to_string());
         }
+        if matches!(options.layout.control, Control::Roundabout(_)) {
+            return Err("--sumo-export does not support roundabouts".to_string());
+        }
+    }
+    if cfg!(not(feature = "sdl")) && options.serve.is_none() && !options.headless && options.saturation.is_none() {
+        return Err("Built without the viewer window, the sdl feature, so only --serve, --headless and --saturation can run".to_string());
+    }
+
+    Ok(options)
+}
+
+fn parse_seconds(text: &str) -> Result<f32, String> {
+    text.parse()
+        .ok()
+        .filter(|&seconds: &f32| seconds >= 0.0)
+        .ok_or(format!("Invalid duration '{}', expected seconds", text))
+}
 
-        // Update simulation if not paused
-        if !is_paused {
-            intersection.update();
+// Signal plan from the command line: the default or given cycle, with the
+// given offsets or the ones giving the widest green wave along the corridor
+fn signal_plan(options: &Options, network: &Network) -> Result<SignalPlan, String> {
+    let count = network.intersections.len();
+    let mut plan = SignalPlan::uncoordinated(count);
+    if let Some(cycle) = options.cycle {
+        plan.cycle_ticks = (cycle * TICKS_PER_SECOND as f32).round() as u64;
+    }
+    let to_seconds = |ticks: u64| ticks as f32 / TICKS_PER_SECOND as f32;
+
+    match (&options.offsets, options.green_wave) {
+        (Some(_), Some(_)) => return Err("Use either --offsets or --green-wave, not both".to_string()),
+        (Some(offsets), None) => {
+            if offsets.len() != count {
+                return Err(format!("Expected {} offsets, one per intersection, got {}", count, offsets.len()));
+            }
+            plan.offsets = offsets
+                .iter()
+                .map(|offset| (offset * TICKS_PER_SECOND as f32).round() as u64 % plan.cycle_ticks)
+                .collect();
         }
+        (None, Some(speed)) => {
+            let corridor = Corridor::of(network);
+            plan = optimise_offsets(network, &corridor, plan.cycle_ticks, speed);
+            let band = bandwidth(&corridor, &plan, speed);
+            let offsets: Vec<String> = plan
+                .offsets
+                .iter()
+                .map(|&offset| format!("{:.1}", to_seconds(offset)))
+                .collect();
+            println!("Green wave at {} m/s: offsets {} s", speed, offsets.join(", "));
+            println!(
+                "Bandwidth {:.1} s outbound, {:.1} s inbound, of a {:.1} s cycle",
+                to_seconds(band.outbound),
+                to_seconds(band.inbound),
+                to_seconds(plan.cycle_ticks)
+            );
+        }
+        (None, None) => {}
+    }
 
-        // Render
-        canvas.set_draw_color(Color::RGB(0, 128, 0)); // Green background for grass
-        canvas.clear();
+    Ok(plan)
+}
+
+// Simulation together with the viewer state that inputs act on
+struct Viewer {
+    network: Network,
+    history: History,
+    recorder: Recorder,
+    time_space: Option<TimeSpaceDiagram>,
+    demand_log: Option<DemandLog>,
+    dashboard: Option<Dashboard>,
+    trajectories: Option<TrajectoryWriter>,
+    safety: Option<SafetyMonitor>,
+    is_paused: bool,
+    simulation_speed: f32,
+    headless: bool, // No window to watch from, so pausing, stepping and speed changes are ignored
+}
 
-        intersection.render(&mut canvas);
+impl Viewer {
+    fn new(network: Network, recorder: Recorder) -> Self {
+        let mut history = History::new(HISTORY_CAPACITY);
+        history.push(network.clone());
 
-        // Render debug information if debug mode is on
-        if debug_mode {
-            // Draw a simple indicator that debug mode is on
-            canvas.set_draw_color(Color::RGB(255, 255, 255));
-            canvas.fill_rect(sdl2::rect::Rect::new(10, 10, 20, 20))
-                .expect("Failed to render debug indicator");
+        Viewer {
+            network,
+            history,
+            recorder,
+            time_space: None,
+            demand_log: None,
+            dashboard: None,
+            trajectories: None,
+            safety: None,
+            is_paused: false,
+            simulation_speed: 1.0,
+            headless: false,
         }
+    }
+
+    // Apply an input to the simulation, the same way for live and replayed input
+    fn apply_input(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Pause | InputEvent::Resume | InputEvent::Speed(_) | InputEvent::Step if self.headless => {}
+            InputEvent::Pause | InputEvent::Resume => {
+                self.is_paused = event == InputEvent::Pause;
+                println!("Simulation {}", if self.is_paused { "PAUSED" } else { "RESUMED" });
+            }
+            InputEvent::Speed(speed) => {
+                self.simulation_speed = speed;
+                println!("Speed: {:.1}x", self.simulation_speed);
+            }
+            InputEvent::HoldPhase(index) => {
+                self.network.apply_input(event);
+                if let Some(intersection) = self.network.intersections.get(index) {
+                    println!("Phase {}", if intersection.phase_held { "HELD" } else { "RELEASED" });
+                }
+            }
+            InputEvent::Step => self.advance(),
+            _ => self.network.apply_input(event),
+        }
+    }
 
-        canvas.present();
+    // Run one tick of the live simulation and keep a snapshot of the result
+    fn advance(&mut self) {
+        if let Some(log) = &mut self.demand_log {
+            log.record(&self.network);
+        }
+        let started = Instant::now();
+        self.network.update();
+        let step = started.elapsed();
+        self.history.push(self.network.clone());
+        if let Some(diagram) = &mut self.time_space {
+            diagram.record(&self.network);
+        }
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.record(&self.network, step);
+        }
+        if let Some(monitor) = &mut self.safety {
+            monitor.record(&self.network);
+        }
+        if let Some(Err(e)) = self.trajectories.as_mut().map(|writer| writer.record(&self.network)) {
+            println!("Trajectory export stopped: {}", e);
+            self.trajectories = None;
+        }
+    }
+
+    // Feed the recorded inputs and the route file departures that are due on
+    // this tick
+    fn feed(&mut self, replay: &mut Option<Replay>, departures: &mut Vec<(u64, InputEvent)>) {
+        if let Some(active) = replay {
+            while let Some(input) = active.next_due(self.network.tick) {
+                self.recorder.record(self.network.tick, input);
+                self.apply_input(input);
+            }
+            if active.is_finished() {
+                println!("Replay finished at tick {}", self.network.tick);
+                *replay = None;
+            }
+        }
+
+        // Departures are recorded like any other input; one waits while its
+        // leg has no room
+        let tick = self.network.tick;
+        let due = departures.partition_point(|&(at, _)| at <= tick);
+        let mut waiting = Vec::new();
+        for (at, input) in departures.drain(..due) {
+            match input {
+                InputEvent::SpawnTo { entry, leg, .. } if !self.network.can_enter(entry, leg) => {
+                    waiting.push((at, input));
+                }
+                _ => {
+                    self.recorder.record(tick, input);
+                    self.apply_input(input);
+                }
+            }
+        }
+        departures.splice(0..0, waiting);
+    }
+
+    // Time to wait between frames at the current simulation speed
+    fn frame_time(&self) -> Duration {
+        Duration::new(0, (1_000_000_000f64 / (60.0 * self.simulation_speed as f64)) as u32)
+    }
+}
+
+fn main() -> Result<(), String> {
+    let mut options = parse_args()?;
+
+    // A replay brings its own seed, network and signal plan; otherwise use
+    // the given ones or pick a seed. Drivers given on the command line
+    // replace a replay's, keeping its demand and routes.
+    let mut replay = options.replay.as_deref().map(Replay::load).transpose()?;
+    let (seed, mut layout, plan) = match &replay {
+        Some(replay) => (replay.seed, replay.layout.clone(), replay.plan.clone()),
+        None => (options.seed.unwrap_or_else(|| rand::thread_rng().gen()), options.layout.clone(), None),
+    };
+    if let Some(drivers) = options.drivers {
+        layout.drivers = drivers;
+    }
+    println!("Seed: {}", seed);
+
+    // A SUMO network also brings the cycle of its signal program, and the
+    // edges its route files refer to
+    let mut sumo_net = None;
+    if let Some(path) = layout.map.as_deref().filter(|path| sumo::is_network(path)) {
+        let (_, net) = sumo::import(path)?;
+        println!("Imported SUMO junction {}", net.junction);
+        if let (None, Some(cycle)) = (options.cycle, net.cycle) {
+            options.cycle = Some(cycle.max(MIN_CYCLE_TIME as f32 / 1000.0));
+        }
+        sumo_net = Some(net);
+    }
+    let mut network = Network::from_graph(seed, layout.clone(), RoadGraph::of(&layout)?);
+    if layout.map.as_deref().is_some_and(|path| !sumo::is_network(path)) {
+        println!("Imported {} junctions", network.intersections.len());
+    }
+    if options.sumo_export.is_some() && network.intersections.len() > 1 {
+        return Err("--sumo-export writes a single junction, not an imported network".to_string());
+    }
+
+    // Vehicles read from a route file are spawned once they are due
+    let mut departures = Vec::new();
+    if let (Some(path), Some(net)) = (&options.sumo_routes, &sumo_net) {
+        let demand = net.import_routes(path, seed)?;
+        println!("Read {} departures from {}", demand.departures.len(), path);
+        if demand.skipped > 0 {
+            println!("Skipped {} vehicles and flows whose route does not cross the junction", demand.skipped);
+        }
+        departures = demand.departures;
+    }
+    let plan = match plan {
+        Some(plan) => plan,
+        None => signal_plan(&options, &network)?,
+    };
+    network.apply_signal_plan(&plan);
+
+    // A saturation flow study replaces the run
+    if let Some(approach) = &options.saturation {
+        let (intersection, leg) = saturation::parse_approach(approach, &network)?;
+        let study = SaturationStudy::new(&network, intersection, leg)?;
+        print!("{}", saturation::report(&study, &study.run(SATURATION_GREENS)?));
+        return Ok(());
+    }
+
+    let dashboard = options
+        .dashboard
+        .as_deref()
+        .map(|address| Dashboard::start(address).map_err(|e| format!("Dashboard on {} failed: {}", address, e)))
+        .transpose()?;
+    if let Some(dashboard) = &dashboard {
+        println!("Dashboard on http://{}", dashboard.address);
+    }
+
+    // In server mode remote clients drive the simulation instead of the viewer
+    if let Some(address) = &options.serve {
+        let mut server = Server::new(network, Recorder::new(seed, layout, plan));
+        server.dashboard = dashboard;
+        server.run(address).map_err(|e| format!("Server on {} failed: {}", address, e))?;
+        if let Some(path) = &options.record {
+            server
+                .recorder
+                .save(path)
+                .map_err(|e| format!("Failed to save recording to {}: {}", path, e))?;
+            println!("Recording saved to {}", path);
+        }
+        return Ok(());
+    }
+
+    let mut viewer = Viewer::new(network, Recorder::new(seed, layout, plan));
+    if options.time_space.is_some() {
+        viewer.time_space = Some(TimeSpaceDiagram::new(Corridor::of(&viewer.network)));
+    }
+    if options.sumo_export.is_some() {
+        viewer.demand_log = Some(DemandLog::default());
+    }
+    viewer.dashboard = dashboard;
+    if let Some(path) = &options.trajectories {
+        let every = options.trajectory_every.unwrap_or(TRAJECTORY_SAMPLE_TICKS);
+        viewer.trajectories = Some(TrajectoryWriter::create(path, every)?);
+    }
+    if options.conflicts.is_some() {
+        viewer.safety = Some(SafetyMonitor::new(&viewer.network, SafetyThresholds::default()));
+    }
+    if let Some(speed) = options.speed {
+        viewer.simulation_speed = speed;
+    }
+
+    // Headless, the demand runs through until every vehicle has left, or
+    // until the vehicles still in the network have had long enough to leave
+    // after the last of it. Only a run watched on the dashboard keeps to real
+    // time; otherwise it goes as fast as it can.
+    if options.headless {
+        viewer.headless = true;
+        let drain_ticks = HEADLESS_DRAIN_TIME * TICKS_PER_SECOND / 1000;
+        let mut demand_done_at = None;
+        while replay.is_some() || !departures.is_empty() || viewer.network.vehicles().next().is_some() {
+            if replay.is_none() && departures.is_empty() {
+                let done_at = *demand_done_at.get_or_insert(viewer.network.tick);
+                if viewer.network.tick - done_at >= drain_ticks {
+                    break;
+                }
+            }
+            viewer.feed(&mut replay, &mut departures);
+            viewer.advance();
+            if viewer.dashboard.is_some() {
+                std::thread::sleep(viewer.frame_time());
+            }
+        }
+        match viewer.network.vehicles().count() {
+            0 => println!("All vehicles left at tick {}", viewer.network.tick),
+            count => println!(
+                "Stopped at tick {} with {} vehicles still in the network, {} s after the last of the demand",
+                viewer.network.tick,
+                count,
+                HEADLESS_DRAIN_TIME / 1000
+            ),
+        }
+        return save_outputs(&options, &mut viewer);
+    }
+
+    #[cfg(feature = "sdl")]
+    window::run(&mut viewer, &mut replay, &mut departures)?;
+    save_outputs(&options, &mut viewer)
+}
+
+// Write the recording, SUMO export, time-space diagram, conflict log and the
+// rest of the trajectories asked for
+fn save_outputs(options: &Options, viewer: &mut Viewer) -> Result<(), String> {
+    if let Some(path) = &options.record {
+        viewer
+            .recorder
+            .save(path)
+            .map_err(|e| format!("Failed to save recording to {}: {}", path, e))?;
+        println!("Recording saved to {}", path);
+    }
+
+    if let (Some(prefix), Some(log)) = (&options.sumo_export, &viewer.demand_log) {
+        log.export(&viewer.network.intersections[0], prefix)
+            .map_err(|e| format!("Failed to export SUMO files to {}: {}", prefix, e))?;
+        println!("SUMO network and routes saved to {}.net.xml and {}.rou.xml", prefix, prefix);
+    }
+
+    if let (Some(path), Some(diagram)) = (&options.time_space, &viewer.time_space) {
+        diagram
+            .save(path)
+            .map_err(|e| format!("Failed to save time-space diagram to {}: {}", path, e))?;
+        println!("Time-space diagram saved to {}", path);
+    }
+
+    if let (Some(path), Some(writer)) = (&options.trajectories, viewer.trajectories.take()) {
+        writer
+            .finish()
+            .map_err(|e| format!("Failed to save trajectories to {}: {}", path, e))?;
+        println!("Trajectories saved to {}", path);
+    }
 
-        // Cap FPS based on simulation speed
-        std::thread::sleep(Duration::new(0, (1_000_000_000f64 / (60.0 * simulation_speed as f64)) as u32));
+    if let (Some(path), Some(monitor)) = (&options.conflicts, &mut viewer.safety) {
+        monitor.finish();
+        monitor
+            .save(path)
+            .map_err(|e| format!("Failed to save conflicts to {}: {}", path, e))?;
+        let counts: Vec<String> = monitor
+            .counts()
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind.name()))
+            .collect();
+        println!("{} conflicts ({}) saved to {}", monitor.events.len(), counts.join(", "), path);
     }
 
     Ok(())
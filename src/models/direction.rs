@@ -1,3 +1,5 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
 // Direction enum
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
@@ -6,3 +8,36 @@ pub enum Direction {
     East,
     West,
 }
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::South, Direction::East, Direction::West];
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
+
+    // Heading in radians, clockwise on screen from east
+    pub fn heading(self) -> f32 {
+        match self {
+            Direction::East => 0.0,
+            Direction::South => FRAC_PI_2,
+            Direction::West => PI,
+            Direction::North => 3.0 * FRAC_PI_2,
+        }
+    }
+
+    // Compass direction closest to a heading
+    pub fn nearest(heading: f32) -> Direction {
+        match (heading.rem_euclid(TAU) / FRAC_PI_2).round() as u32 % 4 {
+            0 => Direction::East,
+            1 => Direction::South,
+            2 => Direction::West,
+            _ => Direction::North,
+        }
+    }
+}
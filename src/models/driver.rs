@@ -0,0 +1,219 @@
+use std::f32::consts::TAU;
+
+use rand::Rng;
+
+use crate::constants::{LATE_BRAKING_MARGIN, TICKS_PER_SECOND, VEHICLE_SAFE_DISTANCE, VEHICLE_SPEED};
+
+// Desired speeds are kept within these fractions of the nominal speed
+const MIN_SPEED_FACTOR: f32 = 0.5;
+const MAX_SPEED_FACTOR: f32 = 1.5;
+
+// Slowest drivers speed up, in metres per second squared
+const MIN_ACCELERATION: f32 = 0.5;
+
+// How one driver behaves, drawn from a `DriverPopulation` when their vehicle
+// enters the network and kept until it leaves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriverProfile {
+    pub reaction_ticks: u64,  // Perception-reaction time
+    pub aggressiveness: f32,  // From 0, cautious, to 1, aggressive
+    pub compliance: f32,      // Probability of stopping for a red light at each intersection
+    pub desired_speed: f32,   // Metres per second
+    pub acceleration: f32,    // Moving off from a stop, in metres per second squared
+}
+
+impl Default for DriverProfile {
+    // A driver who reacts instantly, keeps the full safe distance, always
+    // stops for red, drives at the nominal speed and gets up to it at once
+    fn default() -> Self {
+        DriverProfile {
+            reaction_ticks: 0,
+            aggressiveness: 0.0,
+            compliance: 1.0,
+            desired_speed: VEHICLE_SPEED,
+            acceleration: f32::INFINITY,
+        }
+    }
+}
+
+impl DriverProfile {
+    // Center-to-center distance at which the driver stops behind the vehicle
+    // ahead; aggressive drivers brake late and stop closer
+    pub fn following_distance(&self) -> f32 {
+        VEHICLE_SAFE_DISTANCE - self.aggressiveness * LATE_BRAKING_MARGIN
+    }
+}
+
+// Spread of driver behaviour in the traffic entering the network
+//
+// Reaction time, aggressiveness, desired speed and acceleration are drawn
+// from normal distributions, clamped to sensible ranges. Every driver shares
+// the population's compliance; whether they stop for red is drawn anew at
+// each intersection.
+//
+// Reaction time and acceleration set how a queue discharges: each driver
+// moves off a reaction time after the vehicle ahead has pulled away, so
+// vehicles that cross the stop line at full speed do so a reaction time
+// plus about a second apart, and the time the first few lose getting up to
+// speed is the start-up lost time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriverPopulation {
+    pub reaction: f32,          // Mean, in seconds
+    pub reaction_sd: f32,       // Seconds
+    pub aggressiveness: f32,    // Mean, from 0 to 1
+    pub aggressiveness_sd: f32,
+    pub compliance: f32,        // Probability of stopping for red
+    pub speed_sd: f32,          // Of the desired speed around the nominal one, in metres per second
+    pub acceleration: f32,      // Mean, in metres per second squared; infinite to get up to speed at once
+    pub acceleration_sd: f32,
+}
+
+impl Default for DriverPopulation {
+    // Every driver behaves as `DriverProfile::default`
+    fn default() -> Self {
+        DriverPopulation {
+            reaction: 0.0,
+            reaction_sd: 0.0,
+            aggressiveness: 0.0,
+            aggressiveness_sd: 0.0,
+            compliance: 1.0,
+            speed_sd: 0.0,
+            acceleration: f32::INFINITY,
+            acceleration_sd: 0.0,
+        }
+    }
+}
+
+// Sample of a normal distribution, by the Box-Muller transform
+fn normal(rng: &mut impl Rng, mean: f32, sd: f32) -> f32 {
+    if sd <= 0.0 {
+        return mean;
+    }
+    let u: f32 = 1.0 - rng.gen::<f32>(); // In (0, 1], so the logarithm is finite
+    let v: f32 = rng.gen();
+    mean + sd * (-2.0 * u.ln()).sqrt() * (TAU * v).cos()
+}
+
+impl DriverPopulation {
+    // Population from a preset name, `key=value` settings, or a preset
+    // followed by settings overriding it, separated by commas
+    //
+    // Presets are `ideal`, the default, `typical` and `aggressive`. Keys are
+    // `reaction` and `reaction-sd` in seconds, `aggressiveness`,
+    // `aggressiveness-sd`, `compliance`, `speed-sd` in metres per second and
+    // `acceleration` and `acceleration-sd` in metres per second squared.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut population = DriverPopulation::default();
+        for (index, item) in spec.split(',').map(str::trim).enumerate() {
+            let Some((key, value)) = item.split_once('=') else {
+                population = match item {
+                    "ideal" if index == 0 => DriverPopulation::default(),
+                    "typical" if index == 0 => DriverPopulation::typical(),
+                    "aggressive" if index == 0 => DriverPopulation::aggressive(),
+                    _ => return Err(format!("Unknown driver preset '{}', expected ideal, typical or aggressive", item)),
+                };
+                continue;
+            };
+            let value: f32 = value
+                .parse()
+                .ok()
+                .filter(|value: &f32| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("Invalid driver setting '{}', expected a finite non-negative number", item))?;
+            match key {
+                "reaction" => population.reaction = value,
+                "reaction-sd" => population.reaction_sd = value,
+                "aggressiveness" | "aggressiveness-sd" | "compliance" if value > 1.0 => {
+                    return Err(format!("Invalid driver setting '{}', expected at most 1", item))
+                }
+                "aggressiveness" => population.aggressiveness = value,
+                "aggressiveness-sd" => population.aggressiveness_sd = value,
+                "compliance" => population.compliance = value,
+                "speed-sd" => population.speed_sd = value,
+                "acceleration" if value == 0.0 => {
+                    return Err(format!("Invalid driver setting '{}', expected a positive acceleration", item))
+                }
+                "acceleration" => population.acceleration = value,
+                "acceleration-sd" => population.acceleration_sd = value,
+                _ => return Err(format!("Unknown driver setting '{}'", key)),
+            }
+        }
+        Ok(population)
+    }
+
+    // Settings in the form `parse` reads, every one spelled out except an
+    // infinite acceleration, which is the default and can not be given
+    pub fn to_spec(&self) -> String {
+        let mut spec = format!(
+            "reaction={},reaction-sd={},aggressiveness={},aggressiveness-sd={},compliance={},speed-sd={}",
+            self.reaction, self.reaction_sd, self.aggressiveness, self.aggressiveness_sd, self.compliance, self.speed_sd
+        );
+        if self.acceleration.is_finite() {
+            spec.push_str(&format!(",acceleration={}", self.acceleration));
+        }
+        spec.push_str(&format!(",acceleration-sd={}", self.acceleration_sd));
+        spec
+    }
+
+    // Drivers as commonly observed in urban traffic
+    pub fn typical() -> Self {
+        DriverPopulation {
+            reaction: 0.9,
+            reaction_sd: 0.3,
+            aggressiveness: 0.3,
+            aggressiveness_sd: 0.2,
+            compliance: 0.97,
+            speed_sd: 1.5,
+            acceleration: 2.0,
+            acceleration_sd: 0.5,
+        }
+    }
+
+    // Hurried drivers who often run red lights and follow closely
+    pub fn aggressive() -> Self {
+        DriverPopulation {
+            reaction: 0.8,
+            reaction_sd: 0.2,
+            aggressiveness: 0.7,
+            aggressiveness_sd: 0.2,
+            compliance: 0.85,
+            speed_sd: 2.5,
+            acceleration: 3.0,
+            acceleration_sd: 0.5,
+        }
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> DriverProfile {
+        let reaction = normal(rng, self.reaction, self.reaction_sd).max(0.0);
+        let aggressiveness = normal(rng, self.aggressiveness, self.aggressiveness_sd).clamp(0.0, 1.0);
+        let desired_speed = normal(rng, VEHICLE_SPEED, self.speed_sd)
+            .clamp(MIN_SPEED_FACTOR * VEHICLE_SPEED, MAX_SPEED_FACTOR * VEHICLE_SPEED);
+        let acceleration = normal(rng, self.acceleration, self.acceleration_sd).max(MIN_ACCELERATION);
+        DriverProfile {
+            reaction_ticks: (reaction * TICKS_PER_SECOND as f32).round() as u64,
+            aggressiveness,
+            compliance: self.compliance,
+            desired_speed,
+            acceleration,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_settings_that_are_not_finite() {
+        assert!(DriverPopulation::parse("reaction=inf").is_err());
+        assert!(DriverPopulation::parse("typical,speed-sd=NaN").is_err());
+        assert!(DriverPopulation::parse("acceleration=-1").is_err());
+    }
+
+    #[test]
+    fn spec_reads_back_as_the_same_population() {
+        let instant = DriverPopulation::parse("reaction=1.2,compliance=0.9").unwrap();
+        assert_eq!(DriverPopulation::parse(&instant.to_spec()), Ok(instant));
+        let typical = DriverPopulation::typical();
+        assert_eq!(DriverPopulation::parse(&typical.to_spec()), Ok(typical));
+    }
+}
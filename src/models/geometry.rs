@@ -0,0 +1,104 @@
+// Axis-aligned rectangle in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl WorldRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        WorldRect { x, y, width, height }
+    }
+
+    pub fn from_center(center: (f32, f32), width: f32, height: f32) -> Self {
+        WorldRect::new(center.0 - width / 2.0, center.1 - height / 2.0, width, height)
+    }
+
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        point.0 >= self.x
+            && point.0 < self.x + self.width
+            && point.1 >= self.y
+            && point.1 < self.y + self.height
+    }
+}
+
+// Path through a sequence of points, walked by distance from the first one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<(f32, f32)>,
+    distances: Vec<f32>, // From the first point to each point
+}
+
+impl Polyline {
+    pub fn new(points: Vec<(f32, f32)>) -> Self {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (index, point) in points.iter().enumerate() {
+            if index > 0 {
+                total += distance(points[index - 1], *point);
+            }
+            distances.push(total);
+        }
+        Polyline { points, distances }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    // Point a distance along the path and the heading there, in radians
+    // clockwise on screen from east; past either end the end segment is
+    // extended
+    pub fn at(&self, along: f32) -> ((f32, f32), f32) {
+        let segment = self
+            .distances
+            .partition_point(|&d| d <= along)
+            .clamp(1, self.points.len().max(2) - 1);
+        let (start, end) = (self.points[segment - 1], self.points[segment]);
+        let length = self.distances[segment] - self.distances[segment - 1];
+        let fraction = if length > 0.0 {
+            (along - self.distances[segment - 1]) / length
+        } else {
+            0.0
+        };
+        let heading = (end.1 - start.1).atan2(end.0 - start.0);
+        (
+            (start.0 + (end.0 - start.0) * fraction, start.1 + (end.1 - start.1) * fraction),
+            heading,
+        )
+    }
+
+    // Points at most `step` apart between two distances along the path
+    pub fn sample(&self, from: f32, to: f32, step: f32) -> Vec<(f32, f32)> {
+        let count = ((to - from) / step).ceil().max(1.0) as usize;
+        (0..=count)
+            .map(|index| self.at(from + (to - from) * index as f32 / count as f32).0)
+            .collect()
+    }
+}
+
+// Whether two convex polygons overlap, by looking for an edge of either one
+// that separates them
+pub fn polygons_overlap(a: &[(f32, f32)], b: &[(f32, f32)]) -> bool {
+    let separates = |polygon: &[(f32, f32)], index: usize| {
+        let (x1, y1) = polygon[index];
+        let (x2, y2) = polygon[(index + 1) % polygon.len()];
+        let axis = (y1 - y2, x2 - x1);
+        let project = |points: &[(f32, f32)]| {
+            points
+                .iter()
+                .map(|&(x, y)| x * axis.0 + y * axis.1)
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), value| (low.min(value), high.max(value)))
+        };
+        let (low_a, high_a) = project(a);
+        let (low_b, high_b) = project(b);
+        high_a <= low_b || high_b <= low_a
+    };
+    !(0..a.len()).any(|index| separates(a, index)) && !(0..b.len()).any(|index| separates(b, index))
+}
+
+pub fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
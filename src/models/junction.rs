@@ -0,0 +1,423 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+
+use crate::constants::{LANE_WIDTH, MAX_LEGS, MIN_LEGS, MIN_LEG_ANGLE, ROAD_WIDTH, VEHICLE_LENGTH};
+use crate::models::direction::Direction;
+use crate::models::geometry::{distance, Polyline};
+use crate::models::route::Route;
+
+// Number of straight pieces a turn through the junction is drawn with
+const CURVE_SEGMENTS: usize = 16;
+
+// Spacing of the points compared when looking for crossing paths, and how
+// close two paths may come before vehicles on them could touch, in metres
+const CONFLICT_STEP: f32 = 0.25;
+const CONFLICT_CLEARANCE: f32 = 3.0;
+
+// Named shapes accepted by `JunctionGeometry::parse`, as leg angles in degrees
+const PRESETS: [(&str, &[f32]); 4] = [
+    ("cross", &[0.0, 90.0, 180.0, 270.0]),
+    ("tee", &[0.0, 90.0, 180.0]),          // Stem to the south
+    ("wye", &[30.0, 150.0, 270.0]),        // Stem to the north
+    ("five", &[0.0, 70.0, 150.0, 215.0, 290.0]),
+];
+
+// Road meeting a junction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    pub angle: f32,    // Heading from the center outwards, in radians clockwise on screen from east
+    pub length: f32,   // From the center to the far end, in metres
+    pub entry: bool,   // Traffic may drive in by the leg
+    pub exit: bool,    // Traffic may drive out by the leg
+    pub priority: u32, // Importance of the road, deciding the major road where there are no lights
+}
+
+impl Leg {
+    // Two-way leg of ordinary importance
+    pub fn new(angle: f32, length: f32) -> Self {
+        Leg {
+            angle,
+            length,
+            entry: true,
+            exit: true,
+            priority: 0,
+        }
+    }
+
+    // Heading of traffic driving in towards the center
+    pub fn inbound(&self) -> f32 {
+        (self.angle + PI).rem_euclid(TAU)
+    }
+
+    // Point `along` metres out from the center and `across` metres to the
+    // right of the leg looking outwards, relative to the center
+    pub fn point(&self, along: f32, across: f32) -> (f32, f32) {
+        let (sin, cos) = self.angle.sin_cos();
+        (along * cos - across * sin, along * sin + across * cos)
+    }
+
+    // Point in the middle of the lane in towards the center or out from it;
+    // traffic keeps to the right
+    pub fn lane_point(&self, along: f32, inbound: bool) -> (f32, f32) {
+        let across = if inbound { -LANE_WIDTH / 2.0 } else { LANE_WIDTH / 2.0 };
+        self.point(along, across)
+    }
+
+    // Corners of the strip from `from` to `to` metres out, `width` wide and
+    // centered `across` metres to the right of the leg
+    pub fn strip(&self, from: f32, to: f32, across: f32, width: f32) -> [(f32, f32); 4] {
+        let (left, right) = (across - width / 2.0, across + width / 2.0);
+        [
+            self.point(from, left),
+            self.point(to, left),
+            self.point(to, right),
+            self.point(from, right),
+        ]
+    }
+}
+
+// Pair of legs a vehicle drives in by and out by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Movement {
+    pub from: usize,
+    pub to: usize,
+}
+
+// Path of a movement through a junction, relative to its center, together
+// with the distances along it that traffic control works with
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovementPath {
+    pub movement: Movement,
+    pub turn: Route,
+    pub path: Polyline,  // From the far end of the entry leg to a vehicle length past the end of the exit leg
+    pub stop_at: f32,    // Where a waiting vehicle's center is, with its front at the stop line
+    pub clear_at: f32,   // Where a vehicle's rear has left the junction
+    pub ring: Option<(f32, f32)>, // Stretch on a roundabout's circulating lane
+    pub conflicts: Vec<Movement>, // Movements whose paths cross or merge with this one
+}
+
+// Legs of a junction, sorted by angle
+//
+// Movements are derived from pairs of legs: traffic can go from any leg it
+// may enter by to any other it may leave by, unless the turn is banned.
+// Whether a movement is a left or right turn or straight on follows from the
+// angle between the two legs. Each leg's stop line is set back from the
+// center far enough that roads meeting at a narrow angle do not overlap
+// before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JunctionGeometry {
+    pub legs: Vec<Leg>,
+    pub banned: Vec<Movement>, // Turn restrictions
+}
+
+impl JunctionGeometry {
+    // Four-way crossing with legs east, south, west and north
+    pub fn cross(length: f32) -> Self {
+        Self::from_degrees(PRESETS[0].1, length).expect("Cross preset is valid")
+    }
+
+    // Legs at angles in degrees clockwise on screen from east, all of one length
+    pub fn from_degrees(degrees: &[f32], length: f32) -> Result<Self, String> {
+        let legs = degrees
+            .iter()
+            .map(|degrees| Leg::new(degrees.rem_euclid(360.0).to_radians(), length))
+            .collect();
+        Self::from_legs(legs)
+    }
+
+    // Junction of the given legs, sorted by angle, without banned turns
+    pub fn from_legs(mut legs: Vec<Leg>) -> Result<Self, String> {
+        if !(MIN_LEGS..=MAX_LEGS).contains(&legs.len()) {
+            return Err(format!("A junction needs {} to {} legs, got {}", MIN_LEGS, MAX_LEGS, legs.len()));
+        }
+        legs.sort_by(|a, b| a.angle.total_cmp(&b.angle));
+        for (index, leg) in legs.iter().enumerate() {
+            let angle = leg.angle.to_degrees();
+            let next = legs.get(index + 1).map_or(legs[0].angle.to_degrees() + 360.0, |next| next.angle.to_degrees());
+            if next - angle < MIN_LEG_ANGLE - 1e-3 {
+                return Err(format!("Legs at {} and {} degrees are less than {} degrees apart", angle, next % 360.0, MIN_LEG_ANGLE));
+            }
+        }
+
+        Ok(JunctionGeometry {
+            legs,
+            banned: Vec::new(),
+        })
+    }
+
+    // A preset name (cross, tee, wye, five) or comma-separated leg angles in degrees
+    pub fn parse(text: &str, length: f32) -> Result<Self, String> {
+        if let Some((_, degrees)) = PRESETS.iter().find(|(name, _)| *name == text) {
+            return Self::from_degrees(degrees, length);
+        }
+        let degrees = text
+            .split(',')
+            .map(|angle| angle.trim().parse())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| format!("Invalid legs '{}', expected cross, tee, wye, five or angles in degrees", text))?;
+        Self::from_degrees(&degrees, length)
+    }
+
+    // Leg angles in degrees, as accepted by `from_degrees`
+    pub fn degrees(&self) -> Vec<f32> {
+        self.legs
+            .iter()
+            .map(|leg| (leg.angle.to_degrees() * 100.0).round() / 100.0)
+            .collect()
+    }
+
+    pub fn movements(&self) -> impl Iterator<Item = Movement> + '_ {
+        (0..self.legs.len()).flat_map(move |from| self.exits(from).into_iter().map(move |to| Movement { from, to }))
+    }
+
+    // Legs traffic coming in by a leg can leave by, from the sharpest left
+    // turn to the sharpest right turn
+    pub fn exits(&self, from: usize) -> Vec<usize> {
+        let mut exits: Vec<usize> = (0..self.legs.len())
+            .filter(|&to| {
+                to != from
+                    && self.legs[from].entry
+                    && self.legs[to].exit
+                    && !self.banned.contains(&Movement { from, to })
+            })
+            .collect();
+        exits.sort_by(|&a, &b| {
+            self.deflection(Movement { from, to: a })
+                .total_cmp(&self.deflection(Movement { from, to: b }))
+        });
+        exits
+    }
+
+    // Signed change of heading over a movement, positive turning right
+    pub fn deflection(&self, movement: Movement) -> f32 {
+        let change = self.legs[movement.to].angle - self.legs[movement.from].inbound();
+        (change + PI).rem_euclid(TAU) - PI
+    }
+
+    pub fn turn(&self, movement: Movement) -> Route {
+        let deflection = self.deflection(movement);
+        if deflection.abs() < FRAC_PI_4 {
+            Route::Straight
+        } else if deflection > 0.0 {
+            Route::Right
+        } else {
+            Route::Left
+        }
+    }
+
+    // Exit closest to a plain left turn, straight on or right turn, if any
+    // exit counts as that route
+    pub fn exit_for(&self, from: usize, route: Route) -> Option<usize> {
+        let ideal = match route {
+            Route::Left => -FRAC_PI_2,
+            Route::Straight => 0.0,
+            Route::Right => FRAC_PI_2,
+        };
+        let off_ideal = |to: usize| (self.deflection(Movement { from, to }) - ideal).abs();
+        self.exits(from)
+            .into_iter()
+            .filter(|&to| self.turn(Movement { from, to }) == route)
+            .min_by(|&a, &b| off_ideal(a).total_cmp(&off_ideal(b)))
+    }
+
+    // Exit with the smallest change of heading
+    pub fn straightest_exit(&self, from: usize) -> usize {
+        let turn = |to: usize| self.deflection(Movement { from, to }).abs();
+        self.exits(from)
+            .into_iter()
+            .min_by(|&a, &b| turn(a).total_cmp(&turn(b)))
+            .unwrap_or(from)
+    }
+
+    // Leg by which traffic travelling in a direction comes in most directly
+    pub fn leg_from(&self, direction: Direction) -> usize {
+        let off = |leg: &Leg| {
+            let difference = (leg.inbound() - direction.heading()).rem_euclid(TAU);
+            difference.min(TAU - difference)
+        };
+        (0..self.legs.len())
+            .min_by(|&a, &b| off(&self.legs[a]).total_cmp(&off(&self.legs[b])))
+            .unwrap_or(0)
+    }
+
+    // Leg that leaves the center in a compass direction, if there is one
+    pub fn leg_towards(&self, direction: Direction) -> Option<usize> {
+        self.legs
+            .iter()
+            .position(|leg| angle_difference(leg.angle, direction.heading()) < 1f32.to_radians())
+    }
+
+    // Distance from the center to a leg's stop line
+    pub fn stop_line(&self, leg: usize) -> f32 {
+        let count = self.legs.len();
+        let angle = self.legs[leg].angle;
+        let neighbours = [self.legs[(leg + 1) % count].angle, self.legs[(leg + count - 1) % count].angle];
+        neighbours
+            .into_iter()
+            .map(|other| {
+                // Road edges of legs meeting at an angle cross this far out
+                let between = angle_difference(angle, other);
+                if between < PI {
+                    ROAD_WIDTH / 2.0 / (between / 2.0).tan()
+                } else {
+                    0.0
+                }
+            })
+            .fold(ROAD_WIDTH / 2.0, f32::max)
+    }
+
+    // Path of a movement across the junction, curving from the stop line of
+    // the entry leg to the edge of the junction on the exit leg
+    pub fn path(&self, movement: Movement) -> MovementPath {
+        let (entry, exit) = (&self.legs[movement.from], &self.legs[movement.to]);
+        let (entry_stop, exit_stop) = (self.stop_line(movement.from), self.stop_line(movement.to));
+        let start = entry.lane_point(entry_stop, true);
+        let end = exit.lane_point(exit_stop, false);
+
+        let mut points = vec![entry.lane_point(entry.length, true)];
+        points.extend(curve(start, entry.inbound(), end, exit.angle));
+        points.push(exit.lane_point(exit.length + VEHICLE_LENGTH, false));
+        let path = Polyline::new(points);
+
+        let approach = entry.length - entry_stop;
+        let across = path.length() - VEHICLE_LENGTH - (exit.length - exit_stop);
+        MovementPath {
+            movement,
+            turn: self.turn(movement),
+            path,
+            stop_at: approach - VEHICLE_LENGTH / 2.0,
+            clear_at: across + VEHICLE_LENGTH / 2.0,
+            ring: None,
+            conflicts: Vec::new(),
+        }
+    }
+
+    // Paths of every movement, with the movements each one conflicts with
+    pub fn paths(&self) -> Vec<MovementPath> {
+        let mut paths: Vec<MovementPath> = self.movements().map(|movement| self.path(movement)).collect();
+        let inside: Vec<Vec<(f32, f32)>> = paths
+            .iter()
+            .map(|path| {
+                path.path
+                    .sample(path.stop_at + VEHICLE_LENGTH / 2.0, path.clear_at - VEHICLE_LENGTH / 2.0, CONFLICT_STEP)
+            })
+            .collect();
+
+        for a in 0..paths.len() {
+            for b in 0..paths.len() {
+                let (first, second) = (paths[a].movement, paths[b].movement);
+                let conflict = first.from != second.from
+                    && (first.to == second.to
+                        || inside[a]
+                            .iter()
+                            .any(|&p| inside[b].iter().any(|&q| distance(p, q) < CONFLICT_CLEARANCE)));
+                if conflict {
+                    paths[a].conflicts.push(second);
+                }
+            }
+        }
+        paths
+    }
+
+    // Groups of legs that get green together: legs roughly opposite each
+    // other share a phase, any other leg gets one of its own
+    //
+    // Phases run clockwise starting from the one with the leg closest to
+    // north, so a crossing has North-South green first. Legs traffic can not
+    // enter by get no phase.
+    pub fn signal_phases(&self) -> Vec<Vec<usize>> {
+        let entries: Vec<usize> = (0..self.legs.len()).filter(|&leg| self.legs[leg].entry).collect();
+        let mut pairs: Vec<(f32, usize, usize)> = Vec::new();
+        for (index, &a) in entries.iter().enumerate() {
+            for &b in &entries[index + 1..] {
+                let off_opposite = PI - angle_difference(self.legs[a].angle, self.legs[b].angle);
+                if off_opposite < FRAC_PI_4 {
+                    pairs.push((off_opposite, a, b));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut phased = vec![false; self.legs.len()];
+        let mut phases = Vec::new();
+        for (_, a, b) in pairs {
+            if !phased[a] && !phased[b] {
+                phased[a] = true;
+                phased[b] = true;
+                phases.push(vec![a, b]);
+            }
+        }
+        phases.extend(entries.into_iter().filter(|&leg| !phased[leg]).map(|leg| vec![leg]));
+
+        let from_north = |leg: usize| (self.legs[leg].angle - Direction::North.heading()).rem_euclid(TAU);
+        for phase in &mut phases {
+            phase.sort_by(|&a, &b| from_north(a).total_cmp(&from_north(b)));
+        }
+        phases.sort_by(|a, b| from_north(a[0]).total_cmp(&from_north(b[0])));
+        phases
+    }
+
+    // Compass name of the direction a leg leaves the center in
+    pub fn leg_name(&self, leg: usize) -> &'static str {
+        const NAMES: [&str; 8] = [
+            "East", "Southeast", "South", "Southwest", "West", "Northwest", "North", "Northeast",
+        ];
+        NAMES[(self.legs[leg].angle / FRAC_PI_4).round() as usize % 8]
+    }
+
+    // Short name for the traffic coming in by a leg: its compass direction
+    // of travel (NB, SB, EB, WB) on a leg along a compass axis, otherwise the
+    // leg number
+    pub fn approach_label(&self, leg: usize) -> String {
+        let inbound = self.legs[leg].inbound();
+        match Direction::ALL
+            .into_iter()
+            .find(|direction| angle_difference(inbound, direction.heading()) < 1f32.to_radians())
+        {
+            Some(Direction::North) => "NB".to_string(),
+            Some(Direction::South) => "SB".to_string(),
+            Some(Direction::East) => "EB".to_string(),
+            Some(Direction::West) => "WB".to_string(),
+            None => format!("L{}", leg),
+        }
+    }
+}
+
+// Smallest angle between two headings, from 0 to pi
+pub fn angle_difference(a: f32, b: f32) -> f32 {
+    let difference = (a - b).rem_euclid(TAU);
+    difference.min(TAU - difference)
+}
+
+// Quadratic curve from a point leaving at one heading to a point arriving at
+// another, bending at the corner where the two lines of travel meet
+fn curve(start: (f32, f32), start_heading: f32, end: (f32, f32), end_heading: f32) -> Vec<(f32, f32)> {
+    let (start_sin, start_cos) = start_heading.sin_cos();
+    let (end_sin, end_cos) = end_heading.sin_cos();
+    let middle = ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+
+    // Solve start + t * start_direction = end - u * end_direction
+    let determinant = end_cos * start_sin - start_cos * end_sin;
+    let corner = if determinant.abs() > 1e-3 {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let t = (end_cos * dy - end_sin * dx) / -determinant;
+        let u = (start_cos * dy - start_sin * dx) / -determinant;
+        if t > 0.0 && u > 0.0 {
+            (start.0 + t * start_cos, start.1 + t * start_sin)
+        } else {
+            middle
+        }
+    } else {
+        middle
+    };
+
+    (0..=CURVE_SEGMENTS)
+        .map(|index| {
+            let t = index as f32 / CURVE_SEGMENTS as f32;
+            let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * t * (1.0 - t), t * t);
+            (
+                a * start.0 + b * corner.0 + c * end.0,
+                a * start.1 + b * corner.1 + c * end.1,
+            )
+        })
+        .collect()
+}
@@ -1,4 +1,7 @@
 pub mod direction;
+pub mod driver;
+pub mod geometry;
+pub mod junction;
 pub mod route;
 pub mod traffic_light;
 pub mod vehicle;
@@ -5,3 +5,13 @@ pub enum Route {
     Straight,
     Right,
 }
+
+impl Route {
+    pub fn name(self) -> &'static str {
+        match self {
+            Route::Left => "left",
+            Route::Straight => "straight",
+            Route::Right => "right",
+        }
+    }
+}
@@ -1,10 +1,7 @@
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-
 use crate::constants::TRAFFIC_LIGHT_SIZE;
-use crate::models::direction::Direction;
+use crate::models::geometry::WorldRect;
+use crate::ui::camera::Camera;
+use crate::ui::surface::{Color, Surface};
 
 // Traffic light state
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -14,35 +11,41 @@ pub enum TrafficLightState {
 }
 
 // Traffic light struct
+#[derive(Debug, Clone)]
 pub struct TrafficLight {
-    pub position: Point,
+    pub position: (f32, f32), // Top-left corner in world coordinates
     pub state: TrafficLightState,
-    pub direction: Direction,
+    pub leg: usize, // Junction leg whose approach the light controls
+    pub changed_at: u64, // Tick the light last changed state
 }
 
 impl TrafficLight {
-    pub fn new(position: Point, direction: Direction) -> Self {
+    pub fn new(position: (f32, f32), leg: usize) -> Self {
         TrafficLight {
             position,
             state: TrafficLightState::Red,
-            direction,
+            leg,
+            changed_at: 0,
         }
     }
 
-    pub fn render(&self, canvas: &mut Canvas<Window>) {
+    // Area the light occupies in the world
+    pub fn bounds(&self) -> WorldRect {
+        WorldRect::new(
+            self.position.0,
+            self.position.1,
+            TRAFFIC_LIGHT_SIZE,
+            TRAFFIC_LIGHT_SIZE,
+        )
+    }
+
+    pub fn render(&self, canvas: &mut dyn Surface, camera: &Camera) {
         let color = match self.state {
             TrafficLightState::Red => Color::RGB(255, 0, 0),
             TrafficLightState::Green => Color::RGB(0, 255, 0),
         };
 
         canvas.set_draw_color(color);
-        canvas
-            .fill_rect(Rect::new(
-                self.position.x,
-                self.position.y,
-                TRAFFIC_LIGHT_SIZE,
-                TRAFFIC_LIGHT_SIZE,
-            ))
-            .expect("Failed to render traffic light");
+        canvas.fill_rect(camera.to_screen_rect(self.bounds()));
     }
 }
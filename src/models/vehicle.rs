@@ -1,282 +1,380 @@
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use std::collections::VecDeque;
+use std::f32::consts::FRAC_PI_3;
+use std::sync::Arc;
 
 use crate::constants::{
-    LANE_WIDTH, VEHICLE_HEIGHT, VEHICLE_SAFE_DISTANCE, VEHICLE_SPEED, VEHICLE_WIDTH, WINDOW_HEIGHT,
-    WINDOW_WIDTH,
+    CAR_FOLLOWING_RANGE, RED_RUNNING_TIME, TICKS_PER_SECOND, TICK_DURATION, VEHICLE_LENGTH, VEHICLE_WIDTH,
 };
 use crate::models::direction::Direction;
+use crate::models::driver::DriverProfile;
+use crate::models::geometry::WorldRect;
+use crate::models::junction::{angle_difference, MovementPath};
 use crate::models::route::Route;
 use crate::models::traffic_light::{TrafficLight, TrafficLightState};
+use crate::ui::camera::Camera;
+use crate::ui::shapes::fill_polygon;
+use crate::ui::surface::{Color, Surface};
+
+// Why a vehicle is not moving this tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    RedLight,
+    Leader(u32), // Id of the vehicle ahead that is too close
+    Conflict,    // A crossing vehicle is occupying the intersection
+    Spillback,   // The next intersection has no room on its approach
+    Yield,       // Giving way to traffic on a roundabout or at a stop sign
+    StopSign,    // Making a full stop at the line
+    Reacting,    // Free to go again, within the driver's reaction time
+}
+
+impl StopReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            StopReason::RedLight => "red light",
+            StopReason::Leader(_) => "leader",
+            StopReason::Conflict => "conflict",
+            StopReason::Spillback => "spillback",
+            StopReason::Yield => "yield",
+            StopReason::StopSign => "stop sign",
+            StopReason::Reacting => "reacting",
+        }
+    }
+}
+
+// Rectangle along a heading from `along.0` to `along.1` metres ahead of a
+// point, `half_width` to either side
+fn rectangle((x, y): (f32, f32), heading: f32, (from, to): (f32, f32), half_width: f32) -> [(f32, f32); 4] {
+    let (sin, cos) = heading.sin_cos();
+    let point = |along: f32, across: f32| (x + along * cos - across * sin, y + along * sin + across * cos);
+    [
+        point(from, -half_width),
+        point(to, -half_width),
+        point(to, half_width),
+        point(from, half_width),
+    ]
+}
+
+fn route_color(route: Route) -> Color {
+    match route {
+        Route::Left => Color::RGB(255, 255, 0),   // Yellow
+        Route::Straight => Color::RGB(0, 0, 255), // Blue
+        Route::Right => Color::RGB(0, 255, 255),  // Cyan
+    }
+}
 
 // Vehicle struct
+//
+// A vehicle follows the path of one movement through the junction it is
+// driving through, from the far end of the entry leg to just past the far
+// end of the exit leg. Its position is its distance along that path.
+//
+// Its driver cruises at their desired speed, slowing to that of a slower
+// vehicle not far ahead, and stops at the distance they keep from the
+// vehicle ahead. Once free to go again, they move off after their reaction
+// time and speed up at their acceleration. A red light that came on within
+// the driver's reaction time is run as if it were still green, and one that
+// has not been red for long also by drivers who do not comply at this
+// intersection.
 #[derive(Debug, Clone)]
 pub struct Vehicle {
-    pub position: (f32, f32),
-    pub direction: Direction,
-    pub route: Route,
+    pub id: u32,
+    pub position: (f32, f32), // Center, in world coordinates
+    pub heading: f32,         // Radians clockwise on screen from east
+    pub route: Route,         // How the current movement turns
     pub color: Color,
-    pub has_passed_intersection: bool,
+    pub stop_reason: Option<StopReason>,
+    pub waiting_ticks: u64, // Ticks spent stopped, i.e. the vehicle's delay so far
+    pub junction: (f32, f32), // Center of the intersection the vehicle is driving through
+    pub path: Arc<MovementPath>, // Way through that intersection, relative to its center
+    pub travelled: f32,         // Along the path, in metres
+    pub next_exits: VecDeque<usize>, // Exit legs at the intersections after this one
+    pub entry_waiting_ticks: u64, // `waiting_ticks` when the vehicle entered this intersection
+    pub driver: DriverProfile,
+    pub runs_red: bool, // The driver does not comply with red lights at this intersection
+    pub ran_red: bool,  // Crossed this intersection's stop line on red
+    pace: f32,          // Speed driven last tick while moving, in metres per second
+    reaction_left: u64, // Ticks before moving off after a stop
 }
 
 impl Vehicle {
-    pub fn new(direction: Direction, route: Route) -> Self {
-        let position = match direction {
-            Direction::North => (
-                WINDOW_WIDTH as f32 / 2.0 - LANE_WIDTH as f32 / 2.0,
-                WINDOW_HEIGHT as f32,
-            ),
-            Direction::South => (
-                WINDOW_WIDTH as f32 / 2.0 + LANE_WIDTH as f32 / 2.0,
-                0.0,
-            ),
-            Direction::East => (
-                0.0,
-                WINDOW_HEIGHT as f32 / 2.0 + LANE_WIDTH as f32 / 2.0,
-            ),
-            Direction::West => (
-                WINDOW_WIDTH as f32,
-                WINDOW_HEIGHT as f32 / 2.0 - LANE_WIDTH as f32 / 2.0,
-            ),
+    // Start at the far end of a movement's entry leg, at the intersection
+    // centered at `junction`
+    pub fn new(id: u32, junction: (f32, f32), path: Arc<MovementPath>, driver: DriverProfile) -> Self {
+        let mut vehicle = Vehicle {
+            id,
+            position: junction,
+            heading: 0.0,
+            route: path.turn,
+            color: route_color(path.turn),
+            stop_reason: None,
+            waiting_ticks: 0,
+            junction,
+            path,
+            travelled: 0.0,
+            next_exits: VecDeque::new(),
+            entry_waiting_ticks: 0,
+            driver,
+            runs_red: false,
+            ran_red: false,
+            pace: driver.desired_speed,
+            reaction_left: 0,
         };
+        vehicle.place();
+        vehicle
+    }
+
+    // Hand the vehicle over to the next intersection along its way, keeping
+    // the distance it has driven past the end of the previous one
+    pub fn enter_junction(&mut self, junction: (f32, f32), path: Arc<MovementPath>) {
+        self.travelled -= self.path.path.length() - VEHICLE_LENGTH;
+        self.junction = junction;
+        self.route = path.turn;
+        self.color = route_color(path.turn);
+        self.path = path;
+        self.entry_waiting_ticks = self.waiting_ticks;
+        self.ran_red = false;
+        self.place();
+    }
 
-        // Assign color based on route
-        let color = match route {
-            Route::Left => Color::RGB(255, 255, 0),   // Yellow
-            Route::Straight => Color::RGB(0, 0, 255), // Blue
-            Route::Right => Color::RGB(0, 255, 255),  // Cyan
+    // Ticks spent stopped since entering the current intersection
+    pub fn junction_delay_ticks(&self) -> u64 {
+        self.waiting_ticks - self.entry_waiting_ticks
+    }
+
+    // `exit_blocked` holds the vehicle at the end of its exit when the next
+    // intersection has no room for it
+    pub fn update(&mut self, tick: u64, traffic_lights: &[TrafficLight], vehicles: &[Vehicle], exit_blocked: bool) {
+        // Check if vehicle should stop at traffic light, for another vehicle
+        // ahead, for a crossing vehicle in the intersection, or for a full
+        // road beyond it, in that order
+        self.stop_reason = if self.should_stop_at_traffic_light(tick, traffic_lights) {
+            Some(StopReason::RedLight)
+        } else if let Some(leader) = self.should_stop_for_vehicle(vehicles) {
+            Some(StopReason::Leader(leader))
+        } else if self.should_stop_for_conflict(traffic_lights, vehicles) {
+            Some(StopReason::Conflict)
+        } else if exit_blocked {
+            Some(StopReason::Spillback)
+        } else {
+            None
         };
 
-        Vehicle {
-            position,
-            direction,
-            route,
-            color,
-            has_passed_intersection: false,
+        if self.is_in_stop_zone() && !self.is_stopped() && self.faces_red(traffic_lights) {
+            self.ran_red = true;
         }
+        self.act(vehicles);
     }
 
-    pub fn update(&mut self, traffic_lights: &[TrafficLight], vehicles: &[Vehicle]) {
-        // Check if vehicle should stop at traffic light
-        let should_stop_at_light = self.should_stop_at_traffic_light(traffic_lights);
-
-        // Check if vehicle should stop for another vehicle
-        let should_stop_for_vehicle = self.should_stop_for_vehicle(vehicles);
-
-        if !should_stop_at_light && !should_stop_for_vehicle {
-            // Move the vehicle based on its direction
-            match self.direction {
-                Direction::North => self.position.1 -= VEHICLE_SPEED,
-                Direction::South => self.position.1 += VEHICLE_SPEED,
-                Direction::East => self.position.0 += VEHICLE_SPEED,
-                Direction::West => self.position.0 -= VEHICLE_SPEED,
-            }
-
-            // Check if vehicle has passed the intersection
-            if !self.has_passed_intersection {
-                let intersection_center_x = WINDOW_WIDTH as f32 / 2.0;
-                let intersection_center_y = WINDOW_HEIGHT as f32 / 2.0;
-
-                match self.direction {
-                    Direction::North => {
-                        if self.position.1 < intersection_center_y {
-                            self.has_passed_intersection = true;
-                            self.change_direction_based_on_route();
-                        }
-                    }
-                    Direction::South => {
-                        if self.position.1 > intersection_center_y {
-                            self.has_passed_intersection = true;
-                            self.change_direction_based_on_route();
-                        }
-                    }
-                    Direction::East => {
-                        if self.position.0 > intersection_center_x {
-                            self.has_passed_intersection = true;
-                            self.change_direction_based_on_route();
-                        }
-                    }
-                    Direction::West => {
-                        if self.position.0 < intersection_center_x {
-                            self.has_passed_intersection = true;
-                            self.change_direction_based_on_route();
-                        }
-                    }
-                }
-            }
+    // Wait while stopped or still reacting to being free to go, otherwise
+    // drive on
+    pub fn act(&mut self, vehicles: &[Vehicle]) {
+        if self.is_stopped() {
+            self.pace = 0.0;
+            self.reaction_left = self.driver.reaction_ticks;
+        } else if self.reaction_left > 0 {
+            self.reaction_left -= 1;
+            self.stop_reason = Some(StopReason::Reacting);
+        } else {
+            let accelerated = self.pace + self.driver.acceleration * TICK_DURATION;
+            self.pace = accelerated.min(self.pace_behind(vehicles));
+            self.travelled += self.pace * TICK_DURATION;
+            self.place();
+            return;
         }
+        self.waiting_ticks += 1;
     }
 
-    fn change_direction_based_on_route(&mut self) {
-        let intersection_center_x = WINDOW_WIDTH as f32 / 2.0;
-        let intersection_center_y = WINDOW_HEIGHT as f32 / 2.0;
-        let lane_offset = LANE_WIDTH as f32 / 2.0;
-
-        match (self.direction, self.route) {
-            (Direction::North, Route::Left) => {
-                self.direction = Direction::West;
-                self.position.1 = intersection_center_y - lane_offset;
-            }
-            (Direction::North, Route::Right) => {
-                self.direction = Direction::East;
-                self.position.1 = intersection_center_y + lane_offset;
-            }
-            (Direction::South, Route::Left) => {
-                self.direction = Direction::East;
-                self.position.1 = intersection_center_y + lane_offset;
-            }
-            (Direction::South, Route::Right) => {
-                self.direction = Direction::West;
-                self.position.1 = intersection_center_y - lane_offset;
-            }
-            (Direction::East, Route::Left) => {
-                self.direction = Direction::North;
-                self.position.0 = intersection_center_x - lane_offset;
-            }
-            (Direction::East, Route::Right) => {
-                self.direction = Direction::South;
-                self.position.0 = intersection_center_x + lane_offset;
-            }
-            (Direction::West, Route::Left) => {
-                self.direction = Direction::South;
-                self.position.0 = intersection_center_x + lane_offset;
-            }
-            (Direction::West, Route::Right) => {
-                self.direction = Direction::North;
-                self.position.0 = intersection_center_x - lane_offset;
-            }
-            _ => {}, // Straight, no change
-        }
+    // Desired speed, or the speed of a slower moving vehicle close ahead
+    fn pace_behind(&self, vehicles: &[Vehicle]) -> f32 {
+        vehicles
+            .iter()
+            .filter(|other| other.id != self.id && !other.is_stopped())
+            .filter(|other| self.gap_to(other).is_some_and(|gap| gap > 0.0 && gap < CAR_FOLLOWING_RANGE))
+            .map(|other| other.pace)
+            .fold(self.driver.desired_speed, f32::min)
+    }
+
+    // Put the vehicle where its distance along the path takes it
+    fn place(&mut self) {
+        let ((x, y), heading) = self.path.path.at(self.travelled);
+        self.position = (self.junction.0 + x, self.junction.1 + y);
+        self.heading = heading;
+    }
+
+    // Compass direction closest to the direction of travel
+    pub fn direction(&self) -> Direction {
+        Direction::nearest(self.heading)
     }
 
-    fn should_stop_at_traffic_light(&self, traffic_lights: &[TrafficLight]) -> bool {
-        if self.has_passed_intersection {
+    // A moving driver runs a red light that came on too recently for them to
+    // react to, or not long ago if they do not comply with it
+    fn should_stop_at_traffic_light(&self, tick: u64, traffic_lights: &[TrafficLight]) -> bool {
+        if !self.is_in_stop_zone() || !self.faces_red(traffic_lights) {
             return false;
         }
+        let red_for = traffic_lights
+            .iter()
+            .filter(|light| light.leg == self.path.movement.from)
+            .map(|light| tick - light.changed_at)
+            .min()
+            .unwrap_or(u64::MAX);
+        let running_window = RED_RUNNING_TIME * TICKS_PER_SECOND / 1000;
+        let runs = red_for < self.driver.reaction_ticks || (self.runs_red && red_for < running_window);
+        self.is_stopped() || !runs
+    }
+
+    // The light on the vehicle's entry leg is red
+    fn faces_red(&self, traffic_lights: &[TrafficLight]) -> bool {
+        traffic_lights
+            .iter()
+            .any(|light| light.leg == self.path.movement.from && light.state == TrafficLightState::Red)
+    }
+
+    // Returns the id of the nearest vehicle ahead that is too close, if any
+    pub fn should_stop_for_vehicle(&self, vehicles: &[Vehicle]) -> Option<u32> {
+        self.leader(vehicles)
+            .filter(|&(_, gap)| gap < self.driver.following_distance())
+            .map(|(leader, _)| leader.id)
+    }
+
+    // Nearest vehicle ahead in the same lane, with the distance to it
+    pub fn leader<'a>(&self, vehicles: &'a [Vehicle]) -> Option<(&'a Vehicle, f32)> {
+        vehicles
+            .iter()
+            .filter(|other| other.id != self.id)
+            .filter_map(|other| self.gap_to(other).filter(|&gap| gap > 0.0).map(|gap| (other, gap)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    // Distance ahead to another vehicle in the same lane, negative if it is
+    // behind
+    //
+    // Vehicles that came in by the same leg of the current intersection are
+    // compared along the path while they share it. Any other vehicle counts
+    // when it is within a vehicle width of the line ahead and heading roughly
+    // the same way.
+    fn gap_to(&self, other: &Vehicle) -> Option<f32> {
+        let same_movement = self.path.movement == other.path.movement;
+        let same_entry = self.path.movement.from == other.path.movement.from && !self.has_entered();
+        if other.junction == self.junction && (same_movement || same_entry) {
+            return Some(other.travelled - self.travelled);
+        }
+
+        let (dx, dy) = (other.position.0 - self.position.0, other.position.1 - self.position.1);
+        let (sin, cos) = self.heading.sin_cos();
+        let across = dy * cos - dx * sin;
+        (across.abs() < VEHICLE_WIDTH && angle_difference(self.heading, other.heading) < FRAC_PI_3)
+            .then_some(dx * cos + dy * sin)
+    }
 
-        // Find the traffic light for this vehicle's direction
-        for light in traffic_lights {
-            if light.direction == self.direction {
-                if light.state == TrafficLightState::Red {
-                    // Calculate distance to intersection
-                    let intersection_center_x = WINDOW_WIDTH as f32 / 2.0;
-                    let intersection_center_y = WINDOW_HEIGHT as f32 / 2.0;
-
-                    match self.direction {
-                        Direction::North => {
-                            let distance_to_intersection = self.position.1 - intersection_center_y;
-                            if distance_to_intersection > 0.0
-                                && distance_to_intersection < VEHICLE_SAFE_DISTANCE
-                            {
-                                return true;
-                            }
-                        }
-                        Direction::South => {
-                            let distance_to_intersection = intersection_center_y - self.position.1;
-                            if distance_to_intersection > 0.0
-                                && distance_to_intersection < VEHICLE_SAFE_DISTANCE
-                            {
-                                return true;
-                            }
-                        }
-                        Direction::East => {
-                            let distance_to_intersection = intersection_center_x - self.position.0;
-                            if distance_to_intersection > 0.0
-                                && distance_to_intersection < VEHICLE_SAFE_DISTANCE
-                            {
-                                return true;
-                            }
-                        }
-                        Direction::West => {
-                            let distance_to_intersection = self.position.0 - intersection_center_x;
-                            if distance_to_intersection > 0.0
-                                && distance_to_intersection < VEHICLE_SAFE_DISTANCE
-                            {
-                                return true;
-                            }
-                        }
-                    }
-                }
-                break;
-            }
+    // Yield to crossing traffic that is already in the intersection
+    //
+    // Vehicles waiting at a red light are not in the way. Among vehicles that
+    // are both about to cross, the one spawned first goes first, so two
+    // vehicles never wait for each other.
+    fn should_stop_for_conflict(&self, traffic_lights: &[TrafficLight], vehicles: &[Vehicle]) -> bool {
+        if !self.is_in_stop_zone() {
+            return false;
         }
 
-        false
+        vehicles.iter().any(|other| {
+            other.id != self.id
+                && other.junction == self.junction
+                && self.path.conflicts.contains(&other.path.movement)
+                && (other.is_in_junction()
+                    || (other.is_in_stop_zone() && !other.faces_red(traffic_lights) && other.id < self.id))
+        })
+    }
+
+    // Position relative to the center of the current intersection
+    pub fn offset(&self) -> (f32, f32) {
+        (self.position.0 - self.junction.0, self.position.1 - self.junction.1)
+    }
+
+    // About to cross the stop line this tick, where a red light or crossing
+    // traffic stops it
+    pub fn is_in_stop_zone(&self) -> bool {
+        !self.has_entered() && self.travelled + self.driver.desired_speed * TICK_DURATION > self.path.stop_at
+    }
+
+    // Front past the stop line
+    pub fn has_entered(&self) -> bool {
+        self.travelled > self.path.stop_at
+    }
+
+    // Past the stop zone, with some of the vehicle still inside the junction
+    pub fn is_in_junction(&self) -> bool {
+        self.has_entered() && self.travelled < self.path.clear_at
+    }
+
+    // Still on the way to the stop line
+    pub fn is_approaching(&self) -> bool {
+        !self.has_entered()
+    }
+
+    pub fn distance_to(&self, other: &Vehicle) -> f32 {
+        let dx = self.position.0 - other.position.0;
+        let dy = self.position.1 - other.position.1;
+        (dx * dx + dy * dy).sqrt()
     }
 
-    fn should_stop_for_vehicle(&self, vehicles: &[Vehicle]) -> bool {
-        for other in vehicles {
-            // Skip self comparison
-            if std::ptr::eq(self, other) {
-                continue;
-            }
-
-            // Only check vehicles in the same direction and lane
-            if self.direction == other.direction {
-                match self.direction {
-                    Direction::North => {
-                        if (self.position.0 - other.position.0).abs() < VEHICLE_WIDTH as f32
-                            && self.position.1 > other.position.1
-                            && self.position.1 - other.position.1 < VEHICLE_SAFE_DISTANCE
-                        {
-                            return true;
-                        }
-                    }
-                    Direction::South => {
-                        if (self.position.0 - other.position.0).abs() < VEHICLE_WIDTH as f32
-                            && self.position.1 < other.position.1
-                            && other.position.1 - self.position.1 < VEHICLE_SAFE_DISTANCE
-                        {
-                            return true;
-                        }
-                    }
-                    Direction::East => {
-                        if (self.position.1 - other.position.1).abs() < VEHICLE_HEIGHT as f32
-                            && self.position.0 < other.position.0
-                            && other.position.0 - self.position.0 < VEHICLE_SAFE_DISTANCE
-                        {
-                            return true;
-                        }
-                    }
-                    Direction::West => {
-                        if (self.position.1 - other.position.1).abs() < VEHICLE_HEIGHT as f32
-                            && self.position.0 > other.position.0
-                            && self.position.0 - other.position.0 < VEHICLE_SAFE_DISTANCE
-                        {
-                            return true;
-                        }
-                    }
-                }
-            }
+    pub fn is_stopped(&self) -> bool {
+        self.stop_reason.is_some()
+    }
+
+    // Speed in metres per second
+    pub fn speed(&self) -> f32 {
+        if self.is_stopped() {
+            0.0
+        } else {
+            self.pace
         }
+    }
+
+    // Corners of the area the vehicle occupies, turned to its heading
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        self.box_ahead(-VEHICLE_LENGTH / 2.0, VEHICLE_LENGTH / 2.0, VEHICLE_WIDTH / 2.0)
+    }
 
-        false
+    // Center and heading once the vehicle has driven `distance` metres
+    // further along its path
+    pub fn pose_ahead(&self, distance: f32) -> ((f32, f32), f32) {
+        let ((x, y), heading) = self.path.path.at(self.travelled + distance);
+        ((self.junction.0 + x, self.junction.1 + y), heading)
     }
 
-    pub fn render(&self, canvas: &mut Canvas<Window>) {
-        canvas.set_draw_color(self.color);
+    // Corners of the area the vehicle occupies at a pose
+    pub fn corners_at(((x, y), heading): ((f32, f32), f32)) -> [(f32, f32); 4] {
+        rectangle((x, y), heading, (-VEHICLE_LENGTH / 2.0, VEHICLE_LENGTH / 2.0), VEHICLE_WIDTH / 2.0)
+    }
 
-        let (width, height) = match self.direction {
-            Direction::North | Direction::South => (VEHICLE_WIDTH, VEHICLE_HEIGHT),
-            Direction::East | Direction::West => (VEHICLE_HEIGHT, VEHICLE_WIDTH),
-        };
+    // Axis-aligned rectangle around the vehicle
+    pub fn bounds(&self) -> WorldRect {
+        let corners = self.corners();
+        let (mut left, mut top) = corners[0];
+        let (mut right, mut bottom) = corners[0];
+        for &(x, y) in &corners[1..] {
+            left = left.min(x);
+            right = right.max(x);
+            top = top.min(y);
+            bottom = bottom.max(y);
+        }
+        WorldRect::new(left, top, right - left, bottom - top)
+    }
+
+    // Area ahead of the vehicle where another vehicle makes it stop, matching
+    // the checks in `should_stop_for_vehicle`
+    pub fn safe_envelope(&self) -> [(f32, f32); 4] {
+        self.box_ahead(0.0, self.driver.following_distance(), VEHICLE_WIDTH)
+    }
 
-        canvas
-            .fill_rect(Rect::new(
-                self.position.0 as i32 - (width / 2) as i32,
-                self.position.1 as i32 - (height / 2) as i32,
-                width,
-                height,
-            ))
-            .expect("Failed to render vehicle");
+    // Rectangle along the heading from `from` to `to` metres ahead of the
+    // center, `half_width` to either side
+    fn box_ahead(&self, from: f32, to: f32, half_width: f32) -> [(f32, f32); 4] {
+        rectangle(self.position, self.heading, (from, to), half_width)
     }
 
-    pub fn is_out_of_bounds(&self) -> bool {
-        self.position.0 < -50.0
-            || self.position.0 > WINDOW_WIDTH as f32 + 50.0
-            || self.position.1 < -50.0
-            || self.position.1 > WINDOW_HEIGHT as f32 + 50.0
+    pub fn render(&self, canvas: &mut dyn Surface, camera: &Camera) {
+        fill_polygon(canvas, camera, &self.corners(), self.color);
     }
 }
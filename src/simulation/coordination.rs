@@ -0,0 +1,281 @@
+use crate::constants::{TICKS_PER_SECOND, TRAFFIC_LIGHT_CYCLE_TIME};
+use crate::models::direction::Direction;
+use crate::simulation::intersection::ms_to_ticks;
+use crate::simulation::network::Network;
+
+// Offsets tried by the optimiser are multiples of this many milliseconds
+const OFFSET_STEP: u64 = 100;
+
+// Shared cycle length and per-intersection offsets
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalPlan {
+    pub cycle_ticks: u64,
+    pub offsets: Vec<u64>, // Per intersection, tick within the cycle at which North-South green starts
+}
+
+impl SignalPlan {
+    // Default cycle with every intersection switching at the same time
+    pub fn uncoordinated(count: usize) -> Self {
+        SignalPlan {
+            cycle_ticks: 2 * ms_to_ticks(TRAFFIC_LIGHT_CYCLE_TIME),
+            offsets: vec![0; count],
+        }
+    }
+}
+
+// Intersections along the middle row of a network, or the middle column if
+// the grid is taller than it is wide
+#[derive(Debug, Clone)]
+pub struct Corridor {
+    pub intersections: Vec<usize>,
+    pub positions: Vec<f32>, // Distance from the first intersection, in metres
+    pub horizontal: bool,
+    pub origin: (f32, f32), // Center of the first intersection
+}
+
+impl Corridor {
+    pub fn of(network: &Network) -> Self {
+        let layout = &network.layout;
+        let horizontal = layout.columns >= layout.rows;
+        let intersections: Vec<usize> = if horizontal {
+            let row = layout.rows / 2;
+            (0..layout.columns).map(|column| row * layout.columns + column).collect()
+        } else {
+            let column = layout.columns / 2;
+            (0..layout.rows).map(|row| row * layout.columns + column).collect()
+        };
+        let positions = (0..intersections.len())
+            .map(|index| index as f32 * layout.link_length)
+            .collect();
+
+        Corridor {
+            origin: network.intersections[intersections[0]].center,
+            intersections,
+            positions,
+            horizontal,
+        }
+    }
+
+    // Direction of travel away from the first intersection
+    pub fn outbound(&self) -> Direction {
+        if self.horizontal {
+            Direction::East
+        } else {
+            Direction::South
+        }
+    }
+
+    // Distance of a world point along the corridor from the first
+    // intersection, and its distance across it
+    pub fn along(&self, point: (f32, f32)) -> (f32, f32) {
+        let (dx, dy) = (point.0 - self.origin.0, point.1 - self.origin.1);
+        if self.horizontal {
+            (dx, dy)
+        } else {
+            (dy, dx)
+        }
+    }
+
+    // Whether traffic in a direction drives along the corridor
+    pub fn serves(&self, direction: Direction) -> bool {
+        direction == self.outbound() || direction == self.outbound().opposite()
+    }
+
+    // Tick within the cycle at which the corridor's phase gets green, for an
+    // intersection offset
+    fn green_start(&self, offset: u64, cycle_ticks: u64) -> u64 {
+        if self.horizontal {
+            (offset + cycle_ticks / 2) % cycle_ticks
+        } else {
+            offset
+        }
+    }
+
+    // Intersection offset giving the corridor's phase green at a tick within the cycle
+    fn offset_for(&self, green_start: u64, cycle_ticks: u64) -> u64 {
+        if self.horizontal {
+            (green_start + cycle_ticks - cycle_ticks / 2) % cycle_ticks
+        } else {
+            green_start
+        }
+    }
+
+    // Ticks to drive from the first intersection to each of the others
+    fn travel_ticks(&self, speed: f32) -> Vec<u64> {
+        self.positions
+            .iter()
+            .map(|position| (position / speed * TICKS_PER_SECOND as f32).round() as u64)
+            .collect()
+    }
+}
+
+// Width of the band of green a platoon driving at the progression speed can
+// travel through without stopping, in ticks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bandwidth {
+    pub outbound: u64,
+    pub inbound: u64,
+}
+
+impl Bandwidth {
+    pub fn total(&self) -> u64 {
+        self.outbound + self.inbound
+    }
+}
+
+// Length of the intersection of green windows [start, start + green) taken
+// modulo the cycle, measured against the first window
+fn common_green(starts: &[u64], green: u64, cycle_ticks: u64) -> u64 {
+    let (mut low, mut high) = (0, green);
+    for &start in &starts[1..] {
+        let shift = (start + cycle_ticks - starts[0] % cycle_ticks) % cycle_ticks;
+        let (from, to) = if shift < green {
+            (shift, green)
+        } else if shift + green > cycle_ticks {
+            (0, shift + green - cycle_ticks)
+        } else {
+            return 0;
+        };
+        low = low.max(from);
+        high = high.min(to);
+        if low >= high {
+            return 0;
+        }
+    }
+    high - low
+}
+
+// Corridor bandwidth of a signal plan in each direction
+//
+// Every intersection's green is shifted back by the travel time from where
+// the platoon sets off, so the band is the part of the cycle in which all
+// shifted greens overlap.
+pub fn bandwidth(corridor: &Corridor, plan: &SignalPlan, speed: f32) -> Bandwidth {
+    let cycle = plan.cycle_ticks;
+    let green_starts: Vec<u64> = corridor
+        .intersections
+        .iter()
+        .map(|&index| corridor.green_start(plan.offsets[index], cycle))
+        .collect();
+    corridor_bandwidth(&green_starts, &corridor.travel_ticks(speed), cycle)
+}
+
+fn corridor_bandwidth(green_starts: &[u64], travel: &[u64], cycle_ticks: u64) -> Bandwidth {
+    let green = cycle_ticks / 2;
+    let outbound: Vec<u64> = green_starts
+        .iter()
+        .zip(travel)
+        .map(|(start, travel)| (start + cycle_ticks - travel % cycle_ticks) % cycle_ticks)
+        .collect();
+    let inbound: Vec<u64> = green_starts
+        .iter()
+        .zip(travel)
+        .map(|(start, travel)| (start + travel) % cycle_ticks)
+        .collect();
+
+    Bandwidth {
+        outbound: common_green(&outbound, green, cycle_ticks),
+        inbound: common_green(&inbound, green, cycle_ticks),
+    }
+}
+
+// Offsets along the corridor that maximise the bandwidth in both directions
+// together, for platoons driving at a progression speed in metres per second
+//
+// Starting from the offsets that are ideal for each direction alone, every
+// intersection's offset in turn is moved to whichever step of the cycle
+// widens the band most, until nothing improves. Intersections off the
+// corridor take the offset of the corridor intersection in the same column
+// (or row), so parallel roads get the same green wave.
+pub fn optimise_offsets(network: &Network, corridor: &Corridor, cycle_ticks: u64, speed: f32) -> SignalPlan {
+    let travel = corridor.travel_ticks(speed);
+    let step = ms_to_ticks(OFFSET_STEP).max(1);
+    let score = |starts: &[u64]| {
+        let band = corridor_bandwidth(starts, &travel, cycle_ticks);
+        (band.total(), band.outbound.min(band.inbound))
+    };
+
+    let ideal_outbound: Vec<u64> = travel.iter().map(|travel| travel % cycle_ticks).collect();
+    let ideal_inbound: Vec<u64> = travel
+        .iter()
+        .map(|travel| (cycle_ticks - travel % cycle_ticks) % cycle_ticks)
+        .collect();
+
+    let mut best = Vec::new();
+    for mut starts in [ideal_outbound, ideal_inbound] {
+        let mut improved = true;
+        while improved {
+            improved = false;
+            // The first intersection stays at zero, offsets only matter relative to it
+            for index in 1..starts.len() {
+                let mut candidate = starts.clone();
+                for start in (0..cycle_ticks).step_by(step as usize) {
+                    candidate[index] = start;
+                    if score(&candidate) > score(&starts) {
+                        starts[index] = start;
+                        improved = true;
+                    }
+                }
+            }
+        }
+        if best.is_empty() || score(&starts) > score(&best) {
+            best = starts;
+        }
+    }
+
+    let layout = &network.layout;
+    let offsets = (0..network.intersections.len())
+        .map(|index| {
+            let along = if corridor.horizontal {
+                index % layout.columns
+            } else {
+                index / layout.columns
+            };
+            corridor.offset_for(best[along], cycle_ticks)
+        })
+        .collect();
+
+    SignalPlan { cycle_ticks, offsets }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::VEHICLE_SPEED;
+    use crate::simulation::network::NetworkLayout;
+
+    fn corridor(columns: usize, link_length: f32) -> Network {
+        Network::new(1, NetworkLayout { rows: 1, columns, link_length, ..NetworkLayout::default() })
+    }
+
+    #[test]
+    fn band_is_full_one_way_for_offsets_matching_the_travel_time() {
+        // 200 m links at 10 m/s are 20 s apart, a third of a 60 s cycle; the
+        // corridor runs east-west, whose phase gets green half a cycle after
+        // the offset
+        let network = corridor(3, 200.0);
+        let corridor = Corridor::of(&network);
+        let cycle_ticks = 60 * TICKS_PER_SECOND;
+        let plan = SignalPlan { cycle_ticks, offsets: vec![1800, 3000, 600] };
+        assert_eq!(bandwidth(&corridor, &plan, 10.0), Bandwidth { outbound: cycle_ticks / 2, inbound: 0 });
+
+        // The same green everywhere leaves no band at all at this speed
+        let plan = SignalPlan { cycle_ticks, offsets: vec![0; 3] };
+        assert_eq!(bandwidth(&corridor, &plan, 10.0), Bandwidth { outbound: 0, inbound: 0 });
+    }
+
+    #[test]
+    fn optimised_offsets_widen_the_band() {
+        let network = corridor(5, 200.0);
+        let corridor = Corridor::of(&network);
+        let uncoordinated = SignalPlan::uncoordinated(5);
+        let optimised = optimise_offsets(&network, &corridor, uncoordinated.cycle_ticks, VEHICLE_SPEED);
+        assert_eq!(optimised.offsets.len(), 5);
+        assert_eq!(optimised.cycle_ticks, uncoordinated.cycle_ticks);
+
+        let before = bandwidth(&corridor, &uncoordinated, VEHICLE_SPEED);
+        let after = bandwidth(&corridor, &optimised, VEHICLE_SPEED);
+        assert!(after.total() > before.total(), "{:?} against {:?}", after, before);
+        assert!(after.outbound > 0);
+    }
+}
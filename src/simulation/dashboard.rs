@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::constants::TICKS_PER_SECOND;
+use crate::simulation::network::Network;
+use crate::simulation::prometheus::{self, StepTimes};
+use crate::simulation::server::{lights_json, metrics_json, vehicles_json};
+
+// Samples kept for the charts, one per simulated second
+const HISTORY_SAMPLES: usize = 3600;
+
+// Longest a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PAGE: &str = include_str!("dashboard.html");
+
+// Network-wide figures at one tick
+struct Sample {
+    tick: u64,
+    queues: Vec<Vec<usize>>, // Per intersection and leg, vehicles stopped before the stop line
+    phases: Vec<usize>,      // Per intersection
+    throughput: f32,
+    average_delay: f32,
+    in_network: usize,
+}
+
+// What the dashboard serves, shared with the thread answering requests
+#[derive(Default)]
+struct Shared {
+    state: String,      // JSON of the latest sample
+    exposition: String, // Prometheus metrics of the latest sample
+    samples: VecDeque<Sample>,
+}
+
+// Web page with live charts of a running simulation
+//
+// An HTTP server on a background thread serves the page at `/`, the current
+// state at `/api/state` (metrics, lights and vehicles, as the remote control
+// server reports them), the last hour of samples at `/api/history` and
+// counters for Prometheus to scrape at `/metrics`. The simulation hands its
+// network over on every tick, along with how long the tick took, and a
+// sample is taken once per simulated second, so the server never holds up
+// the simulation for longer than it takes to copy a sample in.
+pub struct Dashboard {
+    pub address: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+    step_times: StepTimes,
+}
+
+impl Dashboard {
+    pub fn start(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let serving = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A client that goes away mid-request only affects itself
+                let _ = respond(stream, &serving);
+            }
+        });
+        Ok(Dashboard {
+            address,
+            shared,
+            step_times: StepTimes::default(),
+        })
+    }
+
+    // Count a tick that took `step` to run, and take a sample if a
+    // simulated second has passed
+    pub fn record(&mut self, network: &Network, step: Duration) {
+        self.step_times.record(step);
+        if !network.tick.is_multiple_of(TICKS_PER_SECOND) {
+            return;
+        }
+        let sample = Sample {
+            tick: network.tick,
+            queues: network
+                .intersections
+                .iter()
+                .map(|intersection| {
+                    (0..intersection.geometry.legs.len())
+                        .map(|leg| intersection.queue_length(leg))
+                        .collect()
+                })
+                .collect(),
+            phases: network.intersections.iter().map(|intersection| intersection.phase()).collect(),
+            throughput: network.metrics.throughput(network.tick),
+            average_delay: network.metrics.average_delay(),
+            in_network: network.vehicles().count(),
+        };
+        let state = format!(
+            "{{\"metrics\":{},\"lights\":{},\"vehicles\":{}}}",
+            metrics_json(network),
+            lights_json(network),
+            vehicles_json(network)
+        );
+        let exposition = prometheus::exposition(network, &self.step_times);
+
+        let mut shared = self.shared.lock().expect("Dashboard lock poisoned");
+        shared.state = state;
+        shared.exposition = exposition;
+        if shared.samples.len() == HISTORY_SAMPLES {
+            shared.samples.pop_front();
+        }
+        shared.samples.push_back(sample);
+    }
+}
+
+impl Shared {
+    // Samples as columns: times in seconds, and per intersection (and leg)
+    // one list of values each
+    fn history_json(&self) -> String {
+        let column = |value: &dyn Fn(&Sample) -> String| {
+            let values: Vec<String> = self.samples.iter().map(value).collect();
+            format!("[{}]", values.join(","))
+        };
+        let intersections = self.samples.back().map_or(0, |sample| sample.queues.len());
+        let queues: Vec<String> = (0..intersections)
+            .map(|index| {
+                let legs = self.samples.back().map_or(0, |sample| sample.queues[index].len());
+                let legs: Vec<String> = (0..legs)
+                    .map(|leg| column(&|sample| sample.queues[index][leg].to_string()))
+                    .collect();
+                format!("[{}]", legs.join(","))
+            })
+            .collect();
+        let phases: Vec<String> = (0..intersections)
+            .map(|index| column(&|sample| sample.phases[index].to_string()))
+            .collect();
+        format!(
+            "{{\"time\":{},\"throughput\":{},\"average_delay\":{},\"in_network\":{},\"queues\":[{}],\"phases\":[{}]}}",
+            column(&|sample| format!("{:.1}", sample.tick as f32 / TICKS_PER_SECOND as f32)),
+            column(&|sample| format!("{:.1}", sample.throughput)),
+            column(&|sample| format!("{:.3}", sample.average_delay)),
+            column(&|sample| sample.in_network.to_string()),
+            queues.join(","),
+            phases.join(",")
+        )
+    }
+}
+
+// Answer one HTTP request
+fn respond(stream: TcpStream, shared: &Mutex<Shared>) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers, as no request has a body
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let parts: Vec<&str> = request.split_whitespace().collect();
+    let (status, content_type, body) = match parts[..] {
+        ["GET", target, _] => {
+            let path = target.split('?').next().unwrap_or(target);
+            let shared = shared.lock().expect("Dashboard lock poisoned");
+            match path {
+                "/" => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+                "/api/state" if shared.state.is_empty() => ("200 OK", "application/json", "null".to_string()),
+                "/api/state" => ("200 OK", "application/json", shared.state.clone()),
+                "/api/history" => ("200 OK", "application/json", shared.history_json()),
+                "/metrics" => ("200 OK", "text/plain; version=0.0.4", shared.exposition.clone()),
+                _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+            }
+        }
+        [_, _, _] => ("405 Method Not Allowed", "text/plain", "Only GET is supported\n".to_string()),
+        _ => ("400 Bad Request", "text/plain", "Bad request\n".to_string()),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,286 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::constants::{TICKS_PER_SECOND, TICK_DURATION};
+use crate::simulation::intersection::{Control, Intersection};
+use crate::simulation::network::{Network, NetworkLayout, RoadGraph};
+
+// What an agent observes after each step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Observation {
+    Queues,                         // Stopped vehicles on each approach
+    Occupancy { cell_length: f32 }, // Cells along each approach from the stop line out, 1 where a vehicle is
+}
+
+// What an agent is rewarded for over each step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reward {
+    Delay,      // Minus the seconds vehicles spent stopped
+    Throughput, // Vehicles that left the network
+    Pressure,   // Minus the imbalance between the queues into and out of each intersection
+}
+
+// Settings of a signal control environment
+#[derive(Debug, Clone)]
+pub struct EnvConfig {
+    pub layout: NetworkLayout,
+    pub observation: Observation,
+    pub reward: Reward,
+    pub arrival_rate: f32,   // Vehicles per second on each approach at the edge of the network
+    pub decision_ticks: u64, // Ticks simulated per step
+    pub episode_ticks: u64,  // Ticks after which an episode is done
+}
+
+impl Default for EnvConfig {
+    // A single signalised crossing with 360 vehicles per hour on each
+    // approach, a decision every 5 seconds and episodes of an hour
+    fn default() -> Self {
+        EnvConfig {
+            layout: NetworkLayout::default(),
+            observation: Observation::Queues,
+            reward: Reward::Delay,
+            arrival_rate: 0.1,
+            decision_ticks: 5 * TICKS_PER_SECOND,
+            episode_ticks: 3600 * TICKS_PER_SECOND,
+        }
+    }
+}
+
+// Network-wide figures after a step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepInfo {
+    pub tick: u64,
+    pub spawned: u64,
+    pub exited: u64,
+    pub average_delay: f32, // Seconds
+    pub throughput: f32,    // Vehicles per hour
+}
+
+// Gym-style environment for training signal controllers
+//
+// Vehicles arrive at random on every approach at the edge of the network,
+// drawn from a generator seeded on `reset`, so a seed and a sequence of
+// actions always give the same episode. Signals no longer follow their
+// fixed-time plan: each step the action picks the phase every intersection
+// shows, one entry per intersection, and the simulation runs for
+// `decision_ticks`. Roundabouts and stop-controlled junctions have a single
+// action and ignore it. Observations are flat vectors: the chosen encoding
+// for every approach of every intersection in turn, followed by a one-hot
+// encoding of each signalised intersection's current phase.
+pub struct Env {
+    pub config: EnvConfig,
+    pub network: Network,
+    graph: RoadGraph,
+    rng: StdRng,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Result<Self, String> {
+        if let Observation::Occupancy { cell_length } = config.observation {
+            if !(cell_length.is_finite() && cell_length > 0.0) {
+                return Err(format!("Invalid cell length {}, expected a positive number of metres", cell_length));
+            }
+        }
+        if !(config.arrival_rate.is_finite() && config.arrival_rate >= 0.0) {
+            return Err(format!(
+                "Invalid arrival rate {}, expected a non-negative number of vehicles per second",
+                config.arrival_rate
+            ));
+        }
+        if config.decision_ticks == 0 {
+            return Err("Each step needs to simulate at least one tick".to_string());
+        }
+        let graph = RoadGraph::of(&config.layout)?;
+        let mut env = Env {
+            network: Network::from_graph(0, config.layout.clone(), graph.clone()),
+            config,
+            graph,
+            rng: StdRng::seed_from_u64(0),
+        };
+        env.reset(0);
+        Ok(env)
+    }
+
+    // Start a new episode, returning the first observation
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.network = Network::from_graph(seed, self.config.layout.clone(), self.graph.clone());
+        for intersection in &mut self.network.intersections {
+            intersection.phase_held = true;
+        }
+        self.rng = StdRng::seed_from_u64(seed);
+        self.observe()
+    }
+
+    // Number of actions available at each intersection
+    pub fn action_sizes(&self) -> Vec<usize> {
+        self.network
+            .intersections
+            .iter()
+            .map(|intersection| match intersection.control {
+                Control::Signals => intersection.phases.len(),
+                Control::Roundabout(_) | Control::Stop(_) => 1,
+            })
+            .collect()
+    }
+
+    // Length of every observation
+    pub fn observation_size(&self) -> usize {
+        self.observe().len()
+    }
+
+    // Apply one phase per intersection and simulate until the next decision
+    pub fn step(&mut self, action: &[usize]) -> Result<(Vec<f32>, f32, bool, StepInfo), String> {
+        let sizes = self.action_sizes();
+        if action.len() != sizes.len() {
+            return Err(format!("Expected {} actions, one per intersection, got {}", sizes.len(), action.len()));
+        }
+        if let Some((index, &phase)) = action.iter().enumerate().find(|&(index, &phase)| phase >= sizes[index]) {
+            return Err(format!("Intersection {} has no phase {}", index, phase));
+        }
+        for (index, &phase) in action.iter().enumerate() {
+            self.network.select_phase(index, phase);
+        }
+
+        let exited = self.network.metrics.exited;
+        let mut stopped_ticks = 0;
+        for _ in 0..self.config.decision_ticks {
+            self.spawn_arrivals();
+            self.network.update();
+            stopped_ticks += self.network.vehicles().filter(|vehicle| vehicle.is_stopped()).count();
+        }
+
+        let reward = match self.config.reward {
+            Reward::Delay => -(stopped_ticks as f32) * TICK_DURATION,
+            Reward::Throughput => (self.network.metrics.exited - exited) as f32,
+            Reward::Pressure => -(0..self.network.intersections.len())
+                .map(|index| self.pressure(index))
+                .sum::<f32>(),
+        };
+        let metrics = &self.network.metrics;
+        let info = StepInfo {
+            tick: self.network.tick,
+            spawned: metrics.spawned,
+            exited: metrics.exited,
+            average_delay: metrics.average_delay(),
+            throughput: metrics.throughput(self.network.tick),
+        };
+        let done = self.network.tick >= self.config.episode_ticks;
+        Ok((self.observe(), reward, done, info))
+    }
+
+    pub fn observe(&self) -> Vec<f32> {
+        let mut observation = Vec::new();
+        for intersection in &self.network.intersections {
+            for leg in 0..intersection.geometry.legs.len() {
+                match self.config.observation {
+                    Observation::Queues => observation.push(intersection.queue_length(leg) as f32),
+                    Observation::Occupancy { cell_length } => observation.extend(occupancy(intersection, leg, cell_length)),
+                }
+            }
+        }
+        for intersection in &self.network.intersections {
+            if matches!(intersection.control, Control::Signals) {
+                observation.extend((0..intersection.phases.len()).map(|phase| (phase == intersection.phase()) as u8 as f32));
+            }
+        }
+        observation
+    }
+
+    // Arrivals at every approach at the edge of the network for one tick
+    fn spawn_arrivals(&mut self) {
+        let probability = (self.config.arrival_rate * TICK_DURATION).clamp(0.0, 1.0) as f64;
+        for index in 0..self.network.intersections.len() {
+            for leg in 0..self.network.intersections[index].geometry.legs.len() {
+                if self.network.is_entry(index, leg) && self.rng.gen_bool(probability) {
+                    self.network.spawn_at(index, leg, None);
+                }
+            }
+        }
+    }
+
+    // Absolute difference between the vehicles queued on an intersection's
+    // approaches and those queued on the approaches its exits lead into
+    fn pressure(&self, index: usize) -> f32 {
+        let intersection = &self.network.intersections[index];
+        let (mut incoming, mut outgoing) = (0, 0);
+        for leg in 0..intersection.geometry.legs.len() {
+            incoming += intersection.queue_length(leg);
+            if let Some((next, back)) = self.network.neighbour(index, leg) {
+                outgoing += self.network.intersections[next].queue_length(back);
+            }
+        }
+        (incoming as f32 - outgoing as f32).abs()
+    }
+}
+
+// Cells along an approach from the stop line out, 1 where a vehicle's front
+// is
+fn occupancy(intersection: &Intersection, leg: usize, cell_length: f32) -> Vec<f32> {
+    let stop_line = intersection.geometry.stop_line(leg);
+    let approach = intersection.geometry.legs[leg].length - stop_line;
+    let mut cells = vec![0.0; (approach / cell_length).ceil().max(1.0) as usize];
+    for vehicle in &intersection.vehicles {
+        if vehicle.path.movement.from == leg && vehicle.is_approaching() {
+            let cell = ((vehicle.path.stop_at - vehicle.travelled).max(0.0) / cell_length) as usize;
+            if let Some(cell) = cells.get_mut(cell) {
+                *cell = 1.0;
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_config() -> EnvConfig {
+        EnvConfig {
+            layout: NetworkLayout {
+                columns: 2,
+                ..NetworkLayout::default()
+            },
+            arrival_rate: 0.3,
+            ..EnvConfig::default()
+        }
+    }
+
+    #[test]
+    fn sizes_cover_every_approach_and_phase() {
+        let env = Env::new(grid_config()).unwrap();
+        assert_eq!(env.action_sizes(), vec![2, 2]);
+        // Four queues and two phase flags at each intersection
+        assert_eq!(env.observation_size(), 12);
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_episode() {
+        let actions = [[0, 1], [1, 1], [1, 0], [0, 0], [0, 1], [1, 0]];
+        let mut env = Env::new(grid_config()).unwrap();
+        let mut episode = || {
+            let mut steps = vec![(env.reset(42), 0.0, false, None)];
+            for action in actions.iter().cycle().take(60) {
+                let (observation, reward, done, info) = env.step(action).unwrap();
+                steps.push((observation, reward, done, Some(info)));
+            }
+            steps
+        };
+        let first = episode();
+        assert!(first.last().and_then(|step| step.3).is_some_and(|info| info.spawned > 0));
+        assert_eq!(first, episode());
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let with = |change: fn(&mut EnvConfig)| {
+            let mut config = EnvConfig::default();
+            change(&mut config);
+            Env::new(config)
+        };
+        assert!(with(|config| config.observation = Observation::Occupancy { cell_length: 0.0 }).is_err());
+        assert!(with(|config| config.decision_ticks = 0).is_err());
+        assert!(with(|config| config.arrival_rate = -0.1).is_err());
+        assert!(with(|config| config.arrival_rate = f32::NAN).is_err());
+        assert!(with(|config| config.observation = Observation::Occupancy { cell_length: 7.5 }).is_ok());
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+
+use crate::constants::TIMELINE_HEIGHT;
+use crate::simulation::network::Network;
+use crate::ui::camera::Viewport;
+use crate::ui::surface::{Color, Rect, Surface};
+
+// Ring buffer of per-tick snapshots for stepping back and forth through a run
+//
+// The newest snapshot is always the live simulation. While the cursor points
+// at an older snapshot the viewer shows that snapshot instead, and the live
+// simulation does not advance until the cursor is back at the newest one.
+pub struct History {
+    snapshots: VecDeque<Network>,
+    capacity: usize,
+    cursor: Option<usize>, // None while following the live simulation
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+            cursor: None,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Network) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+            // Keep the cursor on the same snapshot, or the oldest one if it was dropped
+            if let Some(cursor) = &mut self.cursor {
+                *cursor = cursor.saturating_sub(1);
+            }
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    // Snapshot being viewed, or None when following the live simulation
+    pub fn view(&self) -> Option<&Network> {
+        self.cursor.map(|cursor| &self.snapshots[cursor])
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.cursor.is_none()
+    }
+
+    pub fn go_live(&mut self) {
+        self.cursor = None;
+    }
+
+    pub fn step_back(&mut self) {
+        let newest = self.snapshots.len().saturating_sub(1);
+        self.cursor = Some(match self.cursor {
+            Some(cursor) => cursor.saturating_sub(1),
+            None => newest.saturating_sub(1),
+        });
+    }
+
+    // Returns false if already live, i.e. there is nothing recorded to step to
+    pub fn step_forward(&mut self) -> bool {
+        match self.cursor {
+            Some(cursor) => {
+                self.cursor = if cursor + 1 >= self.snapshots.len().saturating_sub(1) {
+                    None
+                } else {
+                    Some(cursor + 1)
+                };
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Move the cursor to a point on the timeline, 0.0 being the oldest snapshot
+    pub fn seek(&mut self, fraction: f32) {
+        if self.snapshots.is_empty() {
+            return;
+        }
+        let newest = self.snapshots.len() - 1;
+        let index = (fraction.clamp(0.0, 1.0) * newest as f32).round() as usize;
+        self.cursor = if index >= newest { None } else { Some(index) };
+    }
+
+    // Move the cursor to the snapshot under an x coordinate on the timeline bar
+    pub fn seek_to_x(&mut self, viewport: &Viewport, x: i32) {
+        let recorded_width = viewport.width as f32 * self.filled();
+        self.seek(x as f32 / recorded_width);
+    }
+
+    // Share of the buffer capacity holding snapshots
+    fn filled(&self) -> f32 {
+        self.snapshots.len() as f32 / self.capacity as f32
+    }
+
+    pub fn timeline_contains(viewport: &Viewport, x: i32, y: i32) -> bool {
+        Self::timeline_rect(viewport).contains_point((x, y))
+    }
+
+    // Bar along the bottom edge of the viewport
+    fn timeline_rect(viewport: &Viewport) -> Rect {
+        let height = viewport.ui_scale(TIMELINE_HEIGHT);
+        Rect::new(
+            0,
+            viewport.height.saturating_sub(height) as i32,
+            viewport.width,
+            height,
+        )
+    }
+
+    pub fn render(&self, canvas: &mut dyn Surface, viewport: &Viewport) {
+        let bar = Self::timeline_rect(viewport);
+
+        // Background spans the whole buffer capacity
+        canvas.set_draw_color(Color::RGB(30, 30, 30));
+        canvas.fill_rect(bar);
+
+        // Recorded part of the buffer
+        let filled = self.filled();
+        canvas.set_draw_color(Color::RGB(90, 90, 90));
+        canvas.fill_rect(Rect::new(
+            bar.x(),
+            bar.y(),
+            ((bar.width() as f32 * filled) as u32).max(1),
+            bar.height(),
+        ));
+
+        // Cursor marker, at the far end when following the live simulation
+        let newest = self.snapshots.len().saturating_sub(1).max(1);
+        let position = self.cursor.unwrap_or(newest) as f32 / newest as f32;
+        let color = if self.is_live() {
+            Color::RGB(255, 255, 255)
+        } else {
+            Color::RGB(255, 165, 0)
+        };
+        canvas.set_draw_color(color);
+        canvas.fill_rect(Rect::new(
+            (bar.width() as f32 * filled * position) as i32 - viewport.ui_scale(2) as i32,
+            bar.y(),
+            viewport.ui_scale(4),
+            bar.height(),
+        ));
+    }
+}
@@ -1,206 +1,549 @@
-use rand::Rng;
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use std::time::Instant;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use crate::constants::{
-    LANE_WIDTH, ROAD_WIDTH, TRAFFIC_LIGHT_CYCLE_TIME, TRAFFIC_LIGHT_SIZE, VEHICLE_SAFE_DISTANCE, WINDOW_HEIGHT, WINDOW_WIDTH
+    APPROACH_LENGTH, LANE_MARKING_WIDTH, LANE_WIDTH, ROAD_WIDTH, TICKS_PER_SECOND, TRAFFIC_LIGHT_CYCLE_TIME,
+    TRAFFIC_LIGHT_SIZE, VEHICLE_LENGTH, VEHICLE_SAFE_DISTANCE, VEHICLE_SPAWN_COOLDOWN,
 };
 use crate::models::direction::Direction;
+use crate::models::driver::DriverProfile;
+use crate::models::geometry::polygons_overlap;
+use crate::models::junction::{JunctionGeometry, Movement, MovementPath};
 use crate::models::route::Route;
 use crate::models::traffic_light::{TrafficLight, TrafficLightState};
 use crate::models::vehicle::Vehicle;
+use crate::simulation::metrics::Metrics;
+use crate::simulation::roundabout::Roundabout;
+use crate::simulation::stop_control::{StopControl, StopRule};
+use crate::ui::camera::Camera;
+use crate::ui::shapes::fill_polygon;
+use crate::ui::surface::{Color, Surface};
+
+// Convert a duration in milliseconds to simulation ticks
+pub fn ms_to_ticks(ms: u64) -> u64 {
+    ms * TICKS_PER_SECOND / 1000
+}
+
+// How traffic is controlled where the roads meet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Signals,
+    Roundabout(Roundabout),
+    Stop(StopControl),
+}
+
+impl Control {
+    pub fn name(self) -> &'static str {
+        match self {
+            Control::Signals => "signals",
+            Control::Roundabout(_) => "roundabout",
+            Control::Stop(stop) if stop.rule == StopRule::AllWay => "all-way-stop",
+            Control::Stop(_) => "two-way-stop",
+        }
+    }
+}
 
 // Intersection struct to manage the simulation
+//
+// Time is measured in ticks rather than wall-clock time and all randomness
+// comes from a seeded generator, so the same seed and inputs always produce
+// the same run.
+//
+// An intersection covers the square of half-size `approach_length` around its
+// center, where three to six legs meet. On its own it is a whole simulation;
+// in a `Network` it is one junction, and the network moves vehicles between
+// neighbouring ones.
+//
+// The lights follow a fixed-time plan: a cycle shared with the other
+// intersections of a network and split evenly between the phases, shifted by
+// an offset so green can travel along a corridor as a wave. Manual overrides
+// restart the current phase by moving the offset. Roundabouts and
+// stop-controlled junctions have no lights; their vehicles give way on entry
+// instead. The phase whose legs have the highest priority, or the first one
+// if they are all alike, is the major road of a two-way stop.
+#[derive(Clone)]
 pub struct Intersection {
     pub traffic_lights: Vec<TrafficLight>,
     pub vehicles: Vec<Vehicle>,
-    pub last_traffic_light_change: Instant,
-    pub last_vehicle_spawn: [Instant; 4], // One for each direction
+    pub tick: u64,
+    pub seed: u64,
+    pub cycle_ticks: u64,  // All phases, each getting an equal share
+    pub offset_ticks: u64, // Tick within the cycle at which the first phase starts
+    pub last_vehicle_spawn: Vec<u64>, // One for each leg
+    pub metrics: Metrics,
+    pub next_vehicle_id: u32,
+    pub phase_held: bool, // Manual override: keep the current phase until released
+    pub center: (f32, f32),
+    pub approach_length: f32,
+    pub geometry: JunctionGeometry,
+    pub paths: Arc<Vec<Arc<MovementPath>>>, // One for every movement, shared with the vehicles on it
+    pub phases: Vec<Vec<usize>>, // Legs getting green together, in the order they get it
+    pub major: Vec<usize>,       // Legs of the major road, which never stop on a two-way stop
+    pub blocked_exits: Vec<bool>, // Per leg, set while the road beyond it is full
+    pub control: Control,
+    phase: usize,         // Phase that has green
+    last_entry: Vec<u64>, // Per leg, tick a vehicle last entered the roundabout
+    arrivals: Vec<Option<(u32, u64)>>, // Per leg, vehicle stopped at the stop sign and the tick it got there
+    colliding: Vec<(u32, u32)>,        // Pairs of vehicles overlapping after the last tick
+    rng: StdRng,
 }
 
 impl Intersection {
-    pub fn new() -> Self {
-        // Create traffic lights for each direction
-        let traffic_lights = vec![
-            TrafficLight::new(
-                Point::new(
-                    (WINDOW_WIDTH / 2 - ROAD_WIDTH / 2 - TRAFFIC_LIGHT_SIZE) as i32,
-                    (WINDOW_HEIGHT / 2 + ROAD_WIDTH / 2) as i32,
-                ),
-                Direction::North,
-            ),
-            TrafficLight::new(
-                Point::new(
-                    (WINDOW_WIDTH / 2 + ROAD_WIDTH / 2) as i32,
-                    (WINDOW_HEIGHT / 2  - ROAD_WIDTH / 2 - TRAFFIC_LIGHT_SIZE) as i32,
-                ),
-                Direction::South,
-            ),
-            TrafficLight::new(
-                Point::new(
-                    (WINDOW_WIDTH / 2 - ROAD_WIDTH / 2 - TRAFFIC_LIGHT_SIZE) as i32,
-                    (WINDOW_HEIGHT / 2 - ROAD_WIDTH / 2 - TRAFFIC_LIGHT_SIZE) as i32,
-                ),
-                Direction::East,
-            ),
-            TrafficLight::new(
-                Point::new(
-                    (WINDOW_WIDTH / 2 + ROAD_WIDTH / 2 ) as i32,
-                    (WINDOW_HEIGHT / 2 + ROAD_WIDTH / 2 ) as i32,
-                ),
-                Direction::West,
-            ),
-        ];
+    pub fn new(seed: u64) -> Self {
+        Self::at(seed, (0.0, 0.0), JunctionGeometry::cross(APPROACH_LENGTH), Control::Signals)
+    }
 
-        // Set initial traffic light states (North-South green, East-West red)
+    // Intersection centered at a world point, with the given legs
+    pub fn at(seed: u64, center: (f32, f32), geometry: JunctionGeometry, control: Control) -> Self {
+        // Create a traffic light for each leg traffic can enter by
+        // Each light stands at the stop line, to the right of its approach
+        let legs = geometry.legs.len();
+        let traffic_lights = match control {
+            Control::Signals => (0..legs)
+                .filter(|&leg| geometry.legs[leg].entry)
+                .map(|leg| {
+                    let (x, y) = geometry.legs[leg].point(
+                        geometry.stop_line(leg) + TRAFFIC_LIGHT_SIZE / 2.0,
+                        -(ROAD_WIDTH + TRAFFIC_LIGHT_SIZE) / 2.0,
+                    );
+                    let corner = (
+                        center.0 + x - TRAFFIC_LIGHT_SIZE / 2.0,
+                        center.1 + y - TRAFFIC_LIGHT_SIZE / 2.0,
+                    );
+                    TrafficLight::new(corner, leg)
+                })
+                .collect(),
+            Control::Roundabout(_) | Control::Stop(_) => Vec::new(),
+        };
+        let paths = match control {
+            Control::Signals | Control::Stop(_) => geometry.paths(),
+            Control::Roundabout(roundabout) => roundabout.paths(&geometry),
+        };
+
+        let phases = geometry.signal_phases();
+        let priority = |phase: &Vec<usize>| phase.iter().map(|&leg| geometry.legs[leg].priority).sum::<u32>();
+        let major = phases
+            .iter()
+            .fold(None, |best: Option<&Vec<usize>>, phase| match best {
+                Some(best) if priority(best) >= priority(phase) => Some(best),
+                _ => Some(phase),
+            })
+            .cloned()
+            .unwrap_or_default();
+
+        // Start with the first phase green
         let mut intersection = Intersection {
             traffic_lights,
             vehicles: Vec::new(),
-            last_traffic_light_change: Instant::now(),
-            last_vehicle_spawn: [Instant::now(); 4],
+            tick: 0,
+            seed,
+            cycle_ticks: 2 * ms_to_ticks(TRAFFIC_LIGHT_CYCLE_TIME),
+            offset_ticks: 0,
+            last_vehicle_spawn: vec![0; legs],
+            metrics: Metrics::default(),
+            next_vehicle_id: 1,
+            phase_held: false,
+            center,
+            approach_length: geometry.legs.iter().map(|leg| leg.length).fold(0.0, f32::max),
+            phases,
+            major,
+            geometry,
+            paths: Arc::new(paths.into_iter().map(Arc::new).collect()),
+            blocked_exits: vec![false; legs],
+            control,
+            phase: 0,
+            last_entry: vec![0; legs],
+            arrivals: vec![None; legs],
+            colliding: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
         };
 
-        intersection.update_traffic_lights();
+        intersection.set_phase(0);
         intersection
     }
 
-    pub fn update(&mut self) {
+    // Advance one tick, returning the vehicles that drove out of the intersection's area
+    pub fn update(&mut self) -> Vec<Vehicle> {
+        self.tick += 1;
+
         // Update traffic lights
-        if self.last_traffic_light_change.elapsed().as_millis() > TRAFFIC_LIGHT_CYCLE_TIME as u128 {
-            self.update_traffic_lights();
-            self.last_traffic_light_change = Instant::now();
+        if !self.phase_held {
+            self.set_phase(self.planned_phase());
         }
 
         // Update vehicles
         let vehicles_clone = self.vehicles.clone();
-        for vehicle in &mut self.vehicles {
-            vehicle.update(&self.traffic_lights, &vehicles_clone);
+        let exits_blocked: Vec<bool> = vehicles_clone.iter().map(|v| self.must_hold_at_exit(v)).collect();
+        match self.control {
+            Control::Signals => {
+                for (vehicle, exit_blocked) in self.vehicles.iter_mut().zip(exits_blocked) {
+                    let ran_red = vehicle.ran_red;
+                    vehicle.update(self.tick, &self.traffic_lights, &vehicles_clone, exit_blocked);
+                    if vehicle.ran_red && !ran_red {
+                        self.metrics.red_light_runs += 1;
+                    }
+                }
+            }
+            Control::Roundabout(roundabout) => roundabout.update(
+                self.tick,
+                &mut self.vehicles,
+                &vehicles_clone,
+                &mut self.last_entry,
+                &exits_blocked,
+            ),
+            Control::Stop(stop) => stop.update(
+                self.tick,
+                &mut self.vehicles,
+                &vehicles_clone,
+                &self.major,
+                &mut self.arrivals,
+                &exits_blocked,
+            ),
         }
 
         // Remove vehicles that are out of bounds
-        self.vehicles.retain(|v| !v.is_out_of_bounds());
+        let (departed, remaining) = std::mem::take(&mut self.vehicles)
+            .into_iter()
+            .partition(|v| self.is_outside(v));
+        self.vehicles = remaining;
+        for vehicle in &departed {
+            self.metrics.record_exit(self.tick, vehicle.junction_delay_ticks());
+        }
+        self.detect_collisions();
+        departed
     }
 
-    fn update_traffic_lights(&mut self) {
-        // Simple traffic light algorithm: alternate between North-South and East-West
-        let north_south_green = self.traffic_lights[0].state == TrafficLightState::Green;
-
-        for light in &mut self.traffic_lights {
-            match light.direction {
-                Direction::North | Direction::South => {
-                    light.state = if north_south_green {
-                        TrafficLightState::Red
-                    } else {
-                        TrafficLightState::Green
-                    };
-                }
-                Direction::East | Direction::West => {
-                    light.state = if north_south_green {
-                        TrafficLightState::Green
-                    } else {
-                        TrafficLightState::Red
-                    };
+    // Count the pairs of vehicles whose bodies have come to overlap this
+    // tick; a pair is counted once however long it stays that way
+    fn detect_collisions(&mut self) {
+        let bodies: Vec<(u32, [(f32, f32); 4])> = self.vehicles.iter().map(|v| (v.id, v.corners())).collect();
+        let mut colliding = Vec::new();
+        for (index, (id, corners)) in bodies.iter().enumerate() {
+            for (other, other_corners) in &bodies[index + 1..] {
+                if polygons_overlap(corners, other_corners) {
+                    colliding.push((*id.min(other), *id.max(other)));
                 }
             }
         }
+        self.metrics.collisions += colliding.iter().filter(|pair| !self.colliding.contains(pair)).count() as u64;
+        self.colliding = colliding;
+    }
+
+    // Path of a movement through the intersection
+    pub fn path(&self, movement: Movement) -> Arc<MovementPath> {
+        self.paths
+            .iter()
+            .find(|path| path.movement == movement)
+            .cloned()
+            .expect("Every movement has a path")
+    }
+
+    // A vehicle nearing the end of its exit waits there while that exit is blocked
+    fn must_hold_at_exit(&self, vehicle: &Vehicle) -> bool {
+        let end = vehicle.path.path.length() - VEHICLE_LENGTH;
+        self.blocked_exits[vehicle.path.movement.to]
+            && vehicle.travelled > end - VEHICLE_SAFE_DISTANCE
+            && vehicle.travelled < end
+    }
+
+    // Fully past the end of an exit
+    fn is_outside(&self, vehicle: &Vehicle) -> bool {
+        vehicle.travelled >= vehicle.path.path.length()
+    }
+
+    // Whether the area covered by the intersection contains a point in the world
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (x - self.center.0).abs() <= self.approach_length && (y - self.center.1).abs() <= self.approach_length
+    }
+
+    // Room for another vehicle to enter at the far end of a leg
+    //
+    // A vehicle handed over from the previous intersection arrives slightly
+    // past the end of the leg, so the last vehicle in must have moved on by
+    // a safe distance beyond that.
+    pub fn entry_is_clear(&self, leg: usize) -> bool {
+        !self.vehicles.iter().any(|v| {
+            v.path.movement.from == leg && v.travelled < VEHICLE_LENGTH + VEHICLE_SAFE_DISTANCE
+        })
+    }
+
+    // Name of the phase that currently has green, from the compass
+    // directions of its legs
+    pub fn phase_name(&self) -> String {
+        let names: Vec<&str> = self.phases[self.phase]
+            .iter()
+            .map(|&leg| self.geometry.leg_name(leg))
+            .collect();
+        names.join("-")
+    }
+
+    // Whether traffic travelling in a direction has green, on the leg it
+    // comes in by most directly; never on a roundabout
+    pub fn is_green_for(&self, direction: Direction) -> bool {
+        let leg = self.geometry.leg_from(direction);
+        self.traffic_lights
+            .iter()
+            .any(|light| light.leg == leg && light.state == TrafficLightState::Green)
+    }
+
+    // Ticks left until the lights switch to the next phase
+    pub fn phase_remaining_ticks(&self) -> u64 {
+        let position = self.cycle_position();
+        let phase = self.planned_phase();
+        if phase + 1 < self.phases.len() {
+            (phase as u64 + 1) * self.green_ticks() - position
+        } else {
+            self.cycle_ticks - position
+        }
+    }
+
+    // Ticks of green each phase gets per cycle
+    pub fn green_ticks(&self) -> u64 {
+        self.cycle_ticks / self.phases.len() as u64
+    }
+
+    // Ticks since the start of the current cycle
+    fn cycle_position(&self) -> u64 {
+        (self.tick + self.cycle_ticks - self.offset_ticks % self.cycle_ticks) % self.cycle_ticks
+    }
+
+    fn planned_phase(&self) -> usize {
+        ((self.cycle_position() / self.green_ticks().max(1)) as usize).min(self.phases.len() - 1)
+    }
+
+    // Follow a new cycle length and offset from the current tick on
+    pub fn set_signal_timing(&mut self, cycle_ticks: u64, offset_ticks: u64) {
+        self.cycle_ticks = cycle_ticks;
+        self.offset_ticks = offset_ticks % cycle_ticks;
+        self.set_phase(self.planned_phase());
+    }
+
+    pub fn vehicle(&self, id: u32) -> Option<&Vehicle> {
+        self.vehicles.iter().find(|v| v.id == id)
+    }
+
+    pub fn remove_vehicle(&mut self, id: u32) {
+        self.vehicles.retain(|v| v.id != id);
+    }
+
+    // Vehicle under a point in the world, if any
+    pub fn vehicle_at(&self, x: f32, y: f32) -> Option<&Vehicle> {
+        self.vehicles
+            .iter()
+            .find(|v| v.bounds().contains((x, y)))
+    }
+
+    // Leg of the traffic light under a point in the world, if any
+    pub fn traffic_light_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.traffic_lights
+            .iter()
+            .find(|light| light.bounds().contains((x, y)))
+            .map(|light| light.leg)
+    }
+
+    // Leg under a point in the world, between its stop line and its far end, if any
+    pub fn approach_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let (x, y) = (x - self.center.0, y - self.center.1);
+
+        self.geometry.legs.iter().enumerate().find_map(|(index, leg)| {
+            let (sin, cos) = leg.angle.sin_cos();
+            let along = x * cos + y * sin;
+            let across = y * cos - x * sin;
+            (along > self.geometry.stop_line(index) && along <= leg.length && across.abs() < ROAD_WIDTH / 2.0)
+                .then_some(index)
+        })
     }
 
-    pub fn spawn_vehicle(&mut self, direction: Direction) {
-        let dir_index = match direction {
-            Direction::North => 0,
-            Direction::South => 1,
-            Direction::East => 2,
-            Direction::West => 3,
+    // Lane section by a leg's stop line, or on a roundabout its yield line,
+    // where a vehicle waits to enter, in world coordinates
+    pub fn stop_zone(&self, leg: usize) -> [(f32, f32); 4] {
+        let line = match self.control {
+            Control::Signals | Control::Stop(_) => self.geometry.stop_line(leg),
+            Control::Roundabout(roundabout) => roundabout.yield_distance() - VEHICLE_LENGTH / 2.0,
         };
+        self.geometry.legs[leg]
+            .strip(line, line + VEHICLE_LENGTH, -LANE_WIDTH / 2.0, LANE_WIDTH)
+            .map(|(x, y)| (self.center.0 + x, self.center.1 + y))
+    }
 
-        // Check if enough time has passed since the last spawn in this direction
-        if self.last_vehicle_spawn[dir_index].elapsed().as_millis() < 1000 {
-            return;
+    // Number of vehicles still approaching the intersection on a leg
+    pub fn approach_count(&self, leg: usize) -> usize {
+        self.vehicles
+            .iter()
+            .filter(|v| v.path.movement.from == leg && v.is_approaching())
+            .count()
+    }
+
+    // Number of vehicles stopped before the stop line on a leg
+    pub fn queue_length(&self, leg: usize) -> usize {
+        self.vehicles
+            .iter()
+            .filter(|v| v.path.movement.from == leg && v.is_approaching() && v.is_stopped())
+            .count()
+    }
+
+    // Manual override: switch to the next phase immediately and restart its timer
+    pub fn switch_traffic_lights(&mut self) {
+        self.set_phase((self.phase + 1) % self.phases.len());
+        self.restart_phase();
+    }
+
+    // Move the offset so that the current phase starts at the current tick
+    fn restart_phase(&mut self) {
+        let start = self.phase as u64 * self.green_ticks();
+        self.offset_ticks = (self.tick % self.cycle_ticks + self.cycle_ticks - start) % self.cycle_ticks;
+    }
+
+    // Manual override: give green to the phase containing a leg
+    pub fn force_green(&mut self, leg: usize) {
+        let is_red = self
+            .traffic_lights
+            .iter()
+            .any(|light| light.leg == leg && light.state == TrafficLightState::Red);
+        if let Some(phase) = self.phases.iter().position(|phase| phase.contains(&leg)).filter(|_| is_red) {
+            self.set_phase(phase);
+            self.restart_phase();
         }
+    }
 
-        // Check if there's already a vehicle too close to the spawn point
-        for vehicle in &self.vehicles {
-            if vehicle.direction == direction {
-                let too_close = match direction {
-                    Direction::North => vehicle.position.1 > WINDOW_HEIGHT as f32 - VEHICLE_SAFE_DISTANCE,
-                    Direction::South => vehicle.position.1 < VEHICLE_SAFE_DISTANCE,
-                    Direction::East => vehicle.position.0 < VEHICLE_SAFE_DISTANCE,
-                    Direction::West => vehicle.position.0 > WINDOW_WIDTH as f32 - VEHICLE_SAFE_DISTANCE,
-                };
-
-                if too_close {
-                    return;
-                }
+    // Manual override: give green to a phase and restart its timer, unless
+    // it has green already
+    pub fn select_phase(&mut self, phase: usize) {
+        if phase < self.phases.len() && phase != self.phase {
+            self.set_phase(phase);
+            self.restart_phase();
+        }
+    }
+
+    // Phase that currently has green
+    pub fn phase(&self) -> usize {
+        self.phase
+    }
+
+    // Manual override: stop or resume the automatic phase changes
+    pub fn toggle_phase_hold(&mut self) {
+        self.phase_held = !self.phase_held;
+        self.restart_phase();
+    }
+
+    fn set_phase(&mut self, phase: usize) {
+        if phase != self.phase && matches!(self.control, Control::Signals) {
+            self.metrics.phase_changes += 1;
+        }
+        self.phase = phase;
+        for light in &mut self.traffic_lights {
+            let state = if self.phases[phase].contains(&light.leg) {
+                TrafficLightState::Green
+            } else {
+                TrafficLightState::Red
+            };
+            if state != light.state {
+                light.state = state;
+                light.changed_at = self.tick;
             }
         }
+    }
+
+    // Spawn a vehicle coming in by a leg, on the given route or to a random exit
+    pub fn spawn_vehicle(&mut self, leg: usize, route: Option<Route>) {
+        if leg >= self.geometry.legs.len() || !self.can_spawn(leg) {
+            return;
+        }
 
-        // Generate a random route
-        let mut rng = rand::thread_rng();
-        let route = match rng.gen_range(0..3) {
-            0 => Route::Left,
-            1 => Route::Straight,
-            _ => Route::Right,
+        // Pick a random exit unless a route was chosen
+        let exit = match route {
+            Some(route) => match self.geometry.exit_for(leg, route) {
+                Some(exit) => exit,
+                None => return,
+            },
+            None => {
+                let exits = self.geometry.exits(leg);
+                exits[self.rng.gen_range(0..exits.len())]
+            }
         };
 
         // Create and add the new vehicle
-        self.vehicles.push(Vehicle::new(direction, route));
-        self.last_vehicle_spawn[dir_index] = Instant::now();
-    }
-
-    pub fn render(&self, canvas: &mut Canvas<Window>) {
-        // Render roads
-        canvas.set_draw_color(Color::RGB(100, 100, 100)); // Road color
-        canvas
-            .fill_rect(Rect::new(
-                (WINDOW_WIDTH / 2 - ROAD_WIDTH / 2) as i32,
-                0,
-                ROAD_WIDTH,
-                WINDOW_HEIGHT,
-            ))
-            .expect("Failed to render vertical road");
-        canvas
-            .fill_rect(Rect::new(
-                0,
-                (WINDOW_HEIGHT / 2 - ROAD_WIDTH / 2) as i32,
-                WINDOW_WIDTH,
-                ROAD_WIDTH,
-            ))
-            .expect("Failed to render horizontal road");
-
-        // Render lane markings
-        canvas.set_draw_color(Color::RGB(255, 255, 255)); // White for lane markings
-
-        // Vertical road lane marking
-        canvas
-            .fill_rect(Rect::new(
-                (WINDOW_WIDTH / 2) as i32 - 2,
-                0,
-                4,
-                WINDOW_HEIGHT,
-            ))
-            .expect("Failed to render vertical lane marking");
-
-        // Horizontal road lane marking
-        canvas
-            .fill_rect(Rect::new(
-                0,
-                (WINDOW_HEIGHT / 2) as i32 - 2,
-                WINDOW_WIDTH,
-                4,
-            ))
-            .expect("Failed to render horizontal lane marking");
+        let path = self.path(Movement { from: leg, to: exit });
+        let vehicle = Vehicle::new(self.next_vehicle_id, self.center, path, DriverProfile::default());
+        self.next_vehicle_id += 1;
+        self.spawn(vehicle);
+    }
+
+    // Whether a vehicle may be spawned on a leg now
+    pub fn can_spawn(&self, leg: usize) -> bool {
+        // Check if enough time has passed since the last spawn on this leg
+        if self.tick - self.last_vehicle_spawn[leg] < ms_to_ticks(VEHICLE_SPAWN_COOLDOWN) {
+            return false;
+        }
+
+        // Check if there's already a vehicle too close to the spawn point
+        !self
+            .vehicles
+            .iter()
+            .any(|vehicle| vehicle.path.movement.from == leg && vehicle.travelled < VEHICLE_SAFE_DISTANCE)
+    }
+
+    // Add a newly created vehicle at the start of its approach
+    pub fn spawn(&mut self, vehicle: Vehicle) {
+        self.last_vehicle_spawn[vehicle.path.movement.from] = self.tick;
+        self.admit(vehicle);
+    }
+
+    // Take over a vehicle entering the intersection's area
+    pub fn admit(&mut self, vehicle: Vehicle) {
+        self.metrics.record_spawn();
+        self.vehicles.push(vehicle);
+    }
+
+    pub fn render(&self, canvas: &mut dyn Surface, camera: &Camera) {
+        self.render_roads(canvas, camera);
+        self.render_traffic(canvas, camera);
+    }
+
+    pub fn render_roads(&self, canvas: &mut dyn Surface, camera: &Camera) {
+        let road = Color::RGB(100, 100, 100); // Road color
+        let marking = Color::RGB(255, 255, 255); // White for lane markings
+        let world = |(x, y): (f32, f32)| (self.center.0 + x, self.center.1 + y);
+
+        // Render each leg, and the area between the stop lines where they meet
+        let mut junction = Vec::new();
+        for (index, leg) in self.geometry.legs.iter().enumerate() {
+            fill_polygon(canvas, camera, &leg.strip(0.0, leg.length, 0.0, ROAD_WIDTH).map(world), road);
+            let stop_line = self.geometry.stop_line(index);
+            junction.push(world(leg.point(stop_line, -ROAD_WIDTH / 2.0)));
+            junction.push(world(leg.point(stop_line, ROAD_WIDTH / 2.0)));
+        }
+        fill_polygon(canvas, camera, &junction, road);
+
+        // Render lane markings, up to the stop lines
+        for (index, leg) in self.geometry.legs.iter().enumerate() {
+            let stop_line = self.geometry.stop_line(index);
+            let line = leg.strip(stop_line, leg.length, 0.0, LANE_MARKING_WIDTH).map(world);
+            fill_polygon(canvas, camera, &line, marking);
+        }
+
+        match self.control {
+            Control::Signals => {}
+            Control::Roundabout(roundabout) => roundabout.render(canvas, camera, self.center),
+            Control::Stop(stop) => stop.render(canvas, camera, self.center, &self.geometry, &self.major),
+        }
+    }
 
+    // Render traffic lights and vehicles, on top of the roads of every intersection
+    pub fn render_traffic(&self, canvas: &mut dyn Surface, camera: &Camera) {
         // Render traffic lights
         for light in &self.traffic_lights {
-            light.render(canvas);
+            light.render(canvas, camera);
         }
 
         // Render vehicles
         for vehicle in &self.vehicles {
-            vehicle.render(canvas);
+            vehicle.render(canvas, camera);
         }
     }
 }
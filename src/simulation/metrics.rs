@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+use crate::constants::{THROUGHPUT_WINDOW, TICKS_PER_SECOND};
+use crate::simulation::intersection::ms_to_ticks;
+
+// Running counters for delay and throughput
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub spawned: u64,
+    pub exited: u64,
+    pub total_delay_ticks: u64, // Summed over vehicles that have exited
+    pub phase_changes: u64,
+    pub collisions: u64, // Pairs of vehicles that came to overlap
+    pub red_light_runs: u64, // Vehicles that crossed the stop line on red
+    recent_exits: VecDeque<u64>, // Exit ticks within the throughput window
+}
+
+impl Metrics {
+    pub fn record_spawn(&mut self) {
+        self.spawned += 1;
+    }
+
+    // Count a vehicle leaving, along with the ticks it spent stopped
+    pub fn record_exit(&mut self, tick: u64, delay_ticks: u64) {
+        self.exited += 1;
+        self.total_delay_ticks += delay_ticks;
+        self.recent_exits.push_back(tick);
+
+        let window = ms_to_ticks(THROUGHPUT_WINDOW);
+        while self.recent_exits.front().is_some_and(|&exit| exit + window < tick) {
+            self.recent_exits.pop_front();
+        }
+    }
+
+    // Average time exited vehicles spent stopped, in seconds
+    pub fn average_delay(&self) -> f32 {
+        if self.exited == 0 {
+            return 0.0;
+        }
+        self.total_delay_ticks as f32 / self.exited as f32 / TICKS_PER_SECOND as f32
+    }
+
+    // Vehicles per hour over the throughput window ending at the given tick
+    pub fn throughput(&self, tick: u64) -> f32 {
+        let window = ms_to_ticks(THROUGHPUT_WINDOW);
+        let exits = self
+            .recent_exits
+            .iter()
+            .filter(|&&exit| exit + window >= tick)
+            .count();
+        let elapsed = tick.clamp(1, window) as f32 / TICKS_PER_SECOND as f32;
+        exits as f32 * 3600.0 / elapsed
+    }
+}
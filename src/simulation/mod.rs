@@ -1 +1,19 @@
+pub mod coordination;
+pub mod dashboard;
+pub mod env;
+pub mod history;
 pub mod intersection;
+pub mod metrics;
+pub mod network;
+pub mod osm;
+pub mod prometheus;
+pub mod replay;
+pub mod roundabout;
+pub mod safety;
+pub mod saturation;
+pub mod server;
+pub mod stop_control;
+pub mod sumo;
+pub mod time_space;
+pub mod trajectory;
+pub mod xml;
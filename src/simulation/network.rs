@@ -0,0 +1,610 @@
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::constants::APPROACH_LENGTH;
+use crate::models::direction::Direction;
+use crate::models::driver::DriverPopulation;
+use crate::models::junction::{JunctionGeometry, Movement};
+use crate::models::route::Route;
+use crate::models::vehicle::Vehicle;
+use crate::simulation::coordination::SignalPlan;
+use crate::simulation::intersection::{Control, Intersection};
+use crate::simulation::metrics::Metrics;
+use crate::simulation::osm;
+use crate::simulation::replay::InputEvent;
+use crate::simulation::sumo;
+use crate::ui::camera::Camera;
+use crate::ui::surface::Surface;
+
+// Mixed into the seed for the generator drivers are drawn from
+const DRIVER_SEED: u64 = 0x6472_6976_6572;
+
+// Size of a grid of intersections, the distance between neighbours, how
+// every junction is controlled and, for a single junction, its legs; or the
+// map the network was imported from instead. Also who drives in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkLayout {
+    pub rows: usize,
+    pub columns: usize,
+    pub link_length: f32, // Center to center, in metres
+    pub control: Control,
+    pub legs: Option<Vec<f32>>, // Leg angles in degrees, a crossing if not given
+    pub map: Option<String>,    // OpenStreetMap or SUMO network file, replacing the grid
+    pub drivers: DriverPopulation,
+}
+
+impl Default for NetworkLayout {
+    // A single intersection with the standard approaches
+    fn default() -> Self {
+        NetworkLayout {
+            rows: 1,
+            columns: 1,
+            link_length: 2.0 * APPROACH_LENGTH,
+            control: Control::Signals,
+            legs: None,
+            map: None,
+            drivers: DriverPopulation::default(),
+        }
+    }
+}
+
+// Junction of a road graph: where it is, its legs and how it is controlled
+#[derive(Debug, Clone, PartialEq)]
+pub struct JunctionSite {
+    pub center: (f32, f32),
+    pub geometry: JunctionGeometry,
+    pub control: Control,
+}
+
+// Junctions and the links joining their legs, in the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoadGraph {
+    pub junctions: Vec<JunctionSite>,
+    pub links: Vec<Vec<Option<(usize, usize)>>>, // Per junction and leg, the junction and its leg at the far end
+}
+
+impl RoadGraph {
+    // Road graph a layout describes: its imported map, or else its grid
+    //
+    // Imported junctions keep the control the map gives them unless the
+    // layout asks for another one than signals.
+    pub fn of(layout: &NetworkLayout) -> Result<Self, String> {
+        let Some(path) = &layout.map else {
+            return Ok(Self::grid(layout));
+        };
+        let mut graph = if sumo::is_network(path) {
+            sumo::import(path)?.0
+        } else {
+            osm::import(path)?
+        };
+        if layout.control != Control::Signals {
+            for junction in &mut graph.junctions {
+                junction.control = layout.control;
+            }
+        }
+        Ok(graph)
+    }
+
+    // Grid of identical junctions centered on the origin, neighbours joined
+    // by the legs that point at each other
+    pub fn grid(layout: &NetworkLayout) -> Self {
+        let half_link = layout.link_length / 2.0;
+        let geometry = match &layout.legs {
+            Some(degrees) => JunctionGeometry::from_degrees(degrees, half_link).expect("Invalid junction legs"),
+            None => JunctionGeometry::cross(half_link),
+        };
+        let mut junctions = Vec::with_capacity(layout.rows * layout.columns);
+        for row in 0..layout.rows {
+            for column in 0..layout.columns {
+                // Center the grid on the origin
+                let center = (
+                    (column as f32 - (layout.columns - 1) as f32 / 2.0) * layout.link_length,
+                    (row as f32 - (layout.rows - 1) as f32 / 2.0) * layout.link_length,
+                );
+                junctions.push(JunctionSite {
+                    center,
+                    geometry: geometry.clone(),
+                    control: layout.control,
+                });
+            }
+        }
+
+        let links = (0..junctions.len())
+            .map(|index| {
+                let geometry = &junctions[index].geometry;
+                (0..geometry.legs.len())
+                    .map(|leg| {
+                        let direction = Direction::ALL
+                            .into_iter()
+                            .find(|&direction| geometry.leg_towards(direction) == Some(leg))?;
+                        let next = grid_neighbour(layout, index, direction)?;
+                        let back = junctions[next].geometry.leg_towards(direction.opposite())?;
+                        Some((next, back))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        RoadGraph { junctions, links }
+    }
+}
+
+// Intersections joined by road links, a grid or an imported road graph
+//
+// Grid intersections are stored row by row from the north-west corner. Each
+// one covers half a link in every direction, so neighbouring areas meet
+// halfway along the link, and each runs its own traffic light controller.
+// Links join the leg of one intersection to the leg of its neighbour that
+// points back at it. A vehicle that drives out along a linked leg is handed
+// over to the neighbour, or leaves the network along a leg without a link.
+// While the approach it would enter is full, it waits at the end of its exit
+// instead, so queues spill back from one intersection into the previous one.
+#[derive(Clone)]
+pub struct Network {
+    pub intersections: Vec<Intersection>,
+    pub layout: NetworkLayout,
+    pub tick: u64,
+    pub seed: u64,
+    pub metrics: Metrics, // Vehicles entering and leaving the network as a whole
+    pub next_vehicle_id: u32,
+    links: Vec<Vec<Option<(usize, usize)>>>, // Per intersection and leg, the neighbour and its leg at the far end
+    exit_hops: Vec<Vec<Option<usize>>>,      // Per intersection and leg, links still to drive to leave the network
+    rng: StdRng,
+    driver_rng: StdRng, // Apart from `rng`, so drivers do not change the routes vehicles take
+}
+
+impl Network {
+    pub fn new(seed: u64, layout: NetworkLayout) -> Self {
+        let graph = RoadGraph::grid(&layout);
+        Self::from_graph(seed, layout, graph)
+    }
+
+    // Network of the junctions of a road graph, such as one imported from a map
+    pub fn from_graph(seed: u64, layout: NetworkLayout, graph: RoadGraph) -> Self {
+        let intersections: Vec<Intersection> = graph
+            .junctions
+            .into_iter()
+            .enumerate()
+            .map(|(index, site)| Intersection::at(seed.wrapping_add(index as u64), site.center, site.geometry, site.control))
+            .collect();
+        let exit_hops = exit_hops(&intersections, &graph.links);
+
+        Network {
+            intersections,
+            layout,
+            tick: 0,
+            seed,
+            metrics: Metrics::default(),
+            next_vehicle_id: 1,
+            links: graph.links,
+            exit_hops,
+            rng: StdRng::seed_from_u64(seed),
+            driver_rng: StdRng::seed_from_u64(seed ^ DRIVER_SEED),
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.tick += 1;
+
+        // An exit is blocked while the approach it leads into has no room
+        for index in 0..self.intersections.len() {
+            let blocked_exits = (0..self.links[index].len())
+                .map(|leg| {
+                    self.neighbour(index, leg)
+                        .is_some_and(|(next, back)| !self.intersections[next].entry_is_clear(back))
+                })
+                .collect();
+            self.intersections[index].blocked_exits = blocked_exits;
+        }
+
+        let mut departed = Vec::new();
+        for (index, intersection) in self.intersections.iter_mut().enumerate() {
+            departed.extend(intersection.update().into_iter().map(|vehicle| (index, vehicle)));
+        }
+
+        // Hand vehicles over to the next intersection, or let them leave the network
+        for (index, mut vehicle) in departed {
+            match self.neighbour(index, vehicle.path.movement.to) {
+                Some((next, back)) => {
+                    let intersection = &mut self.intersections[next];
+                    let exit = vehicle
+                        .next_exits
+                        .pop_front()
+                        .unwrap_or_else(|| intersection.geometry.straightest_exit(back));
+                    vehicle.enter_junction(intersection.center, intersection.path(Movement { from: back, to: exit }));
+                    vehicle.runs_red = self.driver_rng.gen::<f32>() >= vehicle.driver.compliance;
+                    intersection.admit(vehicle);
+                }
+                None => self.metrics.record_exit(self.tick, vehicle.waiting_ticks),
+            }
+        }
+    }
+
+    // Intersection at the far end of a leg, and its leg leading back, if the
+    // leg is linked to a neighbour
+    pub fn neighbour(&self, index: usize, leg: usize) -> Option<(usize, usize)> {
+        self.links.get(index)?.get(leg).copied().flatten()
+    }
+
+    // Whether traffic coming in by a leg enters the network there, i.e. the
+    // leg comes from the edge of the network and may be driven in by
+    pub fn is_entry(&self, index: usize, leg: usize) -> bool {
+        leg < self.links[index].len()
+            && self.neighbour(index, leg).is_none()
+            && self.intersections[index].geometry.legs[leg].entry
+    }
+
+    // Whether a vehicle can enter the network by a leg of an intersection
+    // now, without coming too close to the last one that entered there
+    pub fn can_enter(&self, entry: usize, leg: usize) -> bool {
+        entry < self.intersections.len() && self.is_entry(entry, leg) && self.intersections[entry].can_spawn(leg)
+    }
+
+    // Spawn a vehicle entering the network in a direction
+    //
+    // Traffic comes in by the leg it enters most directly in that direction.
+    // Without an entry intersection one is picked at random along the edge
+    // the direction comes from.
+    pub fn spawn_vehicle(&mut self, direction: Direction, route: Option<Route>, entry: Option<usize>) {
+        let leg_at = |network: &Self, index: usize| network.intersections[index].geometry.leg_from(direction);
+        let entry = match entry {
+            Some(index) if index < self.intersections.len() && self.is_entry(index, leg_at(self, index)) => index,
+            Some(_) => return,
+            None => {
+                let entries: Vec<usize> = (0..self.intersections.len())
+                    .filter(|&index| self.is_entry(index, leg_at(self, index)))
+                    .collect();
+                // Only draw when there is a choice, so a single intersection
+                // gets the same routes as on its own
+                match entries[..] {
+                    [] => return,
+                    [only] => only,
+                    _ => entries[self.rng.gen_range(0..entries.len())],
+                }
+            }
+        };
+        self.spawn_at(entry, leg_at(self, entry), route);
+    }
+
+    // Spawn a vehicle entering the network by a leg of an intersection
+    //
+    // The exit it takes there is the one closest to the given route, or a
+    // random one, and its exits at the intersections after it are random,
+    // planned up front until the vehicle leaves the grid.
+    pub fn spawn_at(&mut self, entry: usize, leg: usize, route: Option<Route>) {
+        if !self.can_enter(entry, leg) {
+            return;
+        }
+
+        let geometry = &self.intersections[entry].geometry;
+        let exit = match route {
+            Some(route) => match geometry.exit_for(leg, route) {
+                Some(exit) => exit,
+                None => return,
+            },
+            None => {
+                let exits = geometry.exits(leg);
+                if exits.is_empty() {
+                    return;
+                }
+                exits[self.rng.gen_range(0..exits.len())]
+            }
+        };
+        self.spawn_to(entry, leg, exit);
+    }
+
+    // Spawn a vehicle entering the network by a leg of an intersection and
+    // leaving that intersection by a given exit, with random exits after it
+    pub fn spawn_to(&mut self, entry: usize, leg: usize, exit: usize) {
+        if !self.can_enter(entry, leg) || !self.intersections[entry].geometry.exits(leg).contains(&exit) {
+            return;
+        }
+        let next_exits = self.plan_exits(entry, exit);
+
+        let intersection = &mut self.intersections[entry];
+        let driver = self.layout.drivers.sample(&mut self.driver_rng);
+        let mut vehicle = Vehicle::new(
+            self.next_vehicle_id,
+            intersection.center,
+            intersection.path(Movement { from: leg, to: exit }),
+            driver,
+        );
+        vehicle.next_exits = next_exits;
+        vehicle.runs_red = self.driver_rng.gen::<f32>() >= driver.compliance;
+        intersection.spawn(vehicle);
+        self.next_vehicle_id += 1;
+        self.metrics.record_spawn();
+    }
+
+    // Random exits at the intersections a vehicle leaving one by a leg
+    // passes, until it drives off the network
+    //
+    // After as many turns as it takes to cross a grid, or as there are
+    // intersections in an imported network, vehicles take the straightest
+    // exit so that every route ends. Where that would lead back to an
+    // intersection they already passed, they take the exit closest to
+    // leaving the network instead.
+    fn plan_exits(&mut self, from: usize, mut leg: usize) -> VecDeque<usize> {
+        let random_turns = match self.layout.map {
+            Some(_) => self.intersections.len(),
+            None => self.layout.rows + self.layout.columns,
+        };
+        let mut exits = VecDeque::new();
+        let mut visited = vec![from];
+        let mut current = from;
+        while let Some((next, back)) = self.neighbour(current, leg) {
+            let geometry = &self.intersections[next].geometry;
+            let options = geometry.exits(back);
+            if options.is_empty() {
+                break;
+            }
+            leg = if exits.len() < random_turns {
+                options[self.rng.gen_range(0..options.len())]
+            } else {
+                let straightest = geometry.straightest_exit(back);
+                let revisits = self
+                    .neighbour(next, straightest)
+                    .is_some_and(|(after, _)| visited.contains(&after));
+                if !revisits && self.exit_hops[next][straightest].is_some() {
+                    straightest
+                } else {
+                    let closest = options
+                        .into_iter()
+                        .filter_map(|exit| Some((self.exit_hops[next][exit]?, exit)))
+                        .min();
+                    match closest {
+                        Some((_, exit)) => exit,
+                        None => break,
+                    }
+                }
+            };
+            exits.push_back(leg);
+            visited.push(next);
+            current = next;
+        }
+        exits
+    }
+
+    pub fn vehicles(&self) -> impl Iterator<Item = &Vehicle> {
+        self.intersections.iter().flat_map(|intersection| &intersection.vehicles)
+    }
+
+    pub fn vehicle(&self, id: u32) -> Option<&Vehicle> {
+        self.vehicles().find(|v| v.id == id)
+    }
+
+    pub fn remove_vehicle(&mut self, id: u32) {
+        for intersection in &mut self.intersections {
+            intersection.remove_vehicle(id);
+        }
+    }
+
+    // Vehicle under a point in the world, if any
+    pub fn vehicle_at(&self, x: f32, y: f32) -> Option<&Vehicle> {
+        self.intersections
+            .iter()
+            .find_map(|intersection| intersection.vehicle_at(x, y))
+    }
+
+    // Intersection and leg of the traffic light under a point in the world, if any
+    pub fn traffic_light_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        self.intersections
+            .iter()
+            .enumerate()
+            .find_map(|(index, intersection)| intersection.traffic_light_at(x, y).map(|leg| (index, leg)))
+    }
+
+    // Intersection and leg of the entry approach under a point in the world, if any
+    pub fn entry_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        self.intersections.iter().enumerate().find_map(|(index, intersection)| {
+            intersection
+                .approach_at(x, y)
+                .filter(|&leg| self.is_entry(index, leg))
+                .map(|leg| (index, leg))
+        })
+    }
+
+    // Intersection whose center is closest to a point in the world
+    pub fn nearest_intersection(&self, x: f32, y: f32) -> usize {
+        let distance = |intersection: &Intersection| {
+            (intersection.center.0 - x).powi(2) + (intersection.center.1 - y).powi(2)
+        };
+        (0..self.intersections.len())
+            .min_by(|&a, &b| distance(&self.intersections[a]).total_cmp(&distance(&self.intersections[b])))
+            .unwrap_or(0)
+    }
+
+    // Half the width and height of the area around the origin covered by
+    // the intersections
+    pub fn extent(&self) -> (f32, f32) {
+        self.intersections.iter().fold((0.0, 0.0), |(width, height), intersection| {
+            let (x, y) = intersection.center;
+            (
+                f32::max(width, x.abs() + intersection.approach_length),
+                f32::max(height, y.abs() + intersection.approach_length),
+            )
+        })
+    }
+
+    // Run every intersection on a shared cycle with its own offset
+    pub fn apply_signal_plan(&mut self, plan: &SignalPlan) {
+        for (intersection, &offset) in self.intersections.iter_mut().zip(&plan.offsets) {
+            intersection.set_signal_timing(plan.cycle_ticks, offset);
+        }
+    }
+
+    // Manual override: switch every intersection to its next phase
+    pub fn switch_traffic_lights(&mut self) {
+        for intersection in &mut self.intersections {
+            intersection.switch_traffic_lights();
+        }
+    }
+
+    pub fn force_green(&mut self, index: usize, leg: usize) {
+        if let Some(intersection) = self.intersections.get_mut(index) {
+            intersection.force_green(leg);
+        }
+    }
+
+    pub fn toggle_phase_hold(&mut self, index: usize) {
+        if let Some(intersection) = self.intersections.get_mut(index) {
+            intersection.toggle_phase_hold();
+        }
+    }
+
+    pub fn select_phase(&mut self, index: usize, phase: usize) {
+        if let Some(intersection) = self.intersections.get_mut(index) {
+            intersection.select_phase(phase);
+        }
+    }
+
+    // Apply an input that changes the simulation; inputs that only change
+    // how it is viewed are left to the viewer
+    pub fn apply_input(&mut self, input: InputEvent) {
+        match input {
+            InputEvent::Spawn { direction, route, entry } => self.spawn_vehicle(direction, route, entry),
+            InputEvent::SpawnAt { entry, leg, route } => self.spawn_at(entry, leg, route),
+            InputEvent::SpawnTo { entry, leg, exit } => self.spawn_to(entry, leg, exit),
+            InputEvent::SwitchLights => self.switch_traffic_lights(),
+            InputEvent::ForceGreen(index, leg) => self.force_green(index, leg),
+            InputEvent::HoldPhase(index) => self.toggle_phase_hold(index),
+            InputEvent::SelectPhase(index, phase) => self.select_phase(index, phase),
+            InputEvent::Remove(id) => self.remove_vehicle(id),
+            InputEvent::Pause | InputEvent::Resume | InputEvent::Speed(_) | InputEvent::Step => {}
+        }
+    }
+
+    pub fn render(&self, canvas: &mut dyn Surface, camera: &Camera) {
+        // Vehicles are handed over a little past the end of an approach, so
+        // all roads go down before any traffic
+        for intersection in &self.intersections {
+            intersection.render_roads(canvas, camera);
+        }
+        for intersection in &self.intersections {
+            intersection.render_traffic(canvas, camera);
+        }
+    }
+}
+
+// Links still to drive after leaving each intersection by each leg until
+// out of the network, where there is a way out
+fn exit_hops(intersections: &[Intersection], links: &[Vec<Option<(usize, usize)>>]) -> Vec<Vec<Option<usize>>> {
+    let mut hops: Vec<Vec<Option<usize>>> = links
+        .iter()
+        .map(|legs| legs.iter().map(|link| link.is_none().then_some(0)).collect())
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..links.len() {
+            for leg in 0..links[index].len() {
+                let Some((next, back)) = links[index][leg] else {
+                    continue;
+                };
+                let Some(best) = intersections[next]
+                    .geometry
+                    .exits(back)
+                    .into_iter()
+                    .filter_map(|exit| hops[next][exit])
+                    .min()
+                else {
+                    continue;
+                };
+                if hops[index][leg].is_none_or(|current| best + 1 < current) {
+                    hops[index][leg] = Some(best + 1);
+                    changed = true;
+                }
+            }
+        }
+    }
+    hops
+}
+
+// Intersection next to another one in a direction, if the grid goes on that way
+fn grid_neighbour(layout: &NetworkLayout, index: usize, direction: Direction) -> Option<usize> {
+    let (row, column) = (index / layout.columns, index % layout.columns);
+    match direction {
+        Direction::North if row > 0 => Some(index - layout.columns),
+        Direction::South if row + 1 < layout.rows => Some(index + layout.columns),
+        Direction::East if column + 1 < layout.columns => Some(index + 1),
+        Direction::West if column > 0 => Some(index - 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TICKS_PER_SECOND;
+    use crate::models::driver::DriverProfile;
+    use crate::models::vehicle::StopReason;
+
+    // Two crossings side by side, 0 to the west and 1 to the east
+    fn pair() -> Network {
+        Network::new(1, NetworkLayout { columns: 2, ..NetworkLayout::default() })
+    }
+
+    // Intersection the vehicle is in, if it is still in the network
+    fn location(network: &Network, id: u32) -> Option<usize> {
+        network.intersections.iter().position(|intersection| intersection.vehicles.iter().any(|v| v.id == id))
+    }
+
+    #[test]
+    fn vehicles_are_handed_over_and_leave_once() {
+        let mut network = pair();
+        for _ in 0..TICKS_PER_SECOND {
+            network.update();
+        }
+        // Coming in from the east and heading west
+        network.spawn_at(1, 0, Some(Route::Straight));
+        assert_eq!(location(&network, 1), Some(1));
+
+        let mut visited = Vec::new();
+        while network.tick < 300 * TICKS_PER_SECOND && network.metrics.exited == 0 {
+            network.update();
+            if let Some(index) = location(&network, 1) {
+                if visited.last() != Some(&index) {
+                    visited.push(index);
+                }
+            }
+        }
+        assert_eq!(visited, [1, 0]);
+        assert_eq!((network.metrics.spawned, network.metrics.exited), (1, 1));
+        assert_eq!(network.vehicles().count(), 0);
+    }
+
+    #[test]
+    fn full_approach_holds_traffic_at_the_exit_before_it() {
+        let mut network = pair();
+        for _ in 0..TICKS_PER_SECOND {
+            network.update();
+        }
+        // A vehicle that never moves, at the start of 0's approach from 1
+        let intersection = &mut network.intersections[0];
+        let driver = DriverProfile {
+            desired_speed: 0.0,
+            ..DriverProfile::default()
+        };
+        let blocker = Vehicle::new(100, intersection.center, intersection.path(Movement { from: 0, to: 2 }), driver);
+        intersection.admit(blocker);
+        assert!(!network.intersections[0].entry_is_clear(0));
+
+        network.spawn_at(1, 0, Some(Route::Straight));
+        for _ in 0..120 * TICKS_PER_SECOND {
+            network.update();
+        }
+        let vehicle = network.vehicle(1).unwrap();
+        assert_eq!(location(&network, 1), Some(1));
+        assert_eq!(vehicle.stop_reason, Some(StopReason::Spillback));
+        assert!(vehicle.travelled > vehicle.path.clear_at);
+
+        // Once there is room it drives on into the next intersection
+        network.remove_vehicle(100);
+        for _ in 0..TICKS_PER_SECOND {
+            network.update();
+        }
+        assert_eq!(location(&network, 1), Some(0));
+    }
+}
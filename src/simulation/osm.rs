@@ -0,0 +1,764 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fs;
+
+use crate::constants::{APPROACH_LENGTH, MAX_LEGS, MIN_LEG_ANGLE, MIN_LINK_LENGTH};
+use crate::models::geometry::distance;
+use crate::models::junction::{JunctionGeometry, Leg, Movement};
+use crate::simulation::intersection::Control;
+use crate::simulation::network::{JunctionSite, RoadGraph};
+use crate::simulation::stop_control::{StopControl, StopRule};
+use crate::simulation::xml::{Tag, Tags};
+
+// Mean radius of the earth, in metres
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+// Furthest a traffic signal node may be along a road from a junction and
+// still control it, in metres
+const SIGNAL_REACH: f32 = 30.0;
+
+// Values of the `highway` tag of roads that carry traffic, from the most
+// important to the least; `_link` roads count as their class
+const HIGHWAYS: [&str; 10] = [
+    "motorway",
+    "trunk",
+    "primary",
+    "secondary",
+    "tertiary",
+    "unclassified",
+    "residential",
+    "living_street",
+    "service",
+    "road",
+];
+
+// Road read from a `highway` way
+struct Road {
+    id: i64,
+    nodes: Vec<i64>,
+    priority: u32,
+    forward: bool,  // Traffic may drive in the order of the nodes
+    backward: bool, // Traffic may drive against it
+}
+
+// Turn restriction relation with a node as its via member
+struct Restriction {
+    from: i64, // Way
+    via: i64,  // Node
+    to: i64,   // Way
+    only: bool, // `only_*`: every other turn from the way is banned; otherwise `no_*`: this one is
+}
+
+// Contents of an OSM file that matter for the road network
+#[derive(Default)]
+struct OsmData {
+    nodes: HashMap<i64, ((f64, f64), bool)>, // Latitude and longitude, and whether it is a traffic signal
+    roads: Vec<Road>,
+    restrictions: Vec<Restriction>,
+}
+
+// Element being read, with what was collected from its children so far
+enum Element {
+    None,
+    Node(i64, (f64, f64), bool),
+    Way(i64, Vec<i64>, HashMap<String, String>),
+    Relation(Vec<(String, i64, String)>, HashMap<String, String>), // Members' type, ref and role
+}
+
+fn read(text: &str) -> Result<OsmData, String> {
+    let mut data = OsmData::default();
+    let mut element = Element::None;
+    for tag in Tags::new(text) {
+        let tag = tag?;
+        match &tag {
+            Tag::Start { name, empty, .. } => {
+                let id = || -> Result<i64, String> {
+                    let id = tag.attribute("id").ok_or(format!("<{}> without an id", name))?;
+                    id.parse().map_err(|_| format!("Invalid id '{}'", id))
+                };
+                match *name {
+                    "node" => {
+                        let coordinate = |key: &str| -> Result<f64, String> {
+                            let value = tag.attribute(key).ok_or(format!("Node without {}", key))?;
+                            value.parse().map_err(|_| format!("Invalid {} '{}'", key, value))
+                        };
+                        element = Element::Node(id()?, (coordinate("lat")?, coordinate("lon")?), false);
+                    }
+                    "way" => element = Element::Way(id()?, Vec::new(), HashMap::new()),
+                    "relation" => element = Element::Relation(Vec::new(), HashMap::new()),
+                    "nd" => {
+                        if let (Element::Way(_, nodes, _), Some(node)) = (&mut element, tag.attribute("ref")) {
+                            nodes.push(node.parse().map_err(|_| format!("Invalid node ref '{}'", node))?);
+                        }
+                    }
+                    "member" => {
+                        if let Element::Relation(members, _) = &mut element {
+                            let member = tag.attribute("ref").and_then(|id| id.parse().ok());
+                            if let (Some(kind), Some(member)) = (tag.attribute("type"), member) {
+                                members.push((kind.to_string(), member, tag.attribute("role").unwrap_or("").to_string()));
+                            }
+                        }
+                    }
+                    "tag" => {
+                        let (Some(key), Some(value)) = (tag.attribute("k"), tag.attribute("v")) else {
+                            continue;
+                        };
+                        match &mut element {
+                            Element::Node(_, _, signals) => {
+                                *signals |= key == "highway" && value == "traffic_signals";
+                            }
+                            Element::Way(_, _, tags) | Element::Relation(_, tags) => {
+                                tags.insert(key.to_string(), value.to_string());
+                            }
+                            Element::None => {}
+                        }
+                    }
+                    _ => {}
+                }
+                if *empty && matches!(*name, "node" | "way" | "relation") {
+                    finish(&mut data, std::mem::replace(&mut element, Element::None));
+                }
+            }
+            Tag::End(name) => {
+                if matches!(*name, "node" | "way" | "relation") {
+                    finish(&mut data, std::mem::replace(&mut element, Element::None));
+                }
+            }
+        }
+    }
+    Ok(data)
+}
+
+// Keep a node, a road or a turn restriction once its element has been read
+fn finish(data: &mut OsmData, element: Element) {
+    match element {
+        Element::None => {}
+        Element::Node(id, position, signals) => {
+            data.nodes.insert(id, (position, signals));
+        }
+        Element::Way(id, nodes, tags) => {
+            let Some(highway) = tags.get("highway") else {
+                return;
+            };
+            let class = highway.strip_suffix("_link").unwrap_or(highway);
+            let Some(rank) = HIGHWAYS.iter().position(|&name| name == class) else {
+                return;
+            };
+
+            // Motorways and roundabouts are one-way unless tagged otherwise
+            let implied = class == "motorway" || matches!(tags.get("junction").map(String::as_str), Some("roundabout" | "circular"));
+            let (forward, backward) = match tags.get("oneway").map(String::as_str) {
+                Some("yes" | "true" | "1") => (true, false),
+                Some("-1" | "reverse") => (false, true),
+                Some("no" | "false" | "0") => (true, true),
+                _ => (true, !implied),
+            };
+            let lanes = tags
+                .get("lanes")
+                .and_then(|lanes| lanes.parse().ok())
+                .unwrap_or(if forward && backward { 2 } else { 1 });
+            data.roads.push(Road {
+                id,
+                nodes,
+                priority: (HIGHWAYS.len() - rank) as u32 * 10 + lanes.min(9),
+                forward,
+                backward,
+            });
+        }
+        Element::Relation(members, tags) => {
+            if tags.get("type").map(String::as_str) != Some("restriction") {
+                return;
+            }
+            let Some(kind) = tags.get("restriction").or_else(|| tags.get("restriction:motorcar")) else {
+                return;
+            };
+            let member = |kind: &str, role: &str| {
+                members
+                    .iter()
+                    .find(|(member_kind, _, member_role)| member_kind == kind && member_role == role)
+                    .map(|&(_, id, _)| id)
+            };
+            if let (Some(from), Some(via), Some(to)) = (member("way", "from"), member("node", "via"), member("way", "to")) {
+                if kind.starts_with("no_") || kind.starts_with("only_") {
+                    data.restrictions.push(Restriction {
+                        from,
+                        via,
+                        to,
+                        only: kind.starts_with("only_"),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Road between two places, which are junctions or the ends of roads
+#[derive(Debug, Clone)]
+struct Edge {
+    ends: [usize; 2],
+    forward: bool,  // Traffic may drive from the first end to the second
+    backward: bool, // Traffic may drive from the second end to the first
+    priority: u32,
+    length: f32,
+    ways: Vec<i64>,     // OSM ways the road is made of
+    signals: [bool; 2], // A traffic signal close to each end
+}
+
+impl Edge {
+    fn reversed(&self) -> Edge {
+        Edge {
+            ends: [self.ends[1], self.ends[0]],
+            forward: self.backward,
+            backward: self.forward,
+            signals: [self.signals[1], self.signals[0]],
+            ..self.clone()
+        }
+    }
+
+    // The same road, with `place` as its first end
+    fn leaving(&self, place: usize) -> Edge {
+        if self.ends[0] == place {
+            self.clone()
+        } else {
+            self.reversed()
+        }
+    }
+}
+
+// Places merged into junctions, each junction keeping the average position
+// and whether any of its places is a traffic signal
+struct Places {
+    parent: Vec<usize>,
+    position: Vec<(f64, f64, usize)>, // Sum of member positions and their count, on the root
+    signals: Vec<bool>,
+}
+
+impl Places {
+    fn add(&mut self, position: (f64, f64), signals: bool) -> usize {
+        self.parent.push(self.parent.len());
+        self.position.push((position.0, position.1, 1));
+        self.signals.push(signals);
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut place: usize) -> usize {
+        while self.parent[place] != place {
+            self.parent[place] = self.parent[self.parent[place]];
+            place = self.parent[place];
+        }
+        place
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            let (x, y, count) = self.position[b];
+            self.position[a].0 += x;
+            self.position[a].1 += y;
+            self.position[a].2 += count;
+            self.signals[a] |= self.signals[b];
+            self.parent[b] = a;
+        }
+    }
+
+    fn center(&self, root: usize) -> (f32, f32) {
+        let (x, y, count) = self.position[root];
+        ((x / count as f64) as f32, (y / count as f64) as f32)
+    }
+}
+
+// Import the road network of an OpenStreetMap XML extract
+//
+// Ways tagged as roads (`highway` of a class that carries traffic) become
+// links, and places where three or more of them meet become junctions,
+// centered on the origin. Bends along a road are straightened: each link
+// runs straight from one junction to the next. Junctions closer together
+// than the shortest link are merged, roads that leave a junction less than
+// the smallest leg angle apart are merged when they lead to the same place
+// and otherwise the less important one is dropped, as are the least
+// important roads of junctions with too many legs.
+//
+// `oneway` tags limit which legs can be driven in or out by, `no_*` and
+// `only_*` turn restrictions with a via node ban turns, and the road class
+// and `lanes` tag rank the legs; the simulation itself drives one lane each
+// way. Junctions with a `traffic_signals` node on them or within
+// `SIGNAL_REACH` along one of their roads get signals, the others a two-way
+// stop giving priority to the most important road.
+pub fn import(path: &str) -> Result<RoadGraph, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let data = read(&text).map_err(|e| format!("{}: {}", path, e))?;
+    build(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn build(data: &OsmData) -> Result<RoadGraph, String> {
+    // Parts of roads whose nodes are all in the extract
+    let runs: Vec<(&Road, Vec<i64>)> = data
+        .roads
+        .iter()
+        .flat_map(|road| {
+            road.nodes
+                .split(|node| !data.nodes.contains_key(node))
+                .filter(|run| run.len() >= 2)
+                .map(move |run| (road, run.to_vec()))
+        })
+        .collect();
+    if runs.is_empty() {
+        return Err("No roads found".to_string());
+    }
+
+    // Project onto a plane through the middle of the area, in metres
+    let (mut south, mut north, mut west, mut east) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for node in runs.iter().flat_map(|(_, run)| run) {
+        let ((lat, lon), _) = data.nodes[node];
+        (south, north, west, east) = (south.min(lat), north.max(lat), west.min(lon), east.max(lon));
+    }
+    let (lat0, lon0) = ((south + north) / 2.0, (west + east) / 2.0);
+    let project = |node: i64| {
+        let ((lat, lon), _) = data.nodes[&node];
+        (
+            (lon - lon0).to_radians() * lat0.to_radians().cos() * EARTH_RADIUS,
+            -(lat - lat0).to_radians() * EARTH_RADIUS,
+        )
+    };
+    let point = |node: i64| {
+        let (x, y) = project(node);
+        (x as f32, y as f32)
+    };
+
+    // Every node where a road ends or that is shared by roads is a place,
+    // and the roads between places are edges
+    let mut uses: HashMap<i64, usize> = HashMap::new();
+    for (_, run) in &runs {
+        for pair in run.windows(2) {
+            *uses.entry(pair[0]).or_default() += 1;
+            *uses.entry(pair[1]).or_default() += 1;
+        }
+    }
+    let mut places = Places {
+        parent: Vec::new(),
+        position: Vec::new(),
+        signals: Vec::new(),
+    };
+    let mut place_of: HashMap<i64, usize> = HashMap::new();
+    let mut edges = Vec::new();
+    for (road, run) in &runs {
+        let mut place_at = |node: i64, places: &mut Places| {
+            *place_of
+                .entry(node)
+                .or_insert_with(|| places.add(project(node), data.nodes[&node].1))
+        };
+        let mut start = 0;
+        let mut length = 0.0;
+        let mut signals_at = Vec::new();
+        for index in 1..run.len() {
+            length += distance(point(run[index - 1]), point(run[index]));
+            let is_place = index == run.len() - 1 || uses[&run[index]] != 2;
+            if !is_place {
+                if data.nodes[&run[index]].1 {
+                    signals_at.push(length);
+                }
+                continue;
+            }
+            edges.push(Edge {
+                ends: [place_at(run[start], &mut places), place_at(run[index], &mut places)],
+                forward: road.forward,
+                backward: road.backward,
+                priority: road.priority,
+                length,
+                ways: vec![road.id],
+                signals: [
+                    signals_at.iter().any(|&at| at <= SIGNAL_REACH),
+                    signals_at.iter().any(|&at| length - at <= SIGNAL_REACH),
+                ],
+            });
+            start = index;
+            length = 0.0;
+            signals_at.clear();
+        }
+    }
+
+    let mut junctions;
+    loop {
+        for edge in &mut edges {
+            edge.ends = [places.find(edge.ends[0]), places.find(edge.ends[1])];
+        }
+        edges.retain(|edge| edge.ends[0] != edge.ends[1] && (edge.forward || edge.backward));
+        let mut adjacency: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, edge) in edges.iter().enumerate() {
+            for end in edge.ends {
+                adjacency.entry(end).or_default().push(index);
+            }
+        }
+
+        // Merge junctions that are too close together to hold a link
+        let short: Vec<[usize; 2]> = edges
+            .iter()
+            .filter(|edge| {
+                roads_at(&adjacency, edge.ends[0]) >= 3
+                    && roads_at(&adjacency, edge.ends[1]) >= 3
+                    && distance(places.center(edge.ends[0]), places.center(edge.ends[1])) < MIN_LINK_LENGTH
+            })
+            .map(|edge| edge.ends)
+            .collect();
+        if !short.is_empty() {
+            for [a, b] in short {
+                places.union(a, b);
+            }
+            continue;
+        }
+
+        // Join the two roads at places where only two meet
+        let mut removed = vec![false; edges.len()];
+        let mut bends: Vec<usize> = adjacency.keys().copied().filter(|&place| roads_at(&adjacency, place) == 2).collect();
+        bends.sort_unstable();
+        let mut changed = false;
+        for place in bends {
+            let [first, second] = adjacency[&place][..] else {
+                continue;
+            };
+            if first == second {
+                continue;
+            }
+            let (before, after) = (edges[first].leaving(place).reversed(), edges[second].leaving(place));
+            let mut ways = before.ways.clone();
+            ways.extend(after.ways.iter().filter(|way| !before.ways.contains(way)));
+            edges[first] = Edge {
+                ends: [before.ends[0], after.ends[1]],
+                forward: before.forward && after.forward,
+                backward: before.backward && after.backward,
+                priority: before.priority.min(after.priority),
+                length: before.length + after.length,
+                ways,
+                signals: [before.signals[0], after.signals[1]],
+            };
+            removed[second] = true;
+            adjacency.remove(&place);
+            if let Some(list) = adjacency.get_mut(&after.ends[1]) {
+                for index in list.iter_mut().filter(|index| **index == second) {
+                    *index = first;
+                }
+            }
+            changed = true;
+        }
+        if changed {
+            edges = drop_removed(edges, &removed);
+            continue;
+        }
+
+        // Merge or drop roads that leave a junction too close to another,
+        // and the least important roads of junctions with too many
+        let mut candidates: Vec<usize> = adjacency.keys().copied().filter(|&place| roads_at(&adjacency, place) >= 3).collect();
+        candidates.sort_unstable();
+        for &place in &candidates {
+            let legs = sorted_legs(place, &adjacency[&place], &edges, &places);
+            if legs.iter().any(|&(edge, _)| removed[edge]) {
+                continue;
+            }
+            let close = (0..legs.len()).find(|&index| {
+                let next = (index + 1) % legs.len();
+                let gap = (legs[next].1 - legs[index].1).rem_euclid(TAU);
+                gap < MIN_LEG_ANGLE.to_radians()
+            });
+            if let Some(index) = close {
+                let (a, b) = (legs[index].0, legs[(index + 1) % legs.len()].0);
+                let (first, second) = (edges[a].leaving(place), edges[b].leaving(place));
+                if first.ends[1] == second.ends[1] {
+                    edges[a] = Edge {
+                        forward: first.forward || second.forward,
+                        backward: first.backward || second.backward,
+                        priority: first.priority.max(second.priority),
+                        length: first.length.min(second.length),
+                        ways: [first.ways.clone(), second.ways.clone()].concat(),
+                        signals: [first.signals[0] || second.signals[0], first.signals[1] || second.signals[1]],
+                        ..first
+                    };
+                    removed[b] = true;
+                } else if first.priority < second.priority {
+                    removed[a] = true;
+                } else {
+                    removed[b] = true;
+                }
+                changed = true;
+            } else if legs.len() > MAX_LEGS {
+                let least = legs.iter().min_by_key(|&&(edge, _)| edges[edge].priority).map(|&(edge, _)| edge);
+                if let Some(edge) = least {
+                    removed[edge] = true;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            edges = drop_removed(edges, &removed);
+            continue;
+        }
+
+        // Junctions with their legs, turn restrictions applied
+        junctions = Vec::new();
+        for &place in &candidates {
+            let legs = sorted_legs(place, &adjacency[&place], &edges, &places);
+            let mut geometry = JunctionGeometry::from_legs(
+                legs.iter()
+                    .map(|&(index, angle)| {
+                        let edge = edges[index].leaving(place);
+                        let length = distance(places.center(place), places.center(edge.ends[1]));
+                        Leg {
+                            angle,
+                            length: if roads_at(&adjacency, edge.ends[1]) >= 3 {
+                                length / 2.0
+                            } else {
+                                length.clamp(MIN_LINK_LENGTH / 2.0, APPROACH_LENGTH)
+                            },
+                            entry: edge.backward,
+                            exit: edge.forward,
+                            priority: edge.priority,
+                        }
+                    })
+                    .collect(),
+            )?;
+            let leg_of_way = |way: i64| {
+                let mut matching = legs.iter().enumerate().filter(|(_, &(edge, _))| edges[edge].ways.contains(&way));
+                match (matching.next(), matching.next()) {
+                    (Some((leg, _)), None) => Some(leg),
+                    _ => None,
+                }
+            };
+            for restriction in &data.restrictions {
+                let Some(&via) = place_of.get(&restriction.via) else {
+                    continue;
+                };
+                if places.find(via) != place {
+                    continue;
+                }
+                let (Some(from), Some(to)) = (leg_of_way(restriction.from), leg_of_way(restriction.to)) else {
+                    continue;
+                };
+                if restriction.only {
+                    geometry.banned.extend(
+                        (0..legs.len())
+                            .filter(|&other| other != to && other != from)
+                            .map(|other| Movement { from, to: other }),
+                    );
+                } else {
+                    geometry.banned.push(Movement { from, to });
+                }
+            }
+            junctions.push((place, legs, geometry));
+        }
+
+        // Stop traffic driving towards a junction it can not leave again
+        for (place, legs, geometry) in &junctions {
+            for (leg, &(index, _)) in legs.iter().enumerate() {
+                if geometry.legs[leg].entry && geometry.exits(leg).is_empty() {
+                    let edge = &mut edges[index];
+                    if edge.ends[1] == *place {
+                        edge.forward = false;
+                    } else {
+                        edge.backward = false;
+                    }
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    if junctions.is_empty() {
+        return Err("No junctions of 3 or more roads found".to_string());
+    }
+
+    // Center the junctions on the origin and link the legs between them
+    let centers: Vec<(f32, f32)> = junctions.iter().map(|(place, _, _)| places.center(*place)).collect();
+    let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for &(x, y) in &centers {
+        (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+    }
+    let middle = ((left + right) / 2.0, (top + bottom) / 2.0);
+    let index_of: HashMap<usize, usize> = junctions
+        .iter()
+        .enumerate()
+        .map(|(index, (place, _, _))| (*place, index))
+        .collect();
+
+    let links = junctions
+        .iter()
+        .map(|(place, legs, _)| {
+            legs.iter()
+                .map(|&(edge, _)| {
+                    let other = edges[edge].leaving(*place).ends[1];
+                    let next = *index_of.get(&other)?;
+                    let back = junctions[next].1.iter().position(|&(index, _)| index == edge)?;
+                    Some((next, back))
+                })
+                .collect()
+        })
+        .collect();
+    let sites = junctions
+        .into_iter()
+        .zip(centers)
+        .map(|((place, legs, geometry), (x, y))| {
+            let signals = places.signals[place]
+                || legs.iter().any(|&(edge, _)| edges[edge].leaving(place).signals[0]);
+            JunctionSite {
+                center: (x - middle.0, y - middle.1),
+                geometry,
+                control: if signals {
+                    Control::Signals
+                } else {
+                    Control::Stop(StopControl::new(StopRule::TwoWay))
+                },
+            }
+        })
+        .collect();
+
+    Ok(RoadGraph {
+        junctions: sites,
+        links,
+    })
+}
+
+// Number of roads meeting at a place
+fn roads_at(adjacency: &HashMap<usize, Vec<usize>>, place: usize) -> usize {
+    adjacency.get(&place).map_or(0, Vec::len)
+}
+
+fn drop_removed(edges: Vec<Edge>, removed: &[bool]) -> Vec<Edge> {
+    edges
+        .into_iter()
+        .zip(removed)
+        .filter(|(_, &removed)| !removed)
+        .map(|(edge, _)| edge)
+        .collect()
+}
+
+// Edges meeting at a place with the angle each leaves it at, sorted by angle
+fn sorted_legs(place: usize, incident: &[usize], edges: &[Edge], places: &Places) -> Vec<(usize, f32)> {
+    let (x, y) = places.center(place);
+    let mut legs: Vec<(usize, f32)> = incident
+        .iter()
+        .map(|&index| {
+            let (other_x, other_y) = places.center(edges[index].leaving(place).ends[1]);
+            (index, (other_y - y).atan2(other_x - x).rem_euclid(TAU))
+        })
+        .collect();
+    legs.sort_by(|a, b| a.1.total_cmp(&b.1));
+    legs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nodes given as id, latitude and longitude, and ways as id, node ids
+    // and extra tags, all residential roads; 0.0009 degrees is about 100 m
+    fn osm(nodes: &[(i64, f64, f64)], ways: &[(i64, &[i64], &str)], extra: &str) -> String {
+        let mut text = String::from("<osm>\n");
+        for (id, lat, lon) in nodes {
+            text.push_str(&format!("  <node id=\"{}\" lat=\"{}\" lon=\"{}\"/>\n", id, lat, lon));
+        }
+        for (id, refs, tags) in ways {
+            text.push_str(&format!("  <way id=\"{}\">", id));
+            for node in refs.iter() {
+                text.push_str(&format!("<nd ref=\"{}\"/>", node));
+            }
+            text.push_str(&format!("<tag k=\"highway\" v=\"residential\"/>{}</way>\n", tags));
+        }
+        text.push_str(extra);
+        text.push_str("</osm>\n");
+        text
+    }
+
+    // Crossing at node 1 of ways to the north (10), south (11), west (12)
+    // and east (13), which become legs east 0, south 1, west 2 and north 3
+    fn crossing(east_tags: &str, extra: &str) -> RoadGraph {
+        let nodes = [(1, 0.0, 0.0), (2, 0.0009, 0.0), (3, -0.0009, 0.0), (4, 0.0, -0.0009), (5, 0.0, 0.0009)];
+        let ways: [(i64, &[i64], &str); 4] = [(10, &[2, 1], ""), (11, &[1, 3], ""), (12, &[4, 1], ""), (13, &[1, 5], east_tags)];
+        build(&read(&osm(&nodes, &ways, extra)).unwrap()).unwrap()
+    }
+
+    fn restriction(kind: &str, from: i64, to: i64) -> String {
+        format!(
+            "  <relation id=\"{}{}\"><member type=\"way\" ref=\"{}\" role=\"from\"/><member type=\"node\" ref=\"1\" role=\"via\"/><member type=\"way\" ref=\"{}\" role=\"to\"/><tag k=\"type\" v=\"restriction\"/><tag k=\"restriction\" v=\"{}\"/></relation>\n",
+            from, to, from, to, kind
+        )
+    }
+
+    #[test]
+    fn plain_crossing() {
+        let graph = crossing("", "");
+        assert_eq!(graph.junctions.len(), 1);
+        let site = &graph.junctions[0];
+        assert!(distance(site.center, (0.0, 0.0)) < 0.1);
+        assert_eq!(site.control, Control::Stop(StopControl::new(StopRule::TwoWay)));
+        let degrees: Vec<f32> = site.geometry.legs.iter().map(|leg| leg.angle.to_degrees().round()).collect();
+        assert_eq!(degrees, vec![0.0, 90.0, 180.0, 270.0]);
+        assert!(site.geometry.legs.iter().all(|leg| leg.entry && leg.exit && (leg.length - 100.0f32.min(APPROACH_LENGTH)).abs() < 1.0));
+        assert!(site.geometry.banned.is_empty());
+        assert_eq!(graph.links, vec![vec![None; 4]]);
+    }
+
+    #[test]
+    fn signal_nodes_near_the_junction_give_it_signals() {
+        let nodes = [(1, 0.0, 0.0), (2, 0.0009, 0.0), (3, -0.0009, 0.0), (4, 0.0, -0.0009), (5, 0.0, 0.0009), (6, 0.0, 0.0002)];
+        let ways: [(i64, &[i64], &str); 4] = [(10, &[2, 1], ""), (11, &[1, 3], ""), (12, &[4, 1], ""), (13, &[1, 6, 5], "")];
+        let text = osm(&nodes, &ways, "").replace(
+            "<node id=\"6\" lat=\"0\" lon=\"0.0002\"/>",
+            "<node id=\"6\" lat=\"0\" lon=\"0.0002\"><tag k=\"highway\" v=\"traffic_signals\"/></node>",
+        );
+        let graph = build(&read(&text).unwrap()).unwrap();
+        assert_eq!(graph.junctions[0].control, Control::Signals);
+        assert_eq!(graph.junctions[0].geometry.legs.len(), 4);
+    }
+
+    #[test]
+    fn oneway_legs_only_lead_out() {
+        let legs = crossing("<tag k=\"oneway\" v=\"yes\"/>", "").junctions[0].geometry.legs.clone();
+        assert!(!legs[0].entry && legs[0].exit);
+        assert!(legs[1..].iter().all(|leg| leg.entry && leg.exit));
+    }
+
+    #[test]
+    fn turn_restrictions_ban_movements() {
+        // No left turn from the west into the north leg, and only straight
+        // on from the south
+        let extra = restriction("no_left_turn", 12, 10) + &restriction("only_straight_on", 11, 10);
+        let graph = crossing("", &extra);
+        let mut banned = graph.junctions[0].geometry.banned.clone();
+        banned.sort_by_key(|movement| (movement.from, movement.to));
+        assert_eq!(
+            banned,
+            vec![Movement { from: 1, to: 0 }, Movement { from: 1, to: 2 }, Movement { from: 2, to: 3 }]
+        );
+        assert_eq!(graph.junctions[0].geometry.exits(1), vec![3]);
+    }
+
+    #[test]
+    fn close_junctions_merge_into_one() {
+        // A main road from west to east with side roads to the north and to
+        // the south, joining it `apart` degrees of longitude from each other
+        let tees = |apart: f64| {
+            let nodes = [
+                (1, 0.0, -apart / 2.0),
+                (2, 0.0, apart / 2.0),
+                (3, 0.0, -0.0009),
+                (4, 0.0, 0.0009),
+                (5, 0.0009, -apart / 2.0),
+                (6, -0.0009, apart / 2.0),
+            ];
+            let ways: [(i64, &[i64], &str); 3] = [(10, &[3, 1, 2, 4], ""), (11, &[5, 1], ""), (12, &[2, 6], "")];
+            build(&read(&osm(&nodes, &ways, "")).unwrap()).unwrap()
+        };
+
+        // About 22 m apart, closer than the shortest link
+        let merged = tees(0.0002);
+        assert_eq!(merged.junctions.len(), 1);
+        assert_eq!(merged.junctions[0].geometry.legs.len(), 4);
+        assert!(distance(merged.junctions[0].center, (0.0, 0.0)) < 0.1);
+
+        // About 78 m apart, two tees joined by a link
+        let apart = tees(0.0007);
+        assert_eq!(apart.junctions.len(), 2);
+        assert!(apart.junctions.iter().all(|site| site.geometry.legs.len() == 3));
+        assert_eq!(apart.links.iter().flatten().flatten().count(), 2);
+    }
+}
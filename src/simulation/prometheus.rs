@@ -0,0 +1,118 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::simulation::network::Network;
+
+// Upper bounds of the step duration buckets, in seconds
+const STEP_BUCKETS: [f64; 10] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1];
+
+// Histogram of the wall-clock time simulation steps took
+#[derive(Debug, Clone, Default)]
+pub struct StepTimes {
+    buckets: [u64; STEP_BUCKETS.len()], // Steps no longer than each bound
+    count: u64,
+    sum: f64, // Seconds
+}
+
+impl StepTimes {
+    pub fn record(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, &bound) in self.buckets.iter_mut().zip(&STEP_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+// Counters and gauges of a network in the Prometheus text format
+//
+// Totals cover the whole run, so Prometheus can take rates of them; queues,
+// delay and throughput are the current values. Per-intersection series are
+// labelled with the intersection's index and, for queues, the leg.
+pub fn exposition(network: &Network, steps: &StepTimes) -> String {
+    let mut text = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(text, "# HELP road_intersection_{} {}", name, help);
+        let _ = writeln!(text, "# TYPE road_intersection_{} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(text, "road_intersection_{}{} {}", name, labels, value);
+        }
+    };
+    let single = |value: String| vec![(String::new(), value)];
+    let per_intersection = |value: &dyn Fn(usize) -> u64| {
+        (0..network.intersections.len())
+            .map(|index| (format!("{{intersection=\"{}\"}}", index), value(index).to_string()))
+            .collect()
+    };
+
+    let metrics = &network.metrics;
+    metric("ticks_total", "counter", "Simulation ticks run, 60 per simulated second.", single(network.tick.to_string()));
+    metric("vehicles_spawned_total", "counter", "Vehicles that entered the network.", single(metrics.spawned.to_string()));
+    metric("vehicles_exited_total", "counter", "Vehicles that left the network.", single(metrics.exited.to_string()));
+    metric(
+        "vehicles_in_network",
+        "gauge",
+        "Vehicles currently in the network.",
+        single(network.vehicles().count().to_string()),
+    );
+    metric(
+        "average_delay_seconds",
+        "gauge",
+        "Time vehicles that left the network spent stopped, on average.",
+        single(format!("{:.3}", metrics.average_delay())),
+    );
+    metric(
+        "throughput_vehicles_per_hour",
+        "gauge",
+        "Exits from the network over the last minute, per hour.",
+        single(format!("{:.1}", metrics.throughput(network.tick))),
+    );
+
+    let queues = network
+        .intersections
+        .iter()
+        .enumerate()
+        .flat_map(|(index, intersection)| {
+            (0..intersection.geometry.legs.len()).map(move |leg| {
+                (
+                    format!("{{intersection=\"{}\",leg=\"{}\"}}", index, leg),
+                    intersection.queue_length(leg).to_string(),
+                )
+            })
+        })
+        .collect();
+    metric("queue_length", "gauge", "Vehicles stopped before the stop line of an approach.", queues);
+    metric(
+        "phase_changes_total",
+        "counter",
+        "Times a signalised intersection switched phase.",
+        per_intersection(&|index| network.intersections[index].metrics.phase_changes),
+    );
+    metric(
+        "collisions_total",
+        "counter",
+        "Pairs of vehicles whose bodies came to overlap.",
+        per_intersection(&|index| network.intersections[index].metrics.collisions),
+    );
+    metric(
+        "red_light_runs_total",
+        "counter",
+        "Vehicles that crossed the stop line of a signalised intersection on red.",
+        per_intersection(&|index| network.intersections[index].metrics.red_light_runs),
+    );
+
+    let mut histogram: Vec<(String, String)> = STEP_BUCKETS
+        .iter()
+        .zip(&steps.buckets)
+        .map(|(bound, count)| (format!("_bucket{{le=\"{}\"}}", bound), count.to_string()))
+        .collect();
+    histogram.push(("_bucket{le=\"+Inf\"}".to_string(), steps.count.to_string()));
+    histogram.push(("_sum".to_string(), format!("{:.6}", steps.sum)));
+    histogram.push(("_count".to_string(), steps.count.to_string()));
+    metric("step_duration_seconds", "histogram", "Wall-clock time taken by a simulation tick.", histogram);
+
+    text
+}
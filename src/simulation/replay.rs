@@ -0,0 +1,549 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+
+use crate::constants::APPROACH_LENGTH;
+use crate::models::direction::Direction;
+use crate::models::driver::DriverPopulation;
+use crate::models::junction::JunctionGeometry;
+use crate::models::route::Route;
+use crate::simulation::coordination::SignalPlan;
+use crate::simulation::intersection::Control;
+use crate::simulation::network::{NetworkLayout, RoadGraph};
+use crate::simulation::roundabout::Roundabout;
+use crate::simulation::stop_control::{StopControl, StopRule};
+
+// External input that can change the course of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    // No route means a random one, no entry a random intersection on the edge of the grid
+    Spawn {
+        direction: Direction,
+        route: Option<Route>,
+        entry: Option<usize>,
+    },
+    // Clicked spawn on a leg of an intersection on the edge of the grid
+    SpawnAt {
+        entry: usize,
+        leg: usize,
+        route: Option<Route>,
+    },
+    // Spawn taking a given exit, as demand read from a route file does
+    SpawnTo {
+        entry: usize,
+        leg: usize,
+        exit: usize,
+    },
+    Pause,
+    Resume,
+    Speed(f32),
+    SwitchLights,
+    ForceGreen(usize, usize), // Intersection index and leg
+    HoldPhase(usize),
+    SelectPhase(usize, usize), // Intersection index and phase
+    Remove(u32),
+    Step,
+}
+
+impl InputEvent {
+    // Inputs that alter the simulation itself rather than how it is viewed
+    pub fn changes_simulation(self) -> bool {
+        matches!(
+            self,
+            InputEvent::Spawn { .. }
+                | InputEvent::SpawnAt { .. }
+                | InputEvent::SpawnTo { .. }
+                | InputEvent::SwitchLights
+                | InputEvent::ForceGreen(..)
+                | InputEvent::HoldPhase(_)
+                | InputEvent::SelectPhase(..)
+                | InputEvent::Remove(_)
+        )
+    }
+
+    // Inputs aimed at an intersection end in `at <index>`, left out for the first one
+    pub fn to_line(self) -> String {
+        match self {
+            InputEvent::Spawn { direction, route, entry } => {
+                let mut line = format!("spawn {}", direction_name(direction));
+                match route {
+                    Some(route) => line.push_str(&format!(" {}", route.name())),
+                    None if entry.is_some() => line.push_str(" random"),
+                    None => {}
+                }
+                if let Some(entry) = entry {
+                    line.push_str(&format!(" at {}", entry));
+                }
+                line
+            }
+            InputEvent::SpawnAt { entry, leg, route } => {
+                let mut line = format!("spawn_leg {}", leg);
+                if let Some(route) = route {
+                    line.push_str(&format!(" {}", route.name()));
+                }
+                line.push_str(&at_suffix(entry));
+                line
+            }
+            InputEvent::SpawnTo { entry, leg, exit } => format!("spawn_to {} {}{}", leg, exit, at_suffix(entry)),
+            InputEvent::Pause => "pause".to_string(),
+            InputEvent::Resume => "resume".to_string(),
+            InputEvent::Speed(speed) => format!("speed {}", speed),
+            InputEvent::SwitchLights => "switch_lights".to_string(),
+            InputEvent::ForceGreen(index, leg) => format!("force_green {}{}", leg, at_suffix(index)),
+            InputEvent::HoldPhase(index) => format!("hold_phase{}", at_suffix(index)),
+            InputEvent::SelectPhase(index, phase) => format!("select_phase {}{}", phase, at_suffix(index)),
+            InputEvent::Remove(id) => format!("remove {}", id),
+            InputEvent::Step => "step".to_string(),
+        }
+    }
+
+    pub fn parse(parts: &[&str]) -> Result<Self, String> {
+        // Split off the intersection the input is aimed at, if any
+        let (parts, index) = match parts {
+            [rest @ .., "at", index] => (
+                rest,
+                Some(index.parse().map_err(|_| format!("invalid intersection '{}'", index))?),
+            ),
+            _ => (parts, None),
+        };
+
+        match parts {
+            ["spawn", direction] => Ok(InputEvent::Spawn {
+                direction: parse_direction(direction)?,
+                route: None,
+                entry: index,
+            }),
+            ["spawn", direction, route] => Ok(InputEvent::Spawn {
+                direction: parse_direction(direction)?,
+                route: match *route {
+                    "random" => None,
+                    _ => Some(parse_route(route)?),
+                },
+                entry: index,
+            }),
+            ["spawn_leg", leg] => Ok(InputEvent::SpawnAt {
+                entry: index.unwrap_or(0),
+                leg: parse_leg(leg)?,
+                route: None,
+            }),
+            ["spawn_leg", leg, route] => Ok(InputEvent::SpawnAt {
+                entry: index.unwrap_or(0),
+                leg: parse_leg(leg)?,
+                route: Some(parse_route(route)?),
+            }),
+            ["spawn_to", leg, exit] => Ok(InputEvent::SpawnTo {
+                entry: index.unwrap_or(0),
+                leg: parse_leg(leg)?,
+                exit: parse_leg(exit)?,
+            }),
+            ["pause"] => Ok(InputEvent::Pause),
+            ["resume"] => Ok(InputEvent::Resume),
+            ["speed", speed] => speed
+                .parse()
+                .map(InputEvent::Speed)
+                .map_err(|_| format!("invalid speed '{}'", speed)),
+            ["switch_lights"] => Ok(InputEvent::SwitchLights),
+            // Files from before junctions had numbered legs name the
+            // direction of travel instead, on a crossing
+            ["force_green", leg] => Ok(InputEvent::ForceGreen(
+                index.unwrap_or(0),
+                match parse_direction(leg) {
+                    Ok(direction) => JunctionGeometry::cross(APPROACH_LENGTH).leg_from(direction),
+                    Err(_) => parse_leg(leg)?,
+                },
+            )),
+            ["hold_phase"] => Ok(InputEvent::HoldPhase(index.unwrap_or(0))),
+            ["select_phase", phase] => Ok(InputEvent::SelectPhase(
+                index.unwrap_or(0),
+                phase.parse().map_err(|_| format!("invalid phase '{}'", phase))?,
+            )),
+            ["remove", id] => id
+                .parse()
+                .map(InputEvent::Remove)
+                .map_err(|_| format!("invalid vehicle id '{}'", id)),
+            ["step"] => Ok(InputEvent::Step),
+            _ => Err(format!("unknown input '{}'", parts.join(" "))),
+        }
+    }
+}
+
+fn at_suffix(index: usize) -> String {
+    if index == 0 {
+        String::new()
+    } else {
+        format!(" at {}", index)
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::North => "north",
+        Direction::South => "south",
+        Direction::East => "east",
+        Direction::West => "west",
+    }
+}
+
+fn parse_direction(name: &str) -> Result<Direction, String> {
+    match name {
+        "north" => Ok(Direction::North),
+        "south" => Ok(Direction::South),
+        "east" => Ok(Direction::East),
+        "west" => Ok(Direction::West),
+        _ => Err(format!("unknown direction '{}'", name)),
+    }
+}
+
+fn parse_leg(text: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("invalid leg '{}'", text))
+}
+
+fn parse_route(name: &str) -> Result<Route, String> {
+    match name {
+        "left" => Ok(Route::Left),
+        "straight" => Ok(Route::Straight),
+        "right" => Ok(Route::Right),
+        _ => Err(format!("unknown route '{}'", name)),
+    }
+}
+
+// Grid size as written on the command line and in replay files, e.g. `3x2`
+// for three rows of two intersections
+pub fn parse_grid(text: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid grid '{}', expected <rows>x<columns>", text);
+    let (rows, columns) = text.split_once('x').ok_or_else(invalid)?;
+    let rows: usize = rows.parse().map_err(|_| invalid())?;
+    let columns: usize = columns.parse().map_err(|_| invalid())?;
+    if rows == 0 || columns == 0 {
+        return Err(invalid());
+    }
+    Ok((rows, columns))
+}
+
+// Records every input together with the tick it was applied on
+//
+// File format: header lines, then one `<tick> <input>` line per event, in
+// the order the events were applied. The headers are:
+//
+//   seed <n>
+//   grid <rows>x<columns> <link length>
+//   signals <cycle ticks> <offset>,<offset>,...
+//   map <path>
+//   legs <degrees>,<degrees>,...
+//   drivers <setting>=<value>,...
+//   junction roundabout <critical gap ticks> <follow-up headway ticks>
+//   junction <all-way-stop|two-way-stop> <full stop ticks> <critical gap ticks>
+//
+// Every file has the first three. A `map` line is written for networks
+// imported from OpenStreetMap or SUMO, `legs` for junctions other than a
+// crossing, `drivers` for drivers other than ideal ones and `junction` for
+// roundabouts and stop signs.
+pub struct Recorder {
+    pub seed: u64,
+    pub layout: NetworkLayout,
+    pub plan: SignalPlan,
+    pub events: Vec<(u64, InputEvent)>,
+}
+
+impl Recorder {
+    pub fn new(seed: u64, layout: NetworkLayout, plan: SignalPlan) -> Self {
+        Recorder {
+            seed,
+            layout,
+            plan,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, event: InputEvent) {
+        self.events.push((tick, event));
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let offsets: Vec<String> = self.plan.offsets.iter().map(|offset| offset.to_string()).collect();
+        let mut contents = format!(
+            "seed {}\ngrid {}x{} {}\nsignals {} {}\n",
+            self.seed,
+            self.layout.rows,
+            self.layout.columns,
+            self.layout.link_length,
+            self.plan.cycle_ticks,
+            offsets.join(",")
+        );
+        if let Some(map) = &self.layout.map {
+            contents.push_str(&format!("map {}\n", map));
+        }
+        if let Some(legs) = &self.layout.legs {
+            let legs: Vec<String> = legs.iter().map(|angle| angle.to_string()).collect();
+            contents.push_str(&format!("legs {}\n", legs.join(",")));
+        }
+        if self.layout.drivers != DriverPopulation::default() {
+            contents.push_str(&format!("drivers {}\n", self.layout.drivers.to_spec()));
+        }
+        match self.layout.control {
+            Control::Signals => {}
+            Control::Roundabout(roundabout) => contents.push_str(&format!(
+                "junction roundabout {} {}\n",
+                roundabout.critical_gap, roundabout.follow_up_headway
+            )),
+            Control::Stop(stop) => contents.push_str(&format!(
+                "junction {} {} {}\n",
+                self.layout.control.name(),
+                stop.full_stop,
+                stop.critical_gap
+            )),
+        }
+        for (tick, event) in &self.events {
+            contents.push_str(&format!("{} {}\n", tick, event.to_line()));
+        }
+        fs::write(path, contents)
+    }
+}
+
+// Re-feeds a recorded run, handing out events once their tick is reached
+//
+// Files without a `grid` line were recorded on a single intersection, files
+// without a `signals` line with uncoordinated signals, files without a
+// `junction` line with signalised junctions, files without a `legs` line
+// on crossings, files without a `map` line on generated layouts and files
+// without a `drivers` line with ideal drivers.
+pub struct Replay {
+    pub seed: u64,
+    pub layout: NetworkLayout,
+    pub plan: Option<SignalPlan>,
+    events: VecDeque<(u64, InputEvent)>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let seed = match lines.next() {
+            Some((_, line)) => match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["seed", seed] => seed
+                    .parse()
+                    .map_err(|_| format!("{}:1: invalid seed '{}'", path, seed))?,
+                _ => return Err(format!("{}:1: expected 'seed <n>' header", path)),
+            },
+            None => return Err(format!("{}: empty replay file", path)),
+        };
+
+        let mut layout = NetworkLayout::default();
+        let mut plan = None;
+        let mut events = VecDeque::new();
+        for (index, line) in lines {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if let ["grid", grid, link_length] = parts[..] {
+                let (rows, columns) = parse_grid(grid).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+                let link_length = link_length
+                    .parse()
+                    .map_err(|_| format!("{}:{}: invalid link length '{}'", path, index + 1, link_length))?;
+                layout.rows = rows;
+                layout.columns = columns;
+                layout.link_length = link_length;
+                continue;
+            }
+            if let Some(map) = line.strip_prefix("map ") {
+                layout.map = Some(map.trim().to_string());
+                continue;
+            }
+            if let ["drivers", spec] = parts[..] {
+                layout.drivers = DriverPopulation::parse(spec).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+                continue;
+            }
+            if let ["legs", legs] = parts[..] {
+                let geometry =
+                    JunctionGeometry::parse(legs, APPROACH_LENGTH).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+                layout.legs = Some(geometry.degrees());
+                continue;
+            }
+            if let ["junction", "roundabout", critical_gap, follow_up_headway] = parts[..] {
+                let invalid = || format!("{}:{}: invalid roundabout '{}'", path, index + 1, line);
+                layout.control = Control::Roundabout(Roundabout {
+                    critical_gap: critical_gap.parse().map_err(|_| invalid())?,
+                    follow_up_headway: follow_up_headway.parse().map_err(|_| invalid())?,
+                    ..Roundabout::default()
+                });
+                continue;
+            }
+            if let ["junction", rule @ ("all-way-stop" | "two-way-stop"), full_stop, critical_gap] = parts[..] {
+                let invalid = || format!("{}:{}: invalid stop control '{}'", path, index + 1, line);
+                let rule = if rule == "all-way-stop" { StopRule::AllWay } else { StopRule::TwoWay };
+                layout.control = Control::Stop(StopControl {
+                    full_stop: full_stop.parse().map_err(|_| invalid())?,
+                    critical_gap: critical_gap.parse().map_err(|_| invalid())?,
+                    ..StopControl::new(rule)
+                });
+                continue;
+            }
+            if let ["signals", cycle_ticks, offsets] = parts[..] {
+                let invalid = || format!("{}:{}: invalid signal plan '{}'", path, index + 1, line);
+                let cycle_ticks: u64 = cycle_ticks.parse().map_err(|_| invalid())?;
+                let offsets = offsets
+                    .split(',')
+                    .map(|offset| offset.parse())
+                    .collect::<Result<Vec<u64>, _>>()
+                    .map_err(|_| invalid())?;
+                if cycle_ticks < 2 {
+                    return Err(invalid());
+                }
+                plan = Some((index, SignalPlan { cycle_ticks, offsets }));
+                continue;
+            }
+
+            let tick = parts[0]
+                .parse()
+                .map_err(|_| format!("{}:{}: invalid tick '{}'", path, index + 1, parts[0]))?;
+            let event =
+                InputEvent::parse(&parts[1..]).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+            events.push_back((tick, event));
+        }
+
+        // One offset per intersection, counted once the whole layout is read
+        if let Some((index, plan)) = &plan {
+            let count = RoadGraph::of(&layout)?.junctions.len();
+            if plan.offsets.len() != count {
+                return Err(format!(
+                    "{}:{}: expected {} offsets, one per intersection, got {}",
+                    path,
+                    index + 1,
+                    count,
+                    plan.offsets.len()
+                ));
+            }
+        }
+        let plan = plan.map(|(_, plan)| plan);
+
+        Ok(Replay {
+            seed,
+            layout,
+            plan,
+            events,
+        })
+    }
+
+    // Next event due at or before the given tick, if any
+    pub fn next_due(&mut self, tick: u64) -> Option<InputEvent> {
+        match self.events.front() {
+            Some(&(event_tick, _)) if event_tick <= tick => self.events.pop_front().map(|(_, e)| e),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::network::Network;
+
+    // What can be seen of every vehicle, in order
+    fn snapshot(network: &Network) -> Vec<String> {
+        network
+            .vehicles()
+            .map(|vehicle| {
+                format!(
+                    "{} {:?} {} {:?} {}",
+                    vehicle.id, vehicle.position, vehicle.heading, vehicle.stop_reason, vehicle.waiting_ticks
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_input_reads_back_from_its_line() {
+        let events = [
+            InputEvent::Spawn { direction: Direction::North, route: None, entry: None },
+            InputEvent::Spawn { direction: Direction::East, route: Some(Route::Left), entry: None },
+            InputEvent::Spawn { direction: Direction::South, route: None, entry: Some(3) },
+            InputEvent::Spawn { direction: Direction::West, route: Some(Route::Right), entry: Some(0) },
+            InputEvent::SpawnAt { entry: 0, leg: 2, route: None },
+            InputEvent::SpawnAt { entry: 4, leg: 1, route: Some(Route::Straight) },
+            InputEvent::SpawnTo { entry: 0, leg: 3, exit: 0 },
+            InputEvent::SpawnTo { entry: 2, leg: 1, exit: 2 },
+            InputEvent::Pause,
+            InputEvent::Resume,
+            InputEvent::Speed(2.5),
+            InputEvent::SwitchLights,
+            InputEvent::ForceGreen(0, 1),
+            InputEvent::ForceGreen(5, 3),
+            InputEvent::HoldPhase(0),
+            InputEvent::HoldPhase(1),
+            InputEvent::SelectPhase(0, 1),
+            InputEvent::SelectPhase(2, 0),
+            InputEvent::Remove(17),
+            InputEvent::Step,
+        ];
+        for event in events {
+            let line = event.to_line();
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(InputEvent::parse(&parts), Ok(event), "{}", line);
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_the_run_frame_for_frame() {
+        let layout = NetworkLayout {
+            rows: 2,
+            columns: 3,
+            drivers: DriverPopulation::typical(),
+            ..NetworkLayout::default()
+        };
+        let seed = 42;
+        let mut network = Network::from_graph(seed, layout.clone(), RoadGraph::of(&layout).unwrap());
+        let plan = SignalPlan::uncoordinated(network.intersections.len());
+        network.apply_signal_plan(&plan);
+
+        // Random spawns from every side and a forced green now and then
+        let mut recorder = Recorder::new(seed, layout, plan);
+        let directions = [Direction::North, Direction::East, Direction::South, Direction::West];
+        let mut frames = Vec::new();
+        for tick in 0..3600 {
+            let mut inputs = Vec::new();
+            if tick % 17 == 0 {
+                inputs.push(InputEvent::Spawn {
+                    direction: directions[tick / 17 % 4],
+                    route: None,
+                    entry: None,
+                });
+            }
+            if tick % 500 == 250 {
+                inputs.push(InputEvent::ForceGreen(tick / 500 % 6, 1));
+            }
+            for input in inputs {
+                recorder.record(network.tick, input);
+                network.apply_input(input);
+            }
+            network.update();
+            frames.push(snapshot(&network));
+        }
+        assert!(network.metrics.exited > 0);
+
+        let path = std::env::temp_dir().join(format!("road_intersection_{}.replay", std::process::id()));
+        let path = path.to_str().unwrap();
+        recorder.save(path).unwrap();
+        let loaded = Replay::load(path);
+        fs::remove_file(path).unwrap();
+        let mut replay = loaded.unwrap();
+        assert_eq!(replay.layout, recorder.layout);
+
+        let mut again = Network::from_graph(replay.seed, replay.layout.clone(), RoadGraph::of(&replay.layout).unwrap());
+        again.apply_signal_plan(replay.plan.as_ref().unwrap());
+        for (tick, frame) in frames.iter().enumerate() {
+            while let Some(input) = replay.next_due(again.tick) {
+                again.apply_input(input);
+            }
+            again.update();
+            assert_eq!(&snapshot(&again), frame, "tick {}", tick);
+        }
+        assert!(replay.is_finished());
+        assert_eq!(again.metrics.exited, network.metrics.exited);
+    }
+}
@@ -0,0 +1,303 @@
+use std::f32::consts::TAU;
+
+use crate::constants::{
+    LANE_WIDTH, ROAD_WIDTH, ROUNDABOUT_CRITICAL_GAP, ROUNDABOUT_FOLLOW_UP_HEADWAY, ROUNDABOUT_RADIUS, TICK_DURATION,
+    VEHICLE_LENGTH, VEHICLE_SAFE_DISTANCE,
+};
+use crate::models::geometry::Polyline;
+use crate::models::junction::{JunctionGeometry, Movement, MovementPath};
+use crate::models::vehicle::{StopReason, Vehicle};
+use crate::simulation::intersection::ms_to_ticks;
+use crate::ui::camera::Camera;
+use crate::ui::shapes::fill_disc;
+use crate::ui::surface::{Color, Surface};
+
+// Angle between the points a path around the ring is drawn through
+const RING_STEP: f32 = TAU / 72.0;
+
+// Single-lane roundabout in place of the signalised crossing
+//
+// Traffic circulates counter-clockwise on screen, so a right turn takes the
+// first exit. Entering vehicles give way at the yield line: they enter only
+// if no circulating vehicle will reach their entry point within the critical
+// gap, and no sooner than the follow-up headway after the previous vehicle
+// entered from the same approach.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Roundabout {
+    pub radius: f32,            // Of the middle of the circulating lane, in metres
+    pub critical_gap: u64,      // Ticks
+    pub follow_up_headway: u64, // Ticks
+}
+
+impl Default for Roundabout {
+    fn default() -> Self {
+        Roundabout {
+            radius: ROUNDABOUT_RADIUS,
+            critical_gap: ms_to_ticks(ROUNDABOUT_CRITICAL_GAP),
+            follow_up_headway: ms_to_ticks(ROUNDABOUT_FOLLOW_UP_HEADWAY),
+        }
+    }
+}
+
+// Angle of a point relative to the center, from east growing clockwise on screen
+fn angle_of(point: (f32, f32)) -> f32 {
+    point.1.atan2(point.0).rem_euclid(TAU)
+}
+
+// Angle still to travel from one point on the ring to another, going round
+// the way traffic circulates
+fn angle_between(from: f32, to: f32) -> f32 {
+    (from - to).rem_euclid(TAU)
+}
+
+impl Roundabout {
+    // Paths of every movement: in along the entry lane to the middle of the
+    // circulating lane, round it and out along the exit lane
+    //
+    // Entry and exit lanes of a leg meet the ring a lane width apart, so
+    // traffic leaving along a leg passes clear of traffic waiting to enter it.
+    pub fn paths(&self, geometry: &JunctionGeometry) -> Vec<MovementPath> {
+        geometry.movements().map(|movement| self.path(geometry, movement)).collect()
+    }
+
+    fn path(&self, geometry: &JunctionGeometry, movement: Movement) -> MovementPath {
+        let (entry, exit) = (&geometry.legs[movement.from], &geometry.legs[movement.to]);
+        let side = LANE_WIDTH / 2.0;
+        let meet = (self.radius * self.radius - side * side).sqrt();
+        let start = angle_of(entry.lane_point(meet, true));
+        let sweep = angle_between(start, angle_of(exit.lane_point(meet, false)));
+        let steps = (sweep / RING_STEP).ceil().max(1.0) as usize;
+
+        let mut points = vec![entry.lane_point(entry.length, true)];
+        points.extend((0..=steps).map(|step| {
+            let angle = start - sweep * step as f32 / steps as f32;
+            (self.radius * angle.cos(), self.radius * angle.sin())
+        }));
+        points.push(exit.lane_point(exit.length + VEHICLE_LENGTH, false));
+        let path = Polyline::new(points);
+
+        let ring_start = entry.length - meet;
+        let ring_end = path.length() - VEHICLE_LENGTH - (exit.length - meet);
+        MovementPath {
+            movement,
+            turn: geometry.turn(movement),
+            stop_at: entry.length - self.yield_distance(),
+            clear_at: ring_end + VEHICLE_LENGTH / 2.0,
+            ring: Some((ring_start, ring_end)),
+            conflicts: Vec::new(),
+            path,
+        }
+    }
+
+    // Move every vehicle one tick
+    //
+    // `snapshot` is the state at the start of the tick, `last_entry` the tick
+    // each leg last let a vehicle past its yield line and `exits_blocked`
+    // whether each vehicle has to wait at the end of its exit.
+    pub fn update(
+        &self,
+        tick: u64,
+        vehicles: &mut [Vehicle],
+        snapshot: &[Vehicle],
+        last_entry: &mut [u64],
+        exits_blocked: &[bool],
+    ) {
+        for (vehicle, &exit_blocked) in vehicles.iter_mut().zip(exits_blocked) {
+            vehicle.stop_reason = if self.is_circulating(vehicle) {
+                self.ring_leader(vehicle, snapshot)
+                    .or_else(|| self.exit_obstruction(vehicle, snapshot))
+                    .map(StopReason::Leader)
+            } else if let Some(leader) = vehicle.should_stop_for_vehicle(snapshot) {
+                Some(StopReason::Leader(leader))
+            } else if self.must_yield(tick, vehicle, snapshot, last_entry) {
+                Some(StopReason::Yield)
+            } else if exit_blocked {
+                Some(StopReason::Spillback)
+            } else {
+                None
+            };
+
+            let entering = !vehicle.has_entered();
+            vehicle.act(snapshot);
+            if entering && vehicle.has_entered() {
+                last_entry[vehicle.path.movement.from] = tick;
+            }
+        }
+    }
+
+    // Distance from the center at which entering vehicles wait, clear of the
+    // circulating lane
+    pub fn yield_distance(&self) -> f32 {
+        self.radius + ROAD_WIDTH / 2.0 + VEHICLE_LENGTH / 2.0
+    }
+
+    // On the stretch of the path around the ring
+    fn is_circulating(&self, vehicle: &Vehicle) -> bool {
+        vehicle
+            .path
+            .ring
+            .is_some_and(|(start, end)| vehicle.travelled >= start && vehicle.travelled < end)
+    }
+
+    // Gap acceptance at the yield line
+    //
+    // Vehicles already past their own yield line but not yet on the ring
+    // count as circulating from their entry point.
+    fn must_yield(&self, tick: u64, vehicle: &Vehicle, vehicles: &[Vehicle], last_entry: &[u64]) -> bool {
+        if !vehicle.is_in_stop_zone() {
+            return false;
+        }
+        if tick < last_entry[vehicle.path.movement.from] + self.follow_up_headway {
+            return true;
+        }
+        let Some((ring_start, _)) = vehicle.path.ring else {
+            return false;
+        };
+
+        let entry = angle_of(vehicle.path.path.at(ring_start).0);
+        let critical_time = self.critical_gap as f32 * TICK_DURATION;
+        vehicles.iter().any(|other| {
+            let Some((start, end)) = other.path.ring else {
+                return false;
+            };
+            if other.id == vehicle.id || other.junction != vehicle.junction || !other.has_entered() || other.travelled >= end {
+                return false;
+            }
+            let (angle, remaining) = if other.travelled < start {
+                (angle_of(other.path.path.at(start).0), end - start)
+            } else {
+                (angle_of(other.offset()), end - other.travelled)
+            };
+
+            let upstream = angle_between(angle, entry) * self.radius;
+            let downstream = TAU * self.radius - upstream;
+            // Vehicles leaving before the entry are no threat, vehicles just
+            // past it are still in the way
+            let leaves_first = remaining < upstream;
+            // Gaps are judged by how long the circulating driver takes to
+            // cover them at their own speed
+            let critical_distance = critical_time * other.driver.desired_speed;
+            (!leaves_first && upstream < critical_distance) || downstream < VEHICLE_LENGTH + VEHICLE_SAFE_DISTANCE
+        })
+    }
+
+    // Nearest circulating vehicle less than a safe distance ahead on the ring
+    fn ring_leader(&self, vehicle: &Vehicle, vehicles: &[Vehicle]) -> Option<u32> {
+        let angle = angle_of(vehicle.offset());
+        vehicles
+            .iter()
+            .filter(|other| {
+                other.id != vehicle.id && other.junction == vehicle.junction && self.is_circulating(other)
+            })
+            .filter_map(|other| {
+                let ahead = angle_between(angle, angle_of(other.offset())) * self.radius;
+                (ahead > 0.0 && ahead < VEHICLE_SAFE_DISTANCE).then_some((ahead, other.id))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, id)| id)
+    }
+
+    // Vehicle in the exit lane less than a safe distance beyond the exit a
+    // circulating vehicle is about to take
+    fn exit_obstruction(&self, vehicle: &Vehicle, vehicles: &[Vehicle]) -> Option<u32> {
+        let (_, end) = vehicle.path.ring?;
+        let to_exit = end - vehicle.travelled;
+        vehicles
+            .iter()
+            .find(|other| {
+                let Some((_, other_end)) = other.path.ring else {
+                    return false;
+                };
+                let past_exit = other.travelled - other_end;
+                other.id != vehicle.id
+                    && other.junction == vehicle.junction
+                    && other.path.movement.to == vehicle.path.movement.to
+                    && past_exit >= 0.0
+                    && to_exit + past_exit < VEHICLE_SAFE_DISTANCE
+            })
+            .map(|other| other.id)
+    }
+
+    // Circulating lane and central island
+    pub fn render(&self, canvas: &mut dyn Surface, camera: &Camera, center: (f32, f32)) {
+        fill_disc(canvas, camera, center, self.radius + ROAD_WIDTH / 2.0, Color::RGB(100, 100, 100));
+        fill_disc(canvas, camera, center, self.radius - ROAD_WIDTH / 2.0, Color::RGB(0, 128, 0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{TICKS_PER_SECOND, VEHICLE_SPEED};
+    use crate::models::driver::DriverProfile;
+    use crate::simulation::intersection::Control;
+    use crate::simulation::network::{Network, NetworkLayout};
+
+    const FROM_EAST: Movement = Movement { from: 0, to: 2 };
+    const FROM_SOUTH: Movement = Movement { from: 1, to: 3 };
+
+    fn roundabout() -> (Network, Roundabout) {
+        let roundabout = Roundabout::default();
+        let layout = NetworkLayout {
+            control: Control::Roundabout(roundabout),
+            ..NetworkLayout::default()
+        };
+        (Network::new(1, layout), roundabout)
+    }
+
+    fn vehicle(network: &Network, id: u32, movement: Movement, travelled: f32, desired_speed: f32) -> Vehicle {
+        let intersection = &network.intersections[0];
+        let driver = DriverProfile {
+            desired_speed,
+            ..DriverProfile::default()
+        };
+        let mut vehicle = Vehicle::new(id, intersection.center, intersection.path(movement), driver);
+        vehicle.travelled = travelled;
+        vehicle
+    }
+
+    // A vehicle at the east yield line, and one that has just come in from
+    // the south and reaches the east entry next, a given number of seconds
+    // away at its speed
+    fn entering_against(seconds: f32) -> (Network, Roundabout, Vec<Vehicle>) {
+        let (network, roundabout) = roundabout();
+        let intersection = &network.intersections[0];
+        let (east, south) = (intersection.path(FROM_EAST), intersection.path(FROM_SOUTH));
+        let ring_point = |path: &MovementPath| angle_of(path.path.at(path.ring.unwrap().0).0);
+        let upstream = angle_between(ring_point(&south), ring_point(&east)) * roundabout.radius;
+
+        let waiting = vehicle(&network, 1, FROM_EAST, east.stop_at, VEHICLE_SPEED);
+        let circulating = vehicle(&network, 2, FROM_SOUTH, south.ring.unwrap().0 - 0.1, upstream / seconds);
+        (network, roundabout, vec![waiting, circulating])
+    }
+
+    #[test]
+    fn entering_vehicles_take_gaps_of_at_least_the_critical_gap() {
+        let critical = roundabout().1.critical_gap as f32 / TICKS_PER_SECOND as f32;
+        let last_entry = [0; 4];
+
+        let (_, roundabout, vehicles) = entering_against(critical - 1.0);
+        assert!(roundabout.must_yield(600, &vehicles[0], &vehicles, &last_entry));
+
+        let (_, roundabout, vehicles) = entering_against(critical + 1.0);
+        assert!(!roundabout.must_yield(600, &vehicles[0], &vehicles, &last_entry));
+    }
+
+    #[test]
+    fn entries_from_a_leg_are_a_follow_up_headway_apart() {
+        let (network, roundabout) = roundabout();
+        let stop_at = network.intersections[0].path(FROM_EAST).stop_at;
+        let vehicles = [vehicle(&network, 1, FROM_EAST, stop_at, VEHICLE_SPEED)];
+        let tick = 600;
+
+        let mut last_entry = [0; 4];
+        last_entry[0] = tick - roundabout.follow_up_headway + 1;
+        assert!(roundabout.must_yield(tick, &vehicles[0], &vehicles, &last_entry));
+        last_entry[0] = tick - roundabout.follow_up_headway;
+        assert!(!roundabout.must_yield(tick, &vehicles[0], &vehicles, &last_entry));
+
+        // Entries from other legs do not hold it back
+        let last_entry = [0, tick, tick, tick];
+        assert!(!roundabout.must_yield(tick, &vehicles[0], &vehicles, &last_entry));
+    }
+}
@@ -0,0 +1,586 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+
+use crate::constants::{TICKS_PER_SECOND, TICK_DURATION, VEHICLE_LENGTH, VEHICLE_WIDTH};
+use crate::models::geometry::{distance, polygons_overlap};
+use crate::models::junction::{angle_difference, Movement, MovementPath};
+use crate::models::vehicle::Vehicle;
+use crate::simulation::intersection::Intersection;
+use crate::simulation::network::Network;
+
+// Spacing of the points compared when looking for where two paths meet, in metres
+const ZONE_STEP: f32 = 0.25;
+
+// Interval at which vehicles are projected ahead for time to collision, in seconds
+const TTC_STEP: f32 = 0.05;
+
+// Ticks a conflict may go unseen and still count as the same one, so that
+// a TTC hovering around the threshold is not split into many
+const ENCOUNTER_GAP_TICKS: u64 = 6;
+
+// Conflict angles, in degrees, below which conflicts count as rear-end and
+// above which as crossing, as SSAM draws the line
+const REAR_END_ANGLE: f32 = 30.0;
+const CROSSING_ANGLE: f32 = 85.0;
+
+// Type of a conflict, from the angle at which the two vehicles would meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    RearEnd,
+    Crossing,
+    Merging,
+}
+
+impl ConflictKind {
+    fn of_angle(degrees: f32) -> Self {
+        if degrees < REAR_END_ANGLE {
+            ConflictKind::RearEnd
+        } else if degrees > CROSSING_ANGLE {
+            ConflictKind::Crossing
+        } else {
+            ConflictKind::Merging
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConflictKind::RearEnd => "rear-end",
+            ConflictKind::Crossing => "crossing",
+            ConflictKind::Merging => "merging",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Minor,
+    Serious,
+    Critical,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Minor => "minor",
+            Severity::Serious => "serious",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+// Limits in seconds below which an interaction is a conflict, and below
+// which it is serious or critical
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetyThresholds {
+    pub ttc: f32,
+    pub pet: f32,
+    pub serious_ttc: f32,
+    pub serious_pet: f32,
+    pub critical_ttc: f32,
+    pub critical_pet: f32,
+}
+
+impl Default for SafetyThresholds {
+    // SSAM's defaults for what counts as a conflict
+    fn default() -> Self {
+        SafetyThresholds {
+            ttc: 1.5,
+            pet: 5.0,
+            serious_ttc: 1.0,
+            serious_pet: 2.0,
+            critical_ttc: 0.5,
+            critical_pet: 1.0,
+        }
+    }
+}
+
+impl SafetyThresholds {
+    fn severity(&self, ttc: Option<f32>, pet: Option<f32>) -> Severity {
+        let below = |ttc_limit: f32, pet_limit: f32| ttc.is_some_and(|t| t <= ttc_limit) || pet.is_some_and(|p| p <= pet_limit);
+        if below(self.critical_ttc, self.critical_pet) {
+            Severity::Critical
+        } else if below(self.serious_ttc, self.serious_pet) {
+            Severity::Serious
+        } else {
+            Severity::Minor
+        }
+    }
+}
+
+// Two vehicles that came closer to colliding than the thresholds allow
+//
+// `first` is the vehicle ahead, or the one through the conflict point first;
+// `second` the one that would have run into it. Vehicles on course to
+// collide are measured by time to collision (TTC), the lowest seen while
+// the conflict lasted. Vehicles on movements that cross or merge are also
+// measured by post-encroachment time (PET), the gap between `first` leaving
+// the point where their paths meet and `second` reaching it; those events
+// last a single tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictEvent {
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub intersection: usize,
+    pub first: u32,
+    pub second: u32,
+    pub kind: ConflictKind,
+    pub ttc: Option<f32>,   // Seconds
+    pub pet: Option<f32>,   // Seconds
+    pub angle: f32,         // Degrees between the headings where they would meet
+    pub point: (f32, f32),  // Where they would meet, in world coordinates
+    pub severity: Severity,
+}
+
+// Place where the paths of two movements cross or merge
+struct Zone {
+    movements: [Movement; 2],
+    along: [f32; 2], // Distance of the point along each path
+    point: (f32, f32),
+    angle: f32,
+    kind: ConflictKind,
+    inside: Vec<(u32, usize)>,            // Vehicles over the point, with the side they came from
+    last_exit: Option<(u32, usize, u64)>, // Last vehicle to leave the point, its side and the tick
+}
+
+impl Zone {
+    // Where two paths come closest, the first such point along `a` for
+    // paths that merge and then run together
+    fn between(center: (f32, f32), a: &MovementPath, b: &MovementPath) -> Self {
+        let samples = |path: &MovementPath| -> Vec<(f32, (f32, f32))> {
+            let (from, to) = (path.stop_at, path.clear_at.max(path.stop_at));
+            let count = ((to - from) / ZONE_STEP).ceil().max(1.0) as usize;
+            (0..=count)
+                .map(|index| {
+                    let along = from + (to - from) * index as f32 / count as f32;
+                    (along, path.path.at(along).0)
+                })
+                .collect()
+        };
+        let (first, second) = (samples(a), samples(b));
+        let nearest: Vec<(f32, f32, f32)> = first
+            .iter()
+            .map(|&(along, point)| {
+                let (other, gap) = second
+                    .iter()
+                    .map(|&(other, q)| (other, distance(point, q)))
+                    .min_by(|x, y| x.1.total_cmp(&y.1))
+                    .expect("Paths have points");
+                (along, other, gap)
+            })
+            .collect();
+        let closest = nearest.iter().map(|n| n.2).fold(f32::INFINITY, f32::min);
+        let &(along_a, along_b, _) = nearest
+            .iter()
+            .find(|n| n.2 <= closest + ZONE_STEP)
+            .expect("Paths have points");
+
+        let ((x, y), heading_a) = a.path.at(along_a);
+        let (_, heading_b) = b.path.at(along_b);
+        Zone {
+            movements: [a.movement, b.movement],
+            along: [along_a, along_b],
+            point: (center.0 + x, center.1 + y),
+            angle: angle_difference(heading_a, heading_b).to_degrees(),
+            kind: if a.movement.to == b.movement.to {
+                ConflictKind::Merging
+            } else {
+                ConflictKind::Crossing
+            },
+            inside: Vec::new(),
+            last_exit: None,
+        }
+    }
+
+    // Side of the zone a vehicle drives through, if any, and whether its
+    // body is over the point
+    fn covers(&self, vehicle: &Vehicle) -> Option<(usize, bool)> {
+        let side = self.movements.iter().position(|&movement| movement == vehicle.path.movement)?;
+        let reach = (VEHICLE_LENGTH + VEHICLE_WIDTH) / 2.0;
+        Some((side, (vehicle.travelled - self.along[side]).abs() < reach))
+    }
+}
+
+// Pair of vehicles currently below the TTC threshold
+struct Encounter {
+    intersection: usize,
+    pair: (u32, u32),
+    start_tick: u64,
+    seen_tick: u64,
+    lowest: ConflictEvent, // At the lowest TTC so far
+}
+
+// Surrogate safety measures in the style of FHWA's SSAM, taken while the
+// simulation runs
+//
+// Every tick, each vehicle and the one it follows in the same intersection
+// are projected ahead along their paths at their current speeds; if their
+// bodies would overlap within the TTC threshold, they are in a conflict that
+// lasts until they no longer would. At every point where two movements cross
+// or merge, the time between one vehicle clearing it and a vehicle from the
+// other movement reaching it is the PET. Conflicts are typed by the angle
+// between the headings where the vehicles meet, under 30 degrees rear-end,
+// over 85 crossing, otherwise merging, and graded by the thresholds.
+pub struct SafetyMonitor {
+    pub thresholds: SafetyThresholds,
+    pub events: Vec<ConflictEvent>,
+    zones: Vec<Vec<Zone>>, // Per intersection
+    encounters: Vec<Encounter>,
+}
+
+impl SafetyMonitor {
+    pub fn new(network: &Network, thresholds: SafetyThresholds) -> Self {
+        let zones = network
+            .intersections
+            .iter()
+            .map(|intersection| {
+                let paths = &intersection.paths;
+                let mut zones = Vec::new();
+                for (index, a) in paths.iter().enumerate() {
+                    for b in &paths[index + 1..] {
+                        if a.conflicts.contains(&b.movement) {
+                            zones.push(Zone::between(intersection.center, a, b));
+                        }
+                    }
+                }
+                zones
+            })
+            .collect();
+        SafetyMonitor {
+            thresholds,
+            events: Vec::new(),
+            zones,
+            encounters: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, network: &Network) {
+        let tick = network.tick;
+        for (index, intersection) in network.intersections.iter().enumerate() {
+            self.measure_ttc(index, intersection, tick);
+            self.measure_pet(index, intersection, tick);
+        }
+
+        // Conflicts that have not been seen again for a while are over
+        let (over, ongoing) = std::mem::take(&mut self.encounters)
+            .into_iter()
+            .partition(|encounter| encounter.seen_tick + ENCOUNTER_GAP_TICKS < tick);
+        self.encounters = ongoing;
+        self.close(over);
+    }
+
+    // Close the conflicts still going on, at the end of a run
+    pub fn finish(&mut self) {
+        let open = std::mem::take(&mut self.encounters);
+        self.close(open);
+        self.events.sort_by_key(|event| event.start_tick);
+    }
+
+    fn close(&mut self, encounters: Vec<Encounter>) {
+        for encounter in encounters {
+            self.events.push(ConflictEvent {
+                start_tick: encounter.start_tick,
+                end_tick: encounter.seen_tick,
+                ..encounter.lowest
+            });
+        }
+    }
+
+    // Each vehicle against the one it follows
+    //
+    // Drivers stop at once when they come within their following distance,
+    // so a follower still further away than that, less the distance it
+    // covers in a tick, will stop short of its leader and is not in conflict
+    // with it however fast it closes in.
+    fn measure_ttc(&mut self, index: usize, intersection: &Intersection, tick: u64) {
+        let vehicles = &intersection.vehicles;
+        for follower in vehicles {
+            let Some((leader, gap)) = follower.leader(vehicles) else {
+                continue;
+            };
+            let stopping_room = follower.driver.following_distance() - follower.speed() * TICK_DURATION;
+            if follower.is_stopped() || gap >= stopping_room {
+                continue;
+            }
+            let Some((ttc, point, angle)) = time_to_collision(leader, follower, self.thresholds.ttc) else {
+                continue;
+            };
+            let event = ConflictEvent {
+                start_tick: tick,
+                end_tick: tick,
+                intersection: index,
+                first: leader.id,
+                second: follower.id,
+                kind: ConflictKind::of_angle(angle),
+                ttc: Some(ttc),
+                pet: None,
+                angle,
+                point,
+                severity: self.thresholds.severity(Some(ttc), None),
+            };
+            let pair = (leader.id, follower.id);
+            match self
+                .encounters
+                .iter_mut()
+                .find(|encounter| encounter.intersection == index && encounter.pair == pair)
+            {
+                Some(encounter) => {
+                    encounter.seen_tick = tick;
+                    if encounter.lowest.ttc.is_some_and(|lowest| ttc < lowest) {
+                        encounter.lowest = event;
+                    }
+                }
+                None => self.encounters.push(Encounter {
+                    intersection: index,
+                    pair,
+                    start_tick: tick,
+                    seen_tick: tick,
+                    lowest: event,
+                }),
+            }
+        }
+    }
+
+    fn measure_pet(&mut self, index: usize, intersection: &Intersection, tick: u64) {
+        let thresholds = self.thresholds;
+        for zone in &mut self.zones[index] {
+            let mut inside = Vec::new();
+            for vehicle in &intersection.vehicles {
+                if let Some((side, true)) = zone.covers(vehicle) {
+                    inside.push((vehicle.id, side));
+                }
+            }
+
+            for &(id, side) in &inside {
+                if zone.inside.contains(&(id, side)) {
+                    continue;
+                }
+                // Reaching the point while a vehicle from the other side is
+                // still over it, or soon after one left
+                let previous = match zone.inside.iter().find(|&&(_, other)| other != side) {
+                    Some(&(other, _)) => Some((other, 0.0)),
+                    None => zone
+                        .last_exit
+                        .filter(|&(_, other, _)| other != side)
+                        .map(|(other, _, left)| (other, (tick - left) as f32 / TICKS_PER_SECOND as f32)),
+                };
+                if let Some((first, pet)) = previous.filter(|&(_, pet)| pet <= thresholds.pet) {
+                    self.events.push(ConflictEvent {
+                        start_tick: tick,
+                        end_tick: tick,
+                        intersection: index,
+                        first,
+                        second: id,
+                        kind: zone.kind,
+                        ttc: None,
+                        pet: Some(pet),
+                        angle: zone.angle,
+                        point: zone.point,
+                        severity: thresholds.severity(None, Some(pet)),
+                    });
+                }
+            }
+            for &(id, side) in &zone.inside {
+                if !inside.contains(&(id, side)) {
+                    zone.last_exit = Some((id, side, tick));
+                }
+            }
+            zone.inside = inside;
+        }
+    }
+
+    // Number of conflicts of each kind
+    pub fn counts(&self) -> [(ConflictKind, usize); 3] {
+        [ConflictKind::RearEnd, ConflictKind::Crossing, ConflictKind::Merging]
+            .map(|kind| (kind, self.events.iter().filter(|event| event.kind == kind).count()))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    fn to_csv(&self) -> String {
+        let seconds = |tick: u64| tick as f32 / TICKS_PER_SECOND as f32;
+        let optional = |value: Option<f32>| value.map_or(String::new(), |value| format!("{:.2}", value));
+        let mut csv = "start,end,intersection,first,second,kind,ttc,pet,severity,angle,x,y\n".to_string();
+        for event in &self.events {
+            let _ = writeln!(
+                csv,
+                "{:.3},{:.3},{},{},{},{},{},{},{},{:.1},{:.2},{:.2}",
+                seconds(event.start_tick),
+                seconds(event.end_tick),
+                event.intersection,
+                event.first,
+                event.second,
+                event.kind.name(),
+                optional(event.ttc),
+                optional(event.pet),
+                event.severity.name(),
+                event.angle,
+                event.point.0,
+                event.point.1
+            );
+        }
+        csv
+    }
+}
+
+// Seconds until two vehicles' bodies would overlap if both kept their
+// current speeds along their paths, up to `horizon`, with where they would
+// meet and the angle between their headings there
+//
+// Neither center moves faster than its vehicle's speed, so while they are
+// far apart the steps in which they can not yet touch are skipped.
+fn time_to_collision(a: &Vehicle, b: &Vehicle, horizon: f32) -> Option<(f32, (f32, f32), f32)> {
+    let steps = (horizon / TTC_STEP).round() as usize;
+    let diagonal = VEHICLE_LENGTH.hypot(VEHICLE_WIDTH);
+    let closing = (a.speed() + b.speed()) * TTC_STEP;
+    let mut step = 0;
+    while step <= steps {
+        let time = step as f32 * TTC_STEP;
+        let (pose_a, pose_b) = (a.pose_ahead(a.speed() * time), b.pose_ahead(b.speed() * time));
+        let apart = distance(pose_a.0, pose_b.0) - diagonal;
+        if apart > 0.0 {
+            step += ((apart / closing) as usize).max(1);
+            continue;
+        }
+        if !polygons_overlap(&Vehicle::corners_at(pose_a), &Vehicle::corners_at(pose_b)) {
+            step += 1;
+            continue;
+        }
+        let ((center_a, heading_a), (center_b, heading_b)) = (pose_a, pose_b);
+        let point = ((center_a.0 + center_b.0) / 2.0, (center_a.1 + center_b.1) / 2.0);
+        return Some((time, point, angle_difference(heading_a, heading_b).to_degrees()));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::VEHICLE_SPEED;
+    use crate::models::driver::DriverProfile;
+    use crate::models::vehicle::StopReason;
+    use crate::simulation::network::NetworkLayout;
+
+    const EAST_WEST: Movement = Movement { from: 0, to: 2 };
+    const SOUTH_NORTH: Movement = Movement { from: 1, to: 3 };
+
+    fn crossing() -> Network {
+        Network::new(1, NetworkLayout::default())
+    }
+
+    // Vehicle at the given distance along a movement of the crossing,
+    // stopped or driving at the nominal speed
+    fn vehicle(network: &Network, id: u32, movement: Movement, travelled: f32, stopped: bool) -> Vehicle {
+        let intersection = &network.intersections[0];
+        let mut vehicle = Vehicle::new(id, intersection.center, intersection.path(movement), DriverProfile::default());
+        vehicle.travelled = travelled;
+        vehicle.stop_reason = stopped.then_some(StopReason::RedLight);
+        vehicle
+    }
+
+    #[test]
+    fn conflicts_are_typed_by_angle() {
+        assert_eq!(ConflictKind::of_angle(0.0), ConflictKind::RearEnd);
+        assert_eq!(ConflictKind::of_angle(29.0), ConflictKind::RearEnd);
+        assert_eq!(ConflictKind::of_angle(30.0), ConflictKind::Merging);
+        assert_eq!(ConflictKind::of_angle(85.0), ConflictKind::Merging);
+        assert_eq!(ConflictKind::of_angle(90.0), ConflictKind::Crossing);
+        assert_eq!(ConflictKind::of_angle(180.0), ConflictKind::Crossing);
+    }
+
+    #[test]
+    fn severity_follows_the_lower_of_ttc_and_pet() {
+        let thresholds = SafetyThresholds::default();
+        assert_eq!(thresholds.severity(Some(0.4), None), Severity::Critical);
+        assert_eq!(thresholds.severity(Some(0.8), None), Severity::Serious);
+        assert_eq!(thresholds.severity(Some(1.2), None), Severity::Minor);
+        assert_eq!(thresholds.severity(None, Some(0.5)), Severity::Critical);
+        assert_eq!(thresholds.severity(None, Some(1.5)), Severity::Serious);
+        assert_eq!(thresholds.severity(None, Some(4.0)), Severity::Minor);
+        assert_eq!(thresholds.severity(Some(1.2), Some(0.5)), Severity::Critical);
+        assert_eq!(thresholds.severity(None, None), Severity::Minor);
+    }
+
+    #[test]
+    fn time_to_collision_projects_both_vehicles() {
+        let network = crossing();
+
+        // Centers 10 m apart close to a vehicle length at 12 m/s
+        let leader = vehicle(&network, 1, EAST_WEST, 30.0, true);
+        let follower = vehicle(&network, 2, EAST_WEST, 20.0, false);
+        let (ttc, _, angle) = time_to_collision(&leader, &follower, 1.5).unwrap();
+        assert!((ttc - (10.0 - VEHICLE_LENGTH) / VEHICLE_SPEED).abs() <= TTC_STEP, "{}", ttc);
+        assert_eq!(ConflictKind::of_angle(angle), ConflictKind::RearEnd);
+        assert!(time_to_collision(&leader, &follower, 0.25).is_none());
+
+        // Both moving at the same speed never close in
+        let leader = vehicle(&network, 1, EAST_WEST, 30.0, false);
+        assert!(time_to_collision(&leader, &follower, 1.5).is_none());
+
+        // Two vehicles a second away from where their paths cross
+        let intersection = &network.intersections[0];
+        let zone = Zone::between(intersection.center, &intersection.path(EAST_WEST), &intersection.path(SOUTH_NORTH));
+        let a = vehicle(&network, 1, EAST_WEST, zone.along[0] - VEHICLE_SPEED, false);
+        let b = vehicle(&network, 2, SOUTH_NORTH, zone.along[1] - VEHICLE_SPEED, false);
+        let (ttc, point, angle) = time_to_collision(&a, &b, 1.5).unwrap();
+        assert!(ttc < 1.0 && ttc > 0.5, "{}", ttc);
+        assert!(distance(point, zone.point) < VEHICLE_LENGTH);
+        assert_eq!(ConflictKind::of_angle(angle), ConflictKind::Crossing);
+    }
+
+    #[test]
+    fn followers_are_measured_against_their_leader_only() {
+        let mut network = crossing();
+        let following = DriverProfile::default().following_distance();
+        network.intersections[0].vehicles = vec![
+            vehicle(&network, 1, EAST_WEST, 40.0, true),
+            vehicle(&network, 2, EAST_WEST, 40.0 - following + 1.5, false),
+            vehicle(&network, 3, EAST_WEST, 40.0 - 2.0 * following + 1.5, false),
+            vehicle(&network, 4, EAST_WEST, 10.0, false),
+        ];
+        let mut monitor = SafetyMonitor::new(&network, SafetyThresholds::default());
+        monitor.record(&network);
+        monitor.finish();
+
+        // 2 has come within its following distance of 1; 3 would run into 1
+        // within the threshold but follows 2 at the same speed, and 4 is
+        // still far enough back to stop behind 3
+        let pairs: Vec<(u32, u32)> = monitor.events.iter().map(|event| (event.first, event.second)).collect();
+        assert_eq!(pairs, [(1, 2)]);
+        assert_eq!(monitor.events[0].kind, ConflictKind::RearEnd);
+        assert_eq!(monitor.events[0].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn post_encroachment_time_runs_from_leaving_to_reaching_the_point() {
+        let mut network = crossing();
+        let mut monitor = SafetyMonitor::new(&network, SafetyThresholds::default());
+        let zone = monitor.zones[0]
+            .iter()
+            .find(|zone| zone.movements == [EAST_WEST, SOUTH_NORTH])
+            .expect("Straight movements cross");
+        let along = zone.along;
+
+        // 1 drives over the point and clears it at tick 10; 2 reaches it
+        // half a second later
+        let mut timeline = vec![
+            (0, vec![vehicle(&network, 1, EAST_WEST, along[0] - 10.0, false)]),
+            (5, vec![vehicle(&network, 1, EAST_WEST, along[0], false)]),
+            (10, vec![vehicle(&network, 1, EAST_WEST, along[0] + 10.0, false)]),
+            (40, vec![vehicle(&network, 2, SOUTH_NORTH, along[1], false)]),
+        ];
+        for (tick, vehicles) in timeline.drain(..) {
+            network.intersections[0].vehicles = vehicles;
+            monitor.measure_pet(0, &network.intersections[0], tick);
+        }
+
+        assert_eq!(monitor.events.len(), 1);
+        let event = &monitor.events[0];
+        assert_eq!((event.first, event.second), (1, 2));
+        assert_eq!(event.pet, Some(0.5));
+        assert_eq!(event.kind, ConflictKind::Crossing);
+        assert_eq!(event.severity, Severity::Critical);
+    }
+}
@@ -0,0 +1,420 @@
+use std::fmt::Write;
+
+use crate::constants::{TICKS_PER_SECOND, VEHICLE_LENGTH, VEHICLE_SAFE_DISTANCE};
+use crate::models::driver::DriverPopulation;
+use crate::models::junction::{JunctionGeometry, Movement};
+use crate::models::route::Route;
+use crate::models::traffic_light::TrafficLightState;
+use crate::simulation::intersection::Control;
+use crate::simulation::network::{JunctionSite, Network, NetworkLayout, RoadGraph};
+
+// Queue positions whose headways count towards the start-up lost time, as
+// in the Highway Capacity Manual; later ones are at the saturation headway
+const START_UP_VEHICLES: usize = 4;
+
+// Shortest headway the approach is made long enough to queue for, in seconds
+const MIN_HEADWAY: f32 = 1.0;
+
+// Vehicles the approach holds beyond those that could discharge in a green
+const SPARE_QUEUE: usize = 10;
+
+// Greens a movement runs through at most for each saturated one it needs
+const GREENS_PER_SAMPLE: u64 = 4;
+
+// How one movement's queue discharged, averaged over the greens it lasted
+// throughout
+#[derive(Debug, Clone)]
+pub struct Capacity {
+    pub movement: Movement,
+    pub route: Route,
+    pub greens: usize,           // Saturated greens measured
+    pub headways: Vec<f32>,      // Mean by position in the queue, in seconds
+    pub saturation_headway: f32, // Seconds
+    pub saturation_flow: f32,    // Vehicles per hour per lane
+    pub start_up_lost_time: f32, // Seconds
+    pub lost_time: f32,          // Seconds of green per cycle not used at the saturation flow
+    pub green: f32,              // Seconds per cycle
+    pub cycle: f32,              // Seconds
+    pub capacity: f32,           // Vehicles per hour
+}
+
+// Queue standing on the approach at one green onset and when each of its
+// vehicles crossed the stop line
+struct Discharge {
+    onset: u64,
+    end: Option<u64>,        // Tick the light turned red again
+    queue: Vec<u32>,         // Front to back
+    crossed: Vec<u64>,       // Ticks, in queue order
+    crossed_in_green: usize, // Before the end of the green
+}
+
+impl Discharge {
+    // The queue lasted until the end of the green, so every vehicle that
+    // crossed did so at the saturation flow
+    fn is_saturated(&self) -> bool {
+        self.end.is_some() && self.crossed_in_green < self.queue.len()
+    }
+}
+
+// Saturation flow study of one approach of a signalised junction
+//
+// Each movement from the approach is measured on its own, on a copy of the
+// junction with the same legs, signal timing and drivers but no other
+// traffic, and with the approach lengthened to hold a queue longer than a
+// green can discharge. Vehicles are spawned as fast as the approach takes
+// them. The vehicles on the approach at green onset are the queue; each
+// one's headway is the time from the rear of the one before it crossing the
+// stop line, or from green onset for the first, to its own rear crossing.
+// Only greens that end with vehicles of the queue still waiting are used.
+//
+// The saturation headway is the mean of the headways after the fourth
+// vehicle, and the start-up lost time what the first four take beyond it.
+// Lost time is the green each cycle that the vehicles getting through,
+// including those crossing just after it ends, leave unused at the
+// saturation flow, and capacity the vehicles getting through per hour.
+pub struct SaturationStudy {
+    pub intersection: usize,
+    pub leg: usize,
+    seed: u64,
+    geometry: JunctionGeometry,
+    drivers: DriverPopulation,
+    cycle_ticks: u64,
+    offset_ticks: u64,
+}
+
+impl SaturationStudy {
+    pub fn new(network: &Network, intersection: usize, leg: usize) -> Result<Self, String> {
+        let junction = network
+            .intersections
+            .get(intersection)
+            .ok_or_else(|| format!("No intersection {}, the network has {}", intersection, network.intersections.len()))?;
+        if !matches!(junction.control, Control::Signals) {
+            return Err(format!("Intersection {} has no signals to measure saturation flow at", intersection));
+        }
+        if !junction.geometry.legs.get(leg).is_some_and(|leg| leg.entry) {
+            return Err(format!("Intersection {} has no approach on leg {}", intersection, leg));
+        }
+
+        // Room for the vehicles a green could let through at the shortest
+        // headway, and then some
+        let mut geometry = junction.geometry.clone();
+        let green = junction.green_ticks() as f32 / TICKS_PER_SECOND as f32;
+        let queue = (green / MIN_HEADWAY).ceil() as usize + SPARE_QUEUE;
+        let length = geometry.stop_line(leg) + queue as f32 * VEHICLE_SAFE_DISTANCE;
+        geometry.legs[leg].length = geometry.legs[leg].length.max(length);
+
+        Ok(SaturationStudy {
+            intersection,
+            leg,
+            seed: network.seed,
+            geometry,
+            drivers: network.layout.drivers,
+            cycle_ticks: junction.cycle_ticks,
+            offset_ticks: junction.offset_ticks,
+        })
+    }
+
+    // Measure every movement from the approach over the given number of
+    // saturated greens each
+    pub fn run(&self, greens: usize) -> Result<Vec<Capacity>, String> {
+        self.geometry
+            .exits(self.leg)
+            .into_iter()
+            .map(|exit| self.measure(exit, greens))
+            .collect()
+    }
+
+    fn measure(&self, exit: usize, greens: usize) -> Result<Capacity, String> {
+        let layout = NetworkLayout {
+            drivers: self.drivers,
+            ..NetworkLayout::default()
+        };
+        let graph = RoadGraph {
+            junctions: vec![JunctionSite {
+                center: (0.0, 0.0),
+                geometry: self.geometry.clone(),
+                control: Control::Signals,
+            }],
+            links: vec![vec![None; self.geometry.legs.len()]],
+        };
+        let mut network = Network::from_graph(self.seed, layout, graph);
+        network.intersections[0].set_signal_timing(self.cycle_ticks, self.offset_ticks);
+        let movement = Movement { from: self.leg, to: exit };
+        let route = network.intersections[0].path(movement).turn;
+
+        let mut samples: Vec<Discharge> = Vec::new();
+        let mut current: Option<Discharge> = None;
+        let limit = self.cycle_ticks * greens as u64 * GREENS_PER_SAMPLE;
+        while samples.len() < greens && network.tick < limit {
+            network.spawn_to(0, self.leg, exit);
+            network.update();
+            let intersection = &network.intersections[0];
+            let Some(light) = intersection.traffic_lights.iter().find(|light| light.leg == self.leg) else {
+                break;
+            };
+
+            if light.changed_at == network.tick {
+                match light.state {
+                    // A green onset ends the previous cycle's discharge
+                    TrafficLightState::Green => {
+                        samples.extend(current.take().filter(Discharge::is_saturated));
+                        let mut queue: Vec<(f32, u32)> = intersection
+                            .vehicles
+                            .iter()
+                            .filter(|vehicle| vehicle.path.movement.from == self.leg && vehicle.is_approaching())
+                            .map(|vehicle| (vehicle.travelled, vehicle.id))
+                            .collect();
+                        queue.sort_by(|a, b| b.0.total_cmp(&a.0));
+                        current = Some(Discharge {
+                            onset: network.tick,
+                            end: None,
+                            queue: queue.into_iter().map(|(_, id)| id).collect(),
+                            crossed: Vec::new(),
+                            crossed_in_green: 0,
+                        });
+                    }
+                    TrafficLightState::Red => {
+                        if let Some(discharge) = &mut current {
+                            discharge.end = Some(network.tick);
+                            discharge.crossed_in_green = discharge.crossed.len();
+                        }
+                    }
+                }
+            }
+
+            // Vehicles of a single lane cross in the order they queued
+            if let Some(discharge) = &mut current {
+                while let Some(&id) = discharge.queue.get(discharge.crossed.len()) {
+                    let crossed = intersection
+                        .vehicle(id)
+                        .is_none_or(|vehicle| vehicle.travelled > vehicle.path.stop_at + VEHICLE_LENGTH);
+                    if !crossed {
+                        break;
+                    }
+                    discharge.crossed.push(network.tick);
+                }
+            }
+        }
+
+        if samples.is_empty() {
+            return Err(format!(
+                "The queue of the {} movement from leg {} cleared within every green, as its vehicles discharge faster than they can be spawned, one a second",
+                route.name(),
+                self.leg
+            ));
+        }
+        summarise(movement, route, &samples, self.cycle_ticks).ok_or_else(|| {
+            format!(
+                "Too few vehicles got through each green to measure the saturation headway of the {} movement from leg {}; use a longer --cycle",
+                route.name(),
+                self.leg
+            )
+        })
+    }
+}
+
+// Averages over the saturated greens, if vehicles past the start-up ones got
+// through any of them
+fn summarise(movement: Movement, route: Route, samples: &[Discharge], cycle_ticks: u64) -> Option<Capacity> {
+    let seconds = |ticks: u64| ticks as f32 / TICKS_PER_SECOND as f32;
+    let headways_of = |discharge: &Discharge| -> Vec<f32> {
+        let mut previous = discharge.onset;
+        discharge
+            .crossed
+            .iter()
+            .map(|&tick| {
+                let headway = seconds(tick - previous);
+                previous = tick;
+                headway
+            })
+            .collect()
+    };
+    let all: Vec<Vec<f32>> = samples.iter().map(headways_of).collect();
+
+    let saturated: Vec<f32> = all.iter().flat_map(|headways| headways.iter().skip(START_UP_VEHICLES)).copied().collect();
+    if saturated.is_empty() {
+        return None;
+    }
+    let saturation_headway = saturated.iter().sum::<f32>() / saturated.len() as f32;
+
+    let positions = all.iter().map(Vec::len).max().unwrap_or(0);
+    let headways: Vec<f32> = (0..positions)
+        .map(|position| {
+            let at: Vec<f32> = all.iter().filter_map(|headways| headways.get(position)).copied().collect();
+            at.iter().sum::<f32>() / at.len() as f32
+        })
+        .collect();
+    let start_up_lost_time = headways
+        .iter()
+        .take(START_UP_VEHICLES)
+        .map(|headway| headway - saturation_headway)
+        .sum();
+
+    let count = samples.len() as f32;
+    let through = samples.iter().map(|discharge| discharge.crossed.len()).sum::<usize>() as f32 / count;
+    let green = samples
+        .iter()
+        .filter_map(|discharge| discharge.end.map(|end| seconds(end - discharge.onset)))
+        .sum::<f32>()
+        / count;
+    let cycle = seconds(cycle_ticks);
+    Some(Capacity {
+        movement,
+        route,
+        greens: samples.len(),
+        headways,
+        saturation_headway,
+        saturation_flow: 3600.0 / saturation_headway,
+        start_up_lost_time,
+        lost_time: green - through * saturation_headway,
+        green,
+        cycle,
+        capacity: through * 3600.0 / cycle,
+    })
+}
+
+// Approach given as `<approach>` or `<approach>@<intersection>`, where the
+// approach is a leg number or its label on the status panel, such as `NB`
+// or `L3`; the intersection is the first one if not given
+pub fn parse_approach(text: &str, network: &Network) -> Result<(usize, usize), String> {
+    let (approach, intersection) = match text.split_once('@') {
+        Some((approach, intersection)) => (
+            approach,
+            intersection
+                .parse()
+                .map_err(|_| format!("Invalid intersection '{}'", intersection))?,
+        ),
+        None => (text, 0),
+    };
+    let geometry = &network
+        .intersections
+        .get(intersection)
+        .ok_or_else(|| format!("No intersection {}, the network has {}", intersection, network.intersections.len()))?
+        .geometry;
+    let leg = match approach.parse() {
+        Ok(leg) => leg,
+        Err(_) => (0..geometry.legs.len())
+            .find(|&leg| geometry.approach_label(leg).eq_ignore_ascii_case(approach))
+            .ok_or_else(|| format!("Unknown approach '{}', expected a leg number or a label such as NB", approach))?,
+    };
+    Ok((intersection, leg))
+}
+
+// Table of the results of a study, one row per movement, followed by the
+// mean headway at each queue position
+pub fn report(study: &SaturationStudy, capacities: &[Capacity]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "Saturation flow of {} (leg {}) at intersection {}",
+        study.geometry.approach_label(study.leg),
+        study.leg,
+        study.intersection
+    );
+    let _ = writeln!(
+        text,
+        "{:<10} {:>4} {:>7} {:>10} {:>14} {:>9} {:>9} {:>8} {:>8} {:>9}",
+        "movement", "exit", "greens", "headway s", "sat veh/h/ln", "startup s", "lost s", "green s", "cycle s", "cap veh/h"
+    );
+    for capacity in capacities {
+        let _ = writeln!(
+            text,
+            "{:<10} {:>4} {:>7} {:>10.2} {:>14.0} {:>9.2} {:>9.2} {:>8.1} {:>8.1} {:>9.0}",
+            capacity.route.name(),
+            capacity.movement.to,
+            capacity.greens,
+            capacity.saturation_headway,
+            capacity.saturation_flow,
+            capacity.start_up_lost_time,
+            capacity.lost_time,
+            capacity.green,
+            capacity.cycle,
+            capacity.capacity
+        );
+    }
+    for capacity in capacities {
+        let headways: Vec<String> = capacity.headways.iter().map(|headway| format!("{:.2}", headway)).collect();
+        let _ = writeln!(text, "Headways of {} by queue position, s: {}", capacity.route.name(), headways.join(" "));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Green onset at tick 0 with the queue crossing at the given headways,
+    // in seconds, and the light turning red after `green` seconds
+    fn discharge(queue: usize, headways: &[f32], green: f32) -> Discharge {
+        let mut tick = 0;
+        let crossed: Vec<u64> = headways
+            .iter()
+            .map(|headway| {
+                tick += (headway * TICKS_PER_SECOND as f32).round() as u64;
+                tick
+            })
+            .collect();
+        let end = (green * TICKS_PER_SECOND as f32).round() as u64;
+        Discharge {
+            onset: 0,
+            end: Some(end),
+            queue: (0..queue as u32).collect(),
+            crossed_in_green: crossed.iter().filter(|&&tick| tick < end).count(),
+            crossed,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn summarise_averages_saturated_greens() {
+        let movement = Movement { from: 1, to: 3 };
+        let samples = [
+            discharge(15, &[3.0, 2.5, 2.0, 2.1, 1.9, 1.9, 1.9, 1.9, 1.9, 1.9, 1.9, 1.9], 24.0),
+            discharge(15, &[3.2, 2.3, 2.2, 1.9, 2.1, 2.1, 2.1, 2.1, 2.1, 2.1], 24.0),
+        ];
+        assert!(samples.iter().all(Discharge::is_saturated));
+        let capacity = summarise(movement, Route::Straight, &samples, 60 * TICKS_PER_SECOND).unwrap();
+
+        // Eight headways of 1.9 s and six of 2.1 s after the first four vehicles
+        let saturation_headway = (8.0 * 1.9 + 6.0 * 2.1) / 14.0;
+        assert!(close(capacity.saturation_headway, saturation_headway));
+        assert!(close(capacity.saturation_flow, 3600.0 / saturation_headway));
+        assert_eq!(capacity.headways.len(), 12);
+        assert!(close(capacity.headways[0], 3.1) && close(capacity.headways[11], 1.9));
+        assert!(close(capacity.start_up_lost_time, 3.1 + 2.4 + 2.1 + 2.0 - 4.0 * saturation_headway));
+
+        // Eleven vehicles through each green of 24 s in a 60 s cycle
+        assert_eq!(capacity.greens, 2);
+        assert!(close(capacity.green, 24.0) && close(capacity.cycle, 60.0));
+        assert!(close(capacity.lost_time, 24.0 - 11.0 * saturation_headway));
+        assert!(close(capacity.capacity, 11.0 * 60.0));
+    }
+
+    #[test]
+    fn summarise_needs_vehicles_past_the_start_up_ones() {
+        let samples = [discharge(6, &[3.0, 2.5, 2.0, 2.1], 10.0)];
+        assert!(summarise(Movement { from: 0, to: 2 }, Route::Straight, &samples, 20 * TICKS_PER_SECOND).is_none());
+        assert!(!discharge(6, &[3.0, 2.5, 2.0, 2.1, 2.0, 2.0], 20.0).is_saturated());
+    }
+
+    #[test]
+    fn typical_drivers_discharge_at_base_saturation_flow() {
+        let layout = NetworkLayout {
+            drivers: DriverPopulation::typical(),
+            ..NetworkLayout::default()
+        };
+        let mut network = Network::from_graph(1, layout.clone(), RoadGraph::of(&layout).unwrap());
+        network.intersections[0].set_signal_timing(60 * TICKS_PER_SECOND, 0);
+        let study = SaturationStudy::new(&network, 0, 1).unwrap();
+        let capacity = study.measure(3, 8).unwrap();
+
+        // The Highway Capacity Manual's base saturation headway is about 1.9
+        // to 2.0 s and its start-up lost time about 2 s
+        assert!((1.7..=2.1).contains(&capacity.saturation_headway), "{:?}", capacity);
+        assert!((1.5..=3.0).contains(&capacity.start_up_lost_time), "{:?}", capacity);
+        assert!((1700.0..=2100.0).contains(&capacity.saturation_flow), "{:?}", capacity);
+    }
+}
@@ -0,0 +1,323 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Instant;
+
+use crate::constants::TICKS_PER_SECOND;
+use crate::models::traffic_light::TrafficLightState;
+use crate::models::vehicle::StopReason;
+use crate::simulation::dashboard::Dashboard;
+use crate::simulation::network::Network;
+use crate::simulation::replay::{InputEvent, Recorder};
+
+// Most ticks a single `step` request may advance
+const MAX_STEP_TICKS: u64 = 3600 * TICKS_PER_SECOND;
+
+// Remote control of a headless simulation over TCP
+//
+// Clients connect one at a time and send one request per line; every request
+// gets a single line of JSON back, `{"ok":true,"result":...}` or
+// `{"ok":false,"error":"..."}`. The simulation only advances on `step`.
+// Inputs that change it use the same syntax as the lines of a replay file
+// (`spawn_leg 2 left`, `force_green 1 at 3`, `select_phase 0`, ...) and are
+// recorded like live input. `close` ends the connection and `shutdown` stops
+// the server.
+pub struct Server {
+    pub network: Network,
+    pub recorder: Recorder,
+    pub dashboard: Option<Dashboard>,
+}
+
+impl Server {
+    pub fn new(network: Network, recorder: Recorder) -> Self {
+        Server {
+            network,
+            recorder,
+            dashboard: None,
+        }
+    }
+
+    // Serve clients until one asks for a shutdown; a client whose connection
+    // fails is dropped and the next one served
+    pub fn run(&mut self, address: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        println!("Listening on {}", listener.local_addr()?);
+        for stream in listener.incoming() {
+            match stream.and_then(|stream| self.serve_client(stream)) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => println!("Client connection failed: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    fn serve_client(&mut self, stream: TcpStream) -> io::Result<bool> {
+        let mut writer = stream.try_clone()?;
+        self.serve(BufReader::new(stream), &mut writer)
+    }
+
+    // Answer a client's requests until it closes the connection; true if it
+    // asked for a shutdown
+    pub fn serve(&mut self, mut reader: impl BufRead, writer: &mut impl Write) -> io::Result<bool> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(false);
+            }
+            let Ok(request) = std::str::from_utf8(&line) else {
+                writeln!(writer, "{{\"ok\":false,\"error\":\"request is not UTF-8\"}}")?;
+                continue;
+            };
+            let request = request.trim();
+            if request.is_empty() {
+                continue;
+            }
+            writeln!(writer, "{}", self.reply(request))?;
+            match request {
+                "close" => return Ok(false),
+                "shutdown" => return Ok(true),
+                _ => {}
+            }
+        }
+    }
+
+    // Line of JSON answering one request
+    pub fn reply(&mut self, request: &str) -> String {
+        match self.handle(request) {
+            Ok(result) => format!("{{\"ok\":true,\"result\":{}}}", result),
+            Err(error) => format!("{{\"ok\":false,\"error\":\"{}\"}}", json_escape(&error)),
+        }
+    }
+
+    // Answer one request, with the JSON result
+    pub fn handle(&mut self, request: &str) -> Result<String, String> {
+        let parts: Vec<&str> = request.split_whitespace().collect();
+        match parts[..] {
+            ["step"] => self.step(1),
+            ["step", ticks] => self.step(
+                ticks
+                    .parse()
+                    .ok()
+                    .filter(|ticks| (1..=MAX_STEP_TICKS).contains(ticks))
+                    .ok_or(format!("invalid tick count '{}'", ticks))?,
+            ),
+            ["vehicles"] => Ok(vehicles_json(&self.network)),
+            ["lights"] => Ok(lights_json(&self.network)),
+            ["metrics"] => Ok(metrics_json(&self.network)),
+            ["close"] | ["shutdown"] => Ok("null".to_string()),
+            _ => {
+                let input = InputEvent::parse(&parts)?;
+                if !input.changes_simulation() {
+                    return Err(format!("'{}' does not apply to a remote simulation", request));
+                }
+                let next_id = self.network.next_vehicle_id;
+                self.recorder.record(self.network.tick, input);
+                self.network.apply_input(input);
+                // Spawns answer with the new vehicle's id, or null when there was no room
+                Ok(match input {
+                    InputEvent::Spawn { .. } | InputEvent::SpawnAt { .. } | InputEvent::SpawnTo { .. } => {
+                        if self.network.next_vehicle_id > next_id {
+                            next_id.to_string()
+                        } else {
+                            "null".to_string()
+                        }
+                    }
+                    _ => "null".to_string(),
+                })
+            }
+        }
+    }
+
+    fn step(&mut self, ticks: u64) -> Result<String, String> {
+        let started = Instant::now();
+        for _ in 0..ticks {
+            let tick_started = Instant::now();
+            self.network.update();
+            if let Some(dashboard) = &mut self.dashboard {
+                dashboard.record(&self.network, tick_started.elapsed());
+            }
+        }
+        Ok(format!(
+            "{{\"tick\":{},\"time\":{:.3},\"step_ms\":{:.3}}}",
+            self.network.tick,
+            self.network.tick as f32 / TICKS_PER_SECOND as f32,
+            started.elapsed().as_secs_f64() * 1000.0
+        ))
+    }
+}
+
+// Every vehicle with the intersection it is driving through
+pub fn vehicles_json(network: &Network) -> String {
+    let vehicles: Vec<String> = network
+        .intersections
+        .iter()
+        .enumerate()
+        .flat_map(|(index, intersection)| intersection.vehicles.iter().map(move |vehicle| (index, vehicle)))
+        .map(|(index, vehicle)| {
+            let stop_reason = match vehicle.stop_reason {
+                None => "null".to_string(),
+                Some(StopReason::Leader(id)) => format!("\"leader {}\"", id),
+                Some(reason) => format!("\"{}\"", reason.name()),
+            };
+            format!(
+                "{{\"id\":{},\"x\":{:.2},\"y\":{:.2},\"heading\":{:.4},\"speed\":{:.2},\"intersection\":{},\"from\":{},\"to\":{},\"route\":\"{}\",\"waiting\":{:.2},\"stop_reason\":{}}}",
+                vehicle.id,
+                vehicle.position.0,
+                vehicle.position.1,
+                vehicle.heading,
+                vehicle.speed(),
+                index,
+                vehicle.path.movement.from,
+                vehicle.path.movement.to,
+                vehicle.route.name(),
+                vehicle.waiting_ticks as f32 / TICKS_PER_SECOND as f32,
+                stop_reason
+            )
+        })
+        .collect();
+    format!("[{}]", vehicles.join(","))
+}
+
+// Control and light state of every intersection
+pub fn lights_json(network: &Network) -> String {
+    let intersections: Vec<String> = network
+        .intersections
+        .iter()
+        .enumerate()
+        .map(|(index, intersection)| {
+            let phases: Vec<String> = intersection.phases.iter().map(|phase| json_list(phase)).collect();
+            let lights: Vec<String> = intersection
+                .traffic_lights
+                .iter()
+                .map(|light| {
+                    let state = match light.state {
+                        TrafficLightState::Red => "red",
+                        TrafficLightState::Green => "green",
+                    };
+                    format!("{{\"leg\":{},\"state\":\"{}\"}}", light.leg, state)
+                })
+                .collect();
+            format!(
+                "{{\"intersection\":{},\"control\":\"{}\",\"phase\":{},\"phases\":[{}],\"remaining\":{:.2},\"held\":{},\"lights\":[{}]}}",
+                index,
+                intersection.control.name(),
+                intersection.phase(),
+                phases.join(","),
+                intersection.phase_remaining_ticks() as f32 / TICKS_PER_SECOND as f32,
+                intersection.phase_held,
+                lights.join(",")
+            )
+        })
+        .collect();
+    format!("[{}]", intersections.join(","))
+}
+
+// Network-wide counters and the vehicles approaching each leg of each intersection
+pub fn metrics_json(network: &Network) -> String {
+    let metrics = &network.metrics;
+    let queues: Vec<String> = network
+        .intersections
+        .iter()
+        .map(|intersection| {
+            let counts: Vec<usize> = (0..intersection.geometry.legs.len())
+                .map(|leg| intersection.approach_count(leg))
+                .collect();
+            json_list(&counts)
+        })
+        .collect();
+    format!(
+        "{{\"tick\":{},\"spawned\":{},\"exited\":{},\"in_network\":{},\"average_delay\":{:.3},\"throughput\":{:.1},\"approaching\":[{}]}}",
+        network.tick,
+        metrics.spawned,
+        metrics.exited,
+        network.vehicles().count(),
+        metrics.average_delay(),
+        metrics.throughput(network.tick),
+        queues.join(",")
+    )
+}
+
+fn json_list<T: ToString>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+// Text as the inside of a JSON string, with quotes, backslashes and control
+// characters escaped
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::coordination::SignalPlan;
+    use crate::simulation::network::{NetworkLayout, RoadGraph};
+
+    // Server on a single crossing, as a local client would find it
+    fn server() -> Server {
+        let layout = NetworkLayout::default();
+        let network = Network::from_graph(1, layout.clone(), RoadGraph::of(&layout).unwrap());
+        Server::new(network, Recorder::new(1, layout, SignalPlan::uncoordinated(1)))
+    }
+
+    #[test]
+    fn step_advances_the_tick() {
+        let mut server = server();
+        let reply = server.reply("step 60");
+        assert!(reply.starts_with("{\"ok\":true,\"result\":{\"tick\":60,"), "{}", reply);
+        assert_eq!(server.network.tick, 60);
+    }
+
+    #[test]
+    fn spawn_answers_with_the_id_or_null_without_room() {
+        let mut server = server();
+        server.handle("step 60").unwrap();
+        assert_eq!(server.handle("spawn_leg 0 straight"), Ok("1".to_string()));
+        assert_eq!(server.handle("spawn_leg 0 straight"), Ok("null".to_string()));
+        assert_eq!(server.network.vehicles().count(), 1);
+        assert_eq!(server.recorder.events.len(), 2);
+    }
+
+    #[test]
+    fn unknown_and_viewer_only_requests_are_errors() {
+        let mut server = server();
+        assert!(server.reply("fly away").starts_with("{\"ok\":false,\"error\":\""));
+        assert!(server.reply("pause").starts_with("{\"ok\":false,"));
+        assert_eq!(server.network.tick, 0);
+    }
+
+    #[test]
+    fn requests_that_are_not_utf8_get_an_error_and_the_client_stays() {
+        let mut server = server();
+        let mut output = Vec::new();
+        let shutdown = server.serve(&b"step\n\xff\xfe\nstep 2\nclose\nstep\n"[..], &mut output).unwrap();
+        assert!(!shutdown);
+        let output = String::from_utf8(output).unwrap();
+        let replies: Vec<&str> = output.lines().collect();
+        assert_eq!(replies.len(), 4, "{}", output);
+        assert_eq!(replies[1], "{\"ok\":false,\"error\":\"request is not UTF-8\"}");
+        assert!(replies[2].starts_with("{\"ok\":true,\"result\":{\"tick\":3,"), "{}", replies[2]);
+        assert_eq!(server.network.tick, 3);
+    }
+
+    #[test]
+    fn errors_escape_control_characters() {
+        assert_eq!(json_escape("speed\t2 \"x\"\n\u{1}"), "speed\\t2 \\\"x\\\"\\n\\u0001");
+        let reply = server().reply("pause\tnow");
+        assert!(!reply.contains('\t'), "{}", reply);
+    }
+}
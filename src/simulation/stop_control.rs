@@ -0,0 +1,276 @@
+use std::f32::consts::TAU;
+
+use crate::constants::{
+    LANE_WIDTH, ROAD_WIDTH, STOP_BAR_WIDTH, STOP_CRITICAL_GAP, STOP_FULL_STOP_TIME, STOP_SIGN_SIZE,
+    TICK_DURATION,
+};
+use crate::models::junction::JunctionGeometry;
+use crate::models::vehicle::{StopReason, Vehicle};
+use crate::simulation::intersection::ms_to_ticks;
+use crate::ui::camera::Camera;
+use crate::ui::shapes::fill_polygon;
+use crate::ui::surface::{Color, Surface};
+
+// Which legs have to stop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopRule {
+    AllWay, // Every leg; vehicles go in the order they stopped
+    TwoWay, // Only the minor road; it gives way to the major road
+}
+
+// Unsignalised junction controlled by stop signs
+//
+// Stopping vehicles stand at the line for `full_stop` ticks before they may
+// go. On an all-way stop they then go first-come-first-served: a vehicle
+// waits while a crossing vehicle is in the junction or stopped at its line
+// before it. On a two-way stop the legs of the major road never stop and only
+// give way to vehicles already in the junction; minor-road vehicles also need
+// every crossing major-road vehicle to be at least the critical gap away from
+// its stop line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopControl {
+    pub rule: StopRule,
+    pub full_stop: u64,    // Ticks
+    pub critical_gap: u64, // Ticks
+}
+
+impl StopControl {
+    pub fn new(rule: StopRule) -> Self {
+        StopControl {
+            rule,
+            full_stop: ms_to_ticks(STOP_FULL_STOP_TIME),
+            critical_gap: ms_to_ticks(STOP_CRITICAL_GAP),
+        }
+    }
+
+    // Whether vehicles coming in by a leg have to stop, given the legs of the
+    // major road
+    pub fn stops(&self, leg: usize, major: &[usize]) -> bool {
+        self.rule == StopRule::AllWay || !major.contains(&leg)
+    }
+
+    // Move every vehicle one tick
+    //
+    // `snapshot` is the state at the start of the tick, `major` the legs of
+    // the major road, `arrivals` the vehicle stopped at each leg's line and
+    // the tick it got there, and `exits_blocked` whether each vehicle has to
+    // wait at the end of its exit.
+    pub fn update(
+        &self,
+        tick: u64,
+        vehicles: &mut [Vehicle],
+        snapshot: &[Vehicle],
+        major: &[usize],
+        arrivals: &mut [Option<(u32, u64)>],
+        exits_blocked: &[bool],
+    ) {
+        for (vehicle, &exit_blocked) in vehicles.iter_mut().zip(exits_blocked) {
+            let leg = vehicle.path.movement.from;
+            let stops = self.stops(leg, major);
+            if stops && vehicle.is_in_stop_zone() && arrivals[leg].map(|(id, _)| id) != Some(vehicle.id) {
+                arrivals[leg] = Some((vehicle.id, tick));
+            }
+            let stopped_for = arrivals[leg]
+                .filter(|&(id, _)| id == vehicle.id)
+                .map_or(0, |(_, arrived)| tick - arrived);
+
+            vehicle.stop_reason = if let Some(leader) = vehicle.should_stop_for_vehicle(snapshot) {
+                Some(StopReason::Leader(leader))
+            } else if stops && vehicle.is_in_stop_zone() && stopped_for < self.full_stop {
+                Some(StopReason::StopSign)
+            } else if stops && self.must_yield(vehicle, snapshot, major, arrivals) {
+                Some(StopReason::Yield)
+            } else if !stops && self.must_give_way(vehicle, snapshot, major) {
+                Some(StopReason::Conflict)
+            } else if exit_blocked {
+                Some(StopReason::Spillback)
+            } else {
+                None
+            };
+
+            vehicle.act(snapshot);
+        }
+    }
+
+    // A stopped vehicle waits for crossing vehicles in the junction, crossing
+    // vehicles that stopped before it and, on a two-way stop, crossing
+    // major-road vehicles less than the critical gap from their line
+    fn must_yield(
+        &self,
+        vehicle: &Vehicle,
+        vehicles: &[Vehicle],
+        major: &[usize],
+        arrivals: &[Option<(u32, u64)>],
+    ) -> bool {
+        if !vehicle.is_in_stop_zone() {
+            return false;
+        }
+
+        // Ties between vehicles that stopped on the same tick go to the one
+        // spawned first
+        let arrival = |v: &Vehicle| {
+            arrivals[v.path.movement.from]
+                .filter(|&(id, _)| id == v.id)
+                .map(|(_, tick)| (tick, v.id))
+        };
+        let own = arrival(vehicle);
+        let critical_time = self.critical_gap as f32 * TICK_DURATION;
+        vehicles.iter().any(|other| {
+            if other.id == vehicle.id
+                || other.junction != vehicle.junction
+                || !vehicle.path.conflicts.contains(&other.path.movement)
+            {
+                return false;
+            }
+            if other.is_in_junction() {
+                return true;
+            }
+            if !self.stops(other.path.movement.from, major) {
+                // Judged by how long the major-road driver takes to reach the
+                // line at their own speed
+                let critical_distance = critical_time * other.driver.desired_speed;
+                return !other.has_entered() && other.path.stop_at - other.travelled < critical_distance;
+            }
+            other.is_in_stop_zone() && arrival(other).zip(own).is_some_and(|(theirs, own)| theirs < own)
+        })
+    }
+
+    // A major-road vehicle on a two-way stop waits only for crossing vehicles
+    // in the junction, and for crossing major-road vehicles about to enter
+    // that were spawned first
+    fn must_give_way(&self, vehicle: &Vehicle, vehicles: &[Vehicle], major: &[usize]) -> bool {
+        vehicle.is_in_stop_zone()
+            && vehicles.iter().any(|other| {
+                other.id != vehicle.id
+                    && other.junction == vehicle.junction
+                    && vehicle.path.conflicts.contains(&other.path.movement)
+                    && (other.is_in_junction()
+                        || (!self.stops(other.path.movement.from, major)
+                            && other.is_in_stop_zone()
+                            && other.id < vehicle.id))
+            })
+    }
+
+    // Stop bar across the inbound lane and a stop sign beside it, on every
+    // leg that stops
+    pub fn render(
+        &self,
+        canvas: &mut dyn Surface,
+        camera: &Camera,
+        center: (f32, f32),
+        geometry: &JunctionGeometry,
+        major: &[usize],
+    ) {
+        let world = |(x, y): (f32, f32)| (center.0 + x, center.1 + y);
+        for (index, leg) in geometry.legs.iter().enumerate() {
+            if !leg.entry || !self.stops(index, major) {
+                continue;
+            }
+            let line = geometry.stop_line(index);
+            let bar = leg.strip(line, line + STOP_BAR_WIDTH, -LANE_WIDTH / 2.0, LANE_WIDTH).map(world);
+            fill_polygon(canvas, camera, &bar, Color::RGB(255, 255, 255));
+
+            let (x, y) = world(leg.point(line + STOP_SIGN_SIZE / 2.0, -(ROAD_WIDTH + STOP_SIGN_SIZE) / 2.0));
+            let radius = STOP_SIGN_SIZE / 2.0;
+            let octagon: Vec<(f32, f32)> = (0..8)
+                .map(|corner| {
+                    let angle = (corner as f32 + 0.5) * TAU / 8.0;
+                    (x + radius * angle.cos(), y + radius * angle.sin())
+                })
+                .collect();
+            fill_polygon(canvas, camera, &octagon, Color::RGB(200, 0, 0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{TICKS_PER_SECOND, VEHICLE_SPEED};
+    use crate::models::driver::DriverProfile;
+    use crate::models::junction::Movement;
+    use crate::simulation::intersection::Control;
+    use crate::simulation::network::{Network, NetworkLayout};
+
+    // Minor road east-west, major road north-south
+    const FROM_EAST: Movement = Movement { from: 0, to: 2 };
+    const FROM_SOUTH: Movement = Movement { from: 1, to: 3 };
+    const MAJOR: [usize; 2] = [1, 3];
+
+    fn crossing(rule: StopRule) -> (Network, StopControl) {
+        let stop = StopControl::new(rule);
+        let layout = NetworkLayout {
+            control: Control::Stop(stop),
+            ..NetworkLayout::default()
+        };
+        (Network::new(1, layout), stop)
+    }
+
+    // Vehicle the given distance short of its stop line, at its line if none
+    fn vehicle(network: &Network, id: u32, movement: Movement, short_of_line: f32, desired_speed: f32) -> Vehicle {
+        let intersection = &network.intersections[0];
+        let driver = DriverProfile {
+            desired_speed,
+            ..DriverProfile::default()
+        };
+        let mut vehicle = Vehicle::new(id, intersection.center, intersection.path(movement), driver);
+        vehicle.travelled = vehicle.path.stop_at - short_of_line;
+        vehicle
+    }
+
+    #[test]
+    fn all_way_stop_is_first_come_first_served() {
+        let (network, stop) = crossing(StopRule::AllWay);
+        let vehicles = [
+            vehicle(&network, 1, FROM_EAST, 0.0, VEHICLE_SPEED),
+            vehicle(&network, 2, FROM_SOUTH, 0.0, VEHICLE_SPEED),
+        ];
+        let mut arrivals = vec![None; 4];
+
+        arrivals[0] = Some((1, 20));
+        arrivals[1] = Some((2, 10));
+        assert!(stop.must_yield(&vehicles[0], &vehicles, &MAJOR, &arrivals));
+        assert!(!stop.must_yield(&vehicles[1], &vehicles, &MAJOR, &arrivals));
+
+        arrivals[1] = Some((2, 30));
+        assert!(!stop.must_yield(&vehicles[0], &vehicles, &MAJOR, &arrivals));
+        assert!(stop.must_yield(&vehicles[1], &vehicles, &MAJOR, &arrivals));
+
+        // Stopping on the same tick, the one spawned first goes first
+        arrivals[1] = Some((2, 20));
+        assert!(!stop.must_yield(&vehicles[0], &vehicles, &MAJOR, &arrivals));
+        assert!(stop.must_yield(&vehicles[1], &vehicles, &MAJOR, &arrivals));
+    }
+
+    #[test]
+    fn minor_road_waits_for_a_gap_in_major_road_traffic() {
+        let (network, stop) = crossing(StopRule::TwoWay);
+        assert!(stop.stops(0, &MAJOR) && !stop.stops(1, &MAJOR));
+        let critical = stop.critical_gap as f32 / TICKS_PER_SECOND as f32;
+        let mut arrivals = vec![None; 4];
+        arrivals[0] = Some((1, 0));
+
+        // A major-road vehicle 30 m from its line, less or more than the
+        // critical gap away at its speed
+        for (seconds, yields) in [(critical - 1.0, true), (critical + 1.0, false)] {
+            let vehicles = [
+                vehicle(&network, 1, FROM_EAST, 0.0, VEHICLE_SPEED),
+                vehicle(&network, 2, FROM_SOUTH, 30.0, 30.0 / seconds),
+            ];
+            assert_eq!(stop.must_yield(&vehicles[0], &vehicles, &MAJOR, &arrivals), yields);
+        }
+    }
+
+    #[test]
+    fn major_road_gives_way_only_to_vehicles_in_the_junction() {
+        let (network, stop) = crossing(StopRule::TwoWay);
+        let waiting = vehicle(&network, 1, FROM_EAST, 0.0, VEHICLE_SPEED);
+        let major = vehicle(&network, 2, FROM_SOUTH, 0.0, VEHICLE_SPEED);
+        assert!(!stop.must_give_way(&major, &[waiting.clone(), major.clone()], &MAJOR));
+
+        // The minor-road vehicle 5 m past its line
+        let crossing = vehicle(&network, 1, FROM_EAST, -5.0, VEHICLE_SPEED);
+        assert!(crossing.is_in_junction());
+        assert!(stop.must_give_way(&major, &[crossing, major.clone()], &MAJOR));
+    }
+}
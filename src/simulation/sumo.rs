@@ -0,0 +1,804 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fs;
+use std::io;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::constants::{
+    APPROACH_LENGTH, MIN_LINK_LENGTH, ROAD_WIDTH, TICKS_PER_SECOND, VEHICLE_LENGTH, VEHICLE_SAFE_DISTANCE,
+    VEHICLE_SPEED,
+};
+use crate::models::geometry::distance;
+use crate::models::junction::{JunctionGeometry, Leg, Movement};
+use crate::models::route::Route;
+use crate::simulation::intersection::{Control, Intersection};
+use crate::simulation::network::{JunctionSite, Network, RoadGraph};
+use crate::simulation::replay::InputEvent;
+use crate::simulation::stop_control::{StopControl, StopRule};
+use crate::simulation::xml::{Tag, Tags};
+
+// Flows without an end run this long, as in SUMO, in seconds
+const DEFAULT_FLOW_END: f64 = 86_400.0;
+
+// Network files are told apart from OpenStreetMap files by their name
+pub fn is_network(path: &str) -> bool {
+    path.ends_with(".net.xml")
+}
+
+// Junction read from a SUMO network, with what is needed to read route files
+// written for it
+pub struct SumoNet {
+    pub junction: String,   // Id of the junction in the file
+    pub cycle: Option<f32>, // Length of its signal program, in seconds
+    edges: HashMap<String, (usize, bool)>, // Leg of each edge at the junction, and whether it leads in
+}
+
+// Edge of a SUMO network, internal edges left out
+struct SumoEdge {
+    id: String,
+    from: String,
+    to: String,
+    priority: i32,
+    lanes: u32,
+}
+
+// Contents of a network file that matter for a single junction
+#[derive(Default)]
+struct NetData {
+    edges: Vec<SumoEdge>,
+    junctions: Vec<(String, String, (f32, f32))>, // Id, type and position
+    connections: Vec<(String, String, Option<String>)>, // From and to edge, and the signal controlling it
+    programs: HashMap<String, f32>,                     // Cycle of each signal program, in seconds
+}
+
+// Read a SUMO network and take its busiest junction with its approaches
+//
+// The junction with the most edges is used; the edges to and from each
+// neighbouring junction make up one leg, whose angle points at that junction
+// and whose length is the distance to it, clamped to the length of an
+// approach. Legs with only incoming or only outgoing edges are one way, the
+// turns no connection allows are banned, and the priority and lane count of
+// the edges rank the legs. Junctions of type `traffic_light` get signals,
+// `allway_stop` an all-way stop and the priority types a two-way stop.
+pub fn import(path: &str) -> Result<(RoadGraph, SumoNet), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let data = read_net(&text).map_err(|e| format!("{}: {}", path, e))?;
+    build(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn read_net(text: &str) -> Result<NetData, String> {
+    let mut data = NetData::default();
+    let mut program: Option<String> = None;
+    for tag in Tags::new(text) {
+        let tag = tag?;
+        let Tag::Start { name, empty, .. } = &tag else {
+            if matches!(tag, Tag::End("tlLogic")) {
+                program = None;
+            }
+            continue;
+        };
+        let required = |key: &str| {
+            tag.attribute(key)
+                .map(str::to_string)
+                .ok_or(format!("<{}> without {}", name, key))
+        };
+        match *name {
+            "edge" if tag.attribute("function").is_none_or(|function| function == "normal") => {
+                data.edges.push(SumoEdge {
+                    id: required("id")?,
+                    from: required("from")?,
+                    to: required("to")?,
+                    priority: tag.attribute("priority").and_then(|p| p.parse().ok()).unwrap_or(0),
+                    lanes: 0,
+                });
+            }
+            "lane" => {
+                if let Some(edge) = data.edges.last_mut() {
+                    if tag.attribute("id").is_some_and(|id| id.starts_with(&format!("{}_", edge.id))) {
+                        edge.lanes += 1;
+                    }
+                }
+            }
+            "junction" => {
+                let coordinate = |key: &str| -> Result<f32, String> {
+                    let value = required(key)?;
+                    value.parse().map_err(|_| format!("Invalid {} '{}'", key, value))
+                };
+                let position = (coordinate("x")?, coordinate("y")?);
+                data.junctions.push((required("id")?, required("type")?, position));
+            }
+            "connection" => {
+                let tl = tag.attribute("tl").map(str::to_string);
+                data.connections.push((required("from")?, required("to")?, tl));
+            }
+            "tlLogic" if !*empty => {
+                let id = required("id")?;
+                // Only the first program of each signal counts
+                if !data.programs.contains_key(&id) {
+                    data.programs.insert(id.clone(), 0.0);
+                    program = Some(id);
+                }
+            }
+            "phase" => {
+                if let Some(id) = &program {
+                    let duration = required("duration")?;
+                    let duration: f32 = duration.parse().map_err(|_| format!("Invalid duration '{}'", duration))?;
+                    *data.programs.get_mut(id).expect("Program is read") += duration;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(data)
+}
+
+fn build(data: &NetData) -> Result<(RoadGraph, SumoNet), String> {
+    let edges_at = |id: &str| data.edges.iter().filter(|edge| edge.from == id || edge.to == id).count();
+    let (junction, kind, center) = data
+        .junctions
+        .iter()
+        .filter(|(_, kind, _)| kind != "internal" && kind != "dead_end")
+        .fold(None, |best: Option<&(String, String, (f32, f32))>, junction| match best {
+            Some(best) if edges_at(&best.0) >= edges_at(&junction.0) => Some(best),
+            _ => Some(junction),
+        })
+        .filter(|(id, _, _)| edges_at(id) > 0)
+        .ok_or("No junctions with edges found")?;
+
+    let control = match kind.as_str() {
+        "traffic_light" | "traffic_light_right_on_red" | "traffic_light_unregulated" => Control::Signals,
+        "allway_stop" => Control::Stop(StopControl::new(StopRule::AllWay)),
+        "priority" | "priority_stop" | "right_before_left" | "left_before_right" | "zipper" | "unregulated" => {
+            Control::Stop(StopControl::new(StopRule::TwoWay))
+        }
+        _ => return Err(format!("Junction {} has unsupported type '{}'", junction, kind)),
+    };
+
+    // One leg per neighbouring junction, in SUMO's coordinates with y
+    // pointing north
+    let position_of = |id: &str| data.junctions.iter().find(|(other, _, _)| other == id).map(|j| j.2);
+    let mut neighbours: Vec<(&str, Vec<&SumoEdge>)> = Vec::new();
+    for edge in &data.edges {
+        let neighbour = if edge.to == *junction {
+            edge.from.as_str()
+        } else if edge.from == *junction {
+            edge.to.as_str()
+        } else {
+            continue;
+        };
+        match neighbours.iter_mut().find(|(id, _)| *id == neighbour) {
+            Some((_, edges)) => edges.push(edge),
+            None => neighbours.push((neighbour, vec![edge])),
+        }
+    }
+    let mut legs: Vec<(Leg, Vec<&SumoEdge>)> = neighbours
+        .into_iter()
+        .map(|(neighbour, edges)| {
+            let (x, y) = position_of(neighbour).ok_or(format!("Unknown junction {}", neighbour))?;
+            let (dx, dy) = (x - center.0, center.1 - y);
+            let leg = Leg {
+                angle: dy.atan2(dx).rem_euclid(TAU),
+                length: distance((0.0, 0.0), (dx, dy)).clamp(MIN_LINK_LENGTH / 2.0, APPROACH_LENGTH),
+                entry: edges.iter().any(|edge| edge.to == *junction),
+                exit: edges.iter().any(|edge| edge.from == *junction),
+                priority: edges
+                    .iter()
+                    .map(|edge| (edge.priority.max(0) as u32) * 10 + edge.lanes.min(9))
+                    .max()
+                    .unwrap_or(0),
+            };
+            Ok((leg, edges))
+        })
+        .collect::<Result<_, String>>()?;
+    legs.sort_by(|a, b| a.0.angle.total_cmp(&b.0.angle));
+
+    let edges: HashMap<String, (usize, bool)> = legs
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (_, edges))| edges.iter().map(move |edge| (edge.id.clone(), (index, edge.to == *junction))))
+        .collect();
+    let mut geometry = JunctionGeometry::from_legs(legs.iter().map(|(leg, _)| *leg).collect())?;
+
+    // Only the turns the junction has connections for are allowed
+    let mut allowed = Vec::new();
+    let mut program = None;
+    for (from, to, tl) in &data.connections {
+        if let (Some(&(from, true)), Some(&(to, false))) = (edges.get(from), edges.get(to)) {
+            allowed.push(Movement { from, to });
+            program = program.or(tl.as_ref());
+        }
+    }
+    if !allowed.is_empty() {
+        geometry.banned = geometry.movements().filter(|movement| !allowed.contains(movement)).collect();
+    }
+
+    // Stop traffic driving in by a leg it has no turn to leave by
+    for leg in 0..geometry.legs.len() {
+        if geometry.legs[leg].entry && geometry.exits(leg).is_empty() {
+            geometry.legs[leg].entry = false;
+        }
+    }
+    let cycle = match control {
+        Control::Signals => data.programs.get(program.unwrap_or(junction)).copied(),
+        _ => None,
+    };
+
+    let graph = RoadGraph {
+        links: vec![vec![None; geometry.legs.len()]],
+        junctions: vec![JunctionSite {
+            center: (0.0, 0.0),
+            geometry,
+            control,
+        }],
+    };
+    let net = SumoNet {
+        junction: junction.clone(),
+        cycle,
+        edges,
+    };
+    Ok((graph, net))
+}
+
+// Vehicles read from a route file, as spawns taking the exit their route
+// leaves the junction by
+pub struct Demand {
+    pub departures: Vec<(u64, InputEvent)>, // Sorted by tick
+    pub skipped: usize,                     // Vehicles and flows whose route does not cross the junction
+}
+
+// Vehicle or flow being read, with its route so far
+struct Trip {
+    departures: Vec<f64>, // Seconds
+    route: Option<String>,
+    edges: Vec<String>,
+}
+
+impl SumoNet {
+    // Read the vehicles, trips and flows of a route file written for this
+    // network
+    //
+    // Each one crosses the junction by the first incoming edge of its route
+    // followed by an outgoing one. Flows with a `period`, `vehsPerHour` or
+    // `number` depart evenly spaced and flows with a `probability` once per
+    // second at random, drawn from a generator seeded with `seed`. Vehicle
+    // types are accepted, but every vehicle drives with this simulator's own
+    // size and speed.
+    pub fn import_routes(&self, path: &str, seed: u64) -> Result<Demand, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.read_routes(&text, seed).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn read_routes(&self, text: &str, seed: u64) -> Result<Demand, String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut routes: HashMap<String, Vec<String>> = HashMap::new();
+        let mut trips: Vec<Trip> = Vec::new();
+        let mut current: Option<Trip> = None;
+        for tag in Tags::new(text) {
+            let tag = tag?;
+            let (name, empty) = match &tag {
+                Tag::Start { name, empty, .. } => (*name, *empty),
+                Tag::End(name) => {
+                    if matches!(*name, "vehicle" | "trip" | "flow") {
+                        trips.extend(current.take());
+                    }
+                    continue;
+                }
+            };
+            let time = |key: &str| tag.attribute(key).map(parse_time).transpose();
+            let edge_list = |key: &str| -> Vec<String> {
+                tag.attribute(key)
+                    .map(|edges| edges.split_whitespace().map(str::to_string).collect())
+                    .unwrap_or_default()
+            };
+            match name {
+                "route" => match &mut current {
+                    Some(trip) => trip.edges = edge_list("edges"),
+                    None => {
+                        if let Some(id) = tag.attribute("id") {
+                            routes.insert(id.to_string(), edge_list("edges"));
+                        }
+                    }
+                },
+                "vehicle" | "trip" | "flow" => {
+                    let departures = if name == "flow" {
+                        flow_departures(&tag, &mut rng)?
+                    } else {
+                        vec![time("depart")?.ok_or(format!("<{}> without depart", name))?]
+                    };
+                    let mut edges = edge_list("from");
+                    edges.extend(edge_list("via"));
+                    edges.extend(edge_list("to"));
+                    let trip = Trip {
+                        departures,
+                        route: tag.attribute("route").map(str::to_string),
+                        edges,
+                    };
+                    if empty {
+                        trips.push(trip);
+                    } else {
+                        current = Some(trip);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut demand = Demand {
+            departures: Vec::new(),
+            skipped: 0,
+        };
+        for trip in trips {
+            let edges = match &trip.route {
+                Some(id) => routes.get(id).unwrap_or(&trip.edges),
+                None => &trip.edges,
+            };
+            let movement = edges.windows(2).find_map(|pair| match (self.edges.get(&pair[0]), self.edges.get(&pair[1])) {
+                (Some(&(from, true)), Some(&(to, false))) => Some(Movement { from, to }),
+                _ => None,
+            });
+            let Some(Movement { from, to }) = movement else {
+                demand.skipped += 1;
+                continue;
+            };
+            for seconds in trip.departures {
+                let tick = (seconds * TICKS_PER_SECOND as f64).round() as u64;
+                let spawn = InputEvent::SpawnTo {
+                    entry: 0,
+                    leg: from,
+                    exit: to,
+                };
+                demand.departures.push((tick, spawn));
+            }
+        }
+        demand.departures.sort_by_key(|&(tick, _)| tick);
+        Ok(demand)
+    }
+}
+
+// Departure times of a flow, in seconds
+fn flow_departures(tag: &Tag, rng: &mut StdRng) -> Result<Vec<f64>, String> {
+    let number = |key: &str| -> Result<Option<f64>, String> {
+        tag.attribute(key)
+            .map(|value| value.parse().map_err(|_| format!("Invalid {} '{}'", key, value)))
+            .transpose()
+    };
+    let begin = tag.attribute("begin").map(parse_time).transpose()?.unwrap_or(0.0);
+    let end = tag.attribute("end").map(parse_time).transpose()?;
+    let count = number("number")?.map(|count| count as usize);
+
+    if let Some(probability) = number("probability")? {
+        let end = end.unwrap_or(DEFAULT_FLOW_END);
+        let seconds = (end - begin).max(0.0) as usize;
+        let mut departures: Vec<f64> = (0..seconds)
+            .filter(|_| rng.gen_bool(probability.clamp(0.0, 1.0)))
+            .map(|second| begin + second as f64)
+            .collect();
+        departures.truncate(count.unwrap_or(usize::MAX));
+        return Ok(departures);
+    }
+    let period = match (number("period")?, number("vehsPerHour")?, count, end) {
+        (Some(period), _, _, _) => period,
+        (None, Some(rate), _, _) => 3600.0 / rate,
+        (None, None, Some(count), end) => (end.unwrap_or(DEFAULT_FLOW_END) - begin) / count.max(1) as f64,
+        _ => return Err("<flow> needs period, vehsPerHour, probability or number".to_string()),
+    };
+    if period <= 0.0 || !period.is_finite() {
+        return Err(format!("Invalid flow period {}", period));
+    }
+    let end = end.unwrap_or(if count.is_some() { f64::MAX } else { DEFAULT_FLOW_END });
+    Ok((0..count.unwrap_or(usize::MAX))
+        .map(|index| begin + index as f64 * period)
+        .take_while(|&time| time < end)
+        .collect())
+}
+
+// Time in seconds, or as `[[days:]hours:]minutes:seconds`
+fn parse_time(text: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid time '{}'", text);
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() > 4 {
+        return Err(invalid());
+    }
+    parts.iter().rev().zip([1.0, 60.0, 3600.0, 86_400.0]).try_fold(0.0, |total, (part, scale)| {
+        let value: f64 = part.trim().parse().map_err(|_| invalid())?;
+        Ok(total + value * scale)
+    })
+}
+
+// Vehicle as it entered the scene, for writing the demand back out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Departure {
+    pub id: u32,
+    pub tick: u64,
+    pub movement: Movement,
+}
+
+// Collects the vehicles of a run as they are spawned
+#[derive(Debug, Clone, Default)]
+pub struct DemandLog {
+    pub departures: Vec<Departure>,
+    next_id: u32, // Lowest id not seen yet
+}
+
+impl DemandLog {
+    // Note the vehicles spawned since the last call
+    pub fn record(&mut self, network: &Network) {
+        let mut spawned: Vec<Departure> = network
+            .vehicles()
+            .filter(|vehicle| vehicle.id >= self.next_id)
+            .map(|vehicle| Departure {
+                id: vehicle.id,
+                tick: network.tick,
+                movement: vehicle.path.movement,
+            })
+            .collect();
+        spawned.sort_by_key(|departure| departure.id);
+        self.departures.extend(spawned);
+        self.next_id = self.next_id.max(network.next_vehicle_id);
+    }
+
+    // Write the intersection as `<prefix>.net.xml` and the vehicles as
+    // `<prefix>.rou.xml`, in a form `import` and `import_routes` read back
+    pub fn export(&self, intersection: &Intersection, prefix: &str) -> io::Result<()> {
+        fs::write(format!("{}.net.xml", prefix), network_xml(intersection))?;
+        fs::write(format!("{}.rou.xml", prefix), self.routes_xml())
+    }
+
+    fn routes_xml(&self) -> String {
+        let mut contents = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<routes>\n");
+        contents.push_str(&format!(
+            "    <vType id=\"car\" length=\"{:.2}\" minGap=\"{:.2}\" maxSpeed=\"{:.2}\" sigma=\"0\"/>\n",
+            VEHICLE_LENGTH,
+            VEHICLE_SAFE_DISTANCE - VEHICLE_LENGTH,
+            VEHICLE_SPEED
+        ));
+        for departure in &self.departures {
+            contents.push_str(&format!(
+                "    <vehicle id=\"{}\" type=\"car\" depart=\"{:.2}\" departSpeed=\"max\">\n        <route edges=\"in{} out{}\"/>\n    </vehicle>\n",
+                departure.id,
+                departure.tick as f32 / TICKS_PER_SECOND as f32,
+                departure.movement.from,
+                departure.movement.to
+            ));
+        }
+        contents.push_str("</routes>\n");
+        contents
+    }
+}
+
+// SUMO network of a single intersection, without internal lanes
+//
+// Leg `n` becomes edges `in<n>` and `out<n>` to a dead end at its far end,
+// with one lane each running to the stop line. SUMO's y axis points north,
+// so y is flipped and everything shifted to positive coordinates. Signals
+// get a fixed-time program with the same phases, cycle and offset, in which
+// left turns that cross traffic with green at the same time give way.
+fn network_xml(intersection: &Intersection) -> String {
+    let geometry = &intersection.geometry;
+    let extent = intersection.approach_length;
+    let point = |(x, y): (f32, f32)| format!("{:.2},{:.2}", x + extent, extent - y);
+    let shape = |points: &[(f32, f32)]| points.iter().map(|&p| point(p)).collect::<Vec<_>>().join(" ");
+    let (junction_type, link_state) = match intersection.control {
+        Control::Signals => ("traffic_light", None),
+        Control::Stop(stop) if stop.rule == StopRule::AllWay => ("allway_stop", Some(("w", "w"))),
+        _ => ("priority_stop", Some(("M", "s"))),
+    };
+    let major = |leg: usize| match intersection.control {
+        Control::Stop(stop) => !stop.stops(leg, &intersection.major),
+        _ => true,
+    };
+
+    let links: Vec<Movement> = geometry.movements().collect();
+    let paths = geometry.paths();
+    let conflicts = |a: Movement, b: Movement| {
+        paths
+            .iter()
+            .any(|path| path.movement == a && path.conflicts.contains(&b))
+    };
+    // Minor legs give way to major ones, and left turns to conflicting
+    // traffic from the same class of leg that is not turning left
+    let yields = |a: Movement, b: Movement| {
+        conflicts(a, b)
+            && ((major(b.from) && !major(a.from))
+                || (major(a.from) == major(b.from)
+                    && geometry.turn(a) == Route::Left
+                    && geometry.turn(b) != Route::Left))
+    };
+    let bits = |test: &dyn Fn(Movement) -> bool| links.iter().rev().map(|&link| if test(link) { '1' } else { '0' }).collect::<String>();
+
+    let mut contents = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<net version=\"1.9\">\n");
+    contents.push_str(&format!(
+        "    <location netOffset=\"{:.2},{:.2}\" convBoundary=\"0.00,0.00,{:.2},{:.2}\" origBoundary=\"0.00,0.00,{:.2},{:.2}\" projParameter=\"!\"/>\n",
+        extent, extent, 2.0 * extent, 2.0 * extent, 2.0 * extent, 2.0 * extent
+    ));
+
+    for (index, leg) in geometry.legs.iter().enumerate() {
+        let stop = geometry.stop_line(index);
+        let priority = if major(index) { 2 } else { 1 };
+        let mut edge = |id: String, from: String, to: String, points: [(f32, f32); 2]| {
+            contents.push_str(&format!(
+                "    <edge id=\"{}\" from=\"{}\" to=\"{}\" priority=\"{}\">\n        <lane id=\"{}_0\" index=\"0\" speed=\"{:.2}\" length=\"{:.2}\" shape=\"{}\"/>\n    </edge>\n",
+                id,
+                from,
+                to,
+                priority,
+                id,
+                VEHICLE_SPEED,
+                leg.length - stop,
+                shape(&points)
+            ));
+        };
+        if leg.entry {
+            edge(
+                format!("in{}", index),
+                format!("end{}", index),
+                "center".to_string(),
+                [leg.lane_point(leg.length, true), leg.lane_point(stop, true)],
+            );
+        }
+        if leg.exit {
+            edge(
+                format!("out{}", index),
+                "center".to_string(),
+                format!("end{}", index),
+                [leg.lane_point(stop, false), leg.lane_point(leg.length, false)],
+            );
+        }
+    }
+
+    if intersection.control == Control::Signals {
+        contents.push_str(&format!(
+            "    <tlLogic id=\"center\" type=\"static\" programID=\"0\" offset=\"{:.2}\">\n",
+            intersection.offset_ticks as f32 / TICKS_PER_SECOND as f32
+        ));
+        let green = intersection.green_ticks();
+        for (index, phase) in intersection.phases.iter().enumerate() {
+            let duration = if index + 1 == intersection.phases.len() {
+                intersection.cycle_ticks - index as u64 * green
+            } else {
+                green
+            };
+            let state: String = links
+                .iter()
+                .map(|&link| {
+                    if !phase.contains(&link.from) {
+                        'r'
+                    } else if links.iter().any(|&other| phase.contains(&other.from) && yields(link, other)) {
+                        'g'
+                    } else {
+                        'G'
+                    }
+                })
+                .collect();
+            contents.push_str(&format!(
+                "        <phase duration=\"{:.2}\" state=\"{}\"/>\n",
+                duration as f32 / TICKS_PER_SECOND as f32,
+                state
+            ));
+        }
+        contents.push_str("    </tlLogic>\n");
+    }
+
+    let incoming: Vec<String> = (0..geometry.legs.len())
+        .filter(|&leg| geometry.legs[leg].entry)
+        .map(|leg| format!("in{}_0", leg))
+        .collect();
+    let mut outline: Vec<(f32, f32)> = geometry
+        .legs
+        .iter()
+        .enumerate()
+        .flat_map(|(index, leg)| {
+            let stop = geometry.stop_line(index);
+            [leg.point(stop, -ROAD_WIDTH / 2.0), leg.point(stop, ROAD_WIDTH / 2.0)]
+        })
+        .collect();
+    outline.dedup_by(|a, b| distance(*a, *b) < 0.01);
+    contents.push_str(&format!(
+        "    <junction id=\"center\" type=\"{}\" x=\"{:.2}\" y=\"{:.2}\" incLanes=\"{}\" intLanes=\"\" shape=\"{}\">\n",
+        junction_type,
+        extent,
+        extent,
+        incoming.join(" "),
+        shape(&outline)
+    ));
+    for (index, &link) in links.iter().enumerate() {
+        contents.push_str(&format!(
+            "        <request index=\"{}\" response=\"{}\" foes=\"{}\" cont=\"0\"/>\n",
+            index,
+            bits(&|other| yields(link, other)),
+            bits(&|other| conflicts(link, other))
+        ));
+    }
+    contents.push_str("    </junction>\n");
+    for (index, leg) in geometry.legs.iter().enumerate() {
+        let (x, y) = leg.point(leg.length, 0.0);
+        let incoming = if leg.exit { format!("out{}_0", index) } else { String::new() };
+        contents.push_str(&format!(
+            "    <junction id=\"end{}\" type=\"dead_end\" x=\"{:.2}\" y=\"{:.2}\" incLanes=\"{}\" intLanes=\"\" shape=\"\"/>\n",
+            index,
+            x + extent,
+            extent - y,
+            incoming
+        ));
+    }
+
+    for (index, &link) in links.iter().enumerate() {
+        let direction = match geometry.turn(link) {
+            Route::Left => "l",
+            Route::Straight => "s",
+            Route::Right => "r",
+        };
+        // Major links that give way to another major link are minor ones in SUMO
+        let control = match link_state {
+            None => format!("tl=\"center\" linkIndex=\"{}\" state=\"o\"", index),
+            Some((_, minor_state)) if !major(link.from) => format!("state=\"{}\"", minor_state),
+            Some(("M", _)) if links.iter().any(|&other| yields(link, other)) => "state=\"m\"".to_string(),
+            Some((major_state, _)) => format!("state=\"{}\"", major_state),
+        };
+        contents.push_str(&format!(
+            "    <connection from=\"in{}\" to=\"out{}\" fromLane=\"0\" toLane=\"0\" dir=\"{}\" {}/>\n",
+            link.from,
+            link.to,
+            direction,
+            control
+        ));
+    }
+    contents.push_str("</net>\n");
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::network::NetworkLayout;
+
+    // Signalised tee: legs to the east (0), south (1) and north (2) of the
+    // center, in SUMO's coordinates with y pointing north
+    fn tee(connections: &[(&str, &str)]) -> String {
+        let mut text = String::from(
+            r#"<net>
+    <edge id=":C_0" function="internal"/>
+    <edge id="eC" from="E" to="C" priority="1"><lane id="eC_0"/></edge>
+    <edge id="Ce" from="C" to="E" priority="1"><lane id="Ce_0"/></edge>
+    <edge id="sC" from="S" to="C" priority="2"><lane id="sC_0"/><lane id="sC_1"/></edge>
+    <edge id="Cs" from="C" to="S" priority="2"><lane id="Cs_0"/></edge>
+    <edge id="nC" from="N" to="C" priority="2"><lane id="nC_0"/></edge>
+    <edge id="Cn" from="C" to="N" priority="2"><lane id="Cn_0"/></edge>
+    <tlLogic id="C" type="static" programID="0" offset="0">
+        <phase duration="20" state="GGr"/>
+        <phase duration="25" state="rrG"/>
+    </tlLogic>
+    <junction id="C" type="traffic_light" x="0" y="0"/>
+    <junction id="E" type="dead_end" x="100" y="0"/>
+    <junction id="S" type="dead_end" x="0" y="-100"/>
+    <junction id="N" type="dead_end" x="0" y="100"/>
+"#,
+        );
+        for (from, to) in connections {
+            text.push_str(&format!("    <connection from=\"{}\" to=\"{}\" tl=\"C\"/>\n", from, to));
+        }
+        text.push_str("</net>\n");
+        text
+    }
+
+    fn flow(attributes: &str) -> Result<Vec<f64>, String> {
+        let text = format!("<flow id=\"f\" {}/>", attributes);
+        let tag = Tags::new(&text).next().unwrap()?;
+        flow_departures(&tag, &mut StdRng::seed_from_u64(7))
+    }
+
+    #[test]
+    fn times_read_as_seconds() {
+        assert_eq!(parse_time("1:02:03"), Ok(3723.0));
+        assert_eq!(parse_time("1:00:00:10"), Ok(86_410.0));
+        assert_eq!(parse_time("12.5"), Ok(12.5));
+        assert!(parse_time("soon").is_err());
+        assert!(parse_time("1::3").is_err());
+        assert!(parse_time("1:2:3:4:5").is_err());
+    }
+
+    #[test]
+    fn flows_depart_evenly_or_at_random() {
+        assert_eq!(flow(r#"begin="5" end="40" period="10""#), Ok(vec![5.0, 15.0, 25.0, 35.0]));
+        assert_eq!(flow(r#"end="20" vehsPerHour="720""#), Ok(vec![0.0, 5.0, 10.0, 15.0]));
+        assert_eq!(flow(r#"begin="0" end="100" number="4""#), Ok(vec![0.0, 25.0, 50.0, 75.0]));
+        assert!(flow(r#"end="100""#).is_err());
+        assert!(flow(r#"period="0""#).is_err());
+
+        let random = flow(r#"end="100" probability="0.5""#).unwrap();
+        assert_eq!(flow(r#"end="100" probability="0.5""#).unwrap(), random);
+        assert!((30..=70).contains(&random.len()), "{} departures", random.len());
+        assert!(random.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(random.iter().all(|&time| time.fract() == 0.0 && (0.0..100.0).contains(&time)));
+        assert_eq!(flow(r#"end="100" probability="1" number="3""#), Ok(vec![0.0, 1.0, 2.0]));
+    }
+
+    #[test]
+    fn builds_the_busiest_junction_with_its_turns() {
+        let all = [("eC", "Cs"), ("eC", "Cn"), ("sC", "Cn"), ("sC", "Ce"), ("nC", "Cs"), ("nC", "Ce")];
+        let (graph, net) = build(&read_net(&tee(&all)).unwrap()).unwrap();
+        assert_eq!(net.junction, "C");
+        assert_eq!(net.cycle, Some(45.0));
+        let site = &graph.junctions[0];
+        assert_eq!(site.control, Control::Signals);
+        let degrees: Vec<f32> = site.geometry.legs.iter().map(|leg| leg.angle.to_degrees().round()).collect();
+        assert_eq!(degrees, vec![0.0, 90.0, 270.0]);
+        assert!(site.geometry.legs.iter().all(|leg| leg.entry && leg.exit));
+        assert!(site.geometry.legs[1].priority > site.geometry.legs[0].priority);
+        assert!(site.geometry.banned.is_empty());
+
+        // Without the connections for them, turns are banned
+        let (graph, _) = build(&read_net(&tee(&all[..4])).unwrap()).unwrap();
+        let geometry = &graph.junctions[0].geometry;
+        assert_eq!(geometry.banned, vec![Movement { from: 2, to: 0 }, Movement { from: 2, to: 1 }]);
+        assert!(!geometry.legs[2].entry);
+        assert!(geometry.legs[2].exit);
+    }
+
+    #[test]
+    fn legs_without_turns_take_no_traffic() {
+        let (graph, _) = build(&read_net(&tee(&[("sC", "Cn"), ("nC", "Cs")])).unwrap()).unwrap();
+        let geometry = &graph.junctions[0].geometry;
+        assert!(!geometry.legs[0].entry);
+        assert_eq!(geometry.exits(1), vec![2]);
+        assert_eq!(geometry.exits(2), vec![1]);
+
+        let mut network = Network::from_graph(1, NetworkLayout::default(), graph);
+        for _ in 0..2 * TICKS_PER_SECOND {
+            network.update();
+        }
+        network.spawn_at(0, 0, None);
+        assert_eq!(network.vehicles().count(), 0);
+        network.spawn_at(0, 1, None);
+        assert_eq!(network.vehicles().count(), 1);
+    }
+
+    #[test]
+    fn routes_become_spawns_across_the_junction() {
+        let (_, net) = build(&read_net(&tee(&[("sC", "Cn"), ("nC", "Cs"), ("eC", "Cs")])).unwrap()).unwrap();
+        let routes = r#"<routes>
+    <route id="north" edges="sC Cn"/>
+    <vehicle id="a" depart="2" route="north"/>
+    <trip id="b" depart="1" from="eC" to="Cs"/>
+    <vehicle id="c" depart="3"><route edges="Cn"/></vehicle>
+    <flow id="d" begin="4" end="10" period="5" from="nC" to="Cs"/>
+</routes>"#;
+        let demand = net.read_routes(routes, 1).unwrap();
+        let spawn = |leg, exit| InputEvent::SpawnTo { entry: 0, leg, exit };
+        assert_eq!(
+            demand.departures,
+            vec![(60, spawn(0, 1)), (120, spawn(1, 2)), (240, spawn(2, 1)), (540, spawn(2, 1))]
+        );
+        assert_eq!(demand.skipped, 1);
+    }
+
+    #[test]
+    fn export_reads_back_as_the_same_junction_and_demand() {
+        let mut intersection = Intersection::new(1);
+        intersection.set_signal_timing(40 * TICKS_PER_SECOND, 0);
+        let log = DemandLog {
+            departures: vec![Departure {
+                id: 1,
+                tick: 90,
+                movement: Movement { from: 3, to: 0 },
+            }],
+            next_id: 2,
+        };
+
+        let (graph, net) = build(&read_net(&network_xml(&intersection)).unwrap()).unwrap();
+        assert_eq!(net.cycle, Some(40.0));
+        let site = &graph.junctions[0];
+        assert_eq!(site.control, Control::Signals);
+        assert_eq!(site.geometry.legs.len(), intersection.geometry.legs.len());
+        for (imported, original) in site.geometry.legs.iter().zip(&intersection.geometry.legs) {
+            assert!((imported.angle - original.angle).abs() < 1e-3);
+            assert_eq!((imported.entry, imported.exit), (original.entry, original.exit));
+        }
+        assert!(site.geometry.banned.is_empty());
+
+        let demand = net.read_routes(&log.routes_xml(), 1).unwrap();
+        assert_eq!(demand.departures, vec![(90, InputEvent::SpawnTo { entry: 0, leg: 3, exit: 0 })]);
+    }
+}
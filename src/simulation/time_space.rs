@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use crate::constants::{ROAD_WIDTH, TICKS_PER_SECOND, TIME_SPACE_SAMPLE_TICKS};
+use crate::simulation::coordination::Corridor;
+use crate::simulation::network::Network;
+
+// Size of the plot area in the written SVG, in pixels
+const PLOT_WIDTH: f32 = 1000.0;
+const PLOT_HEIGHT: f32 = 500.0;
+const MARGIN: f32 = 60.0;
+
+const OUTBOUND_COLOR: &str = "#0060c0";
+const INBOUND_COLOR: &str = "#e08000";
+const GREEN_COLOR: &str = "#20a020";
+const RED_COLOR: &str = "#d02020";
+
+// Vehicle trajectories along a corridor against its signal states, sampled
+// while the simulation runs and written out as an SVG time-space diagram
+//
+// Time runs to the right and distance along the corridor upwards. Each
+// signal is a bar at its intersection, green while the corridor's phase has
+// green. Vehicles driving away from the first intersection are drawn in
+// blue, vehicles driving towards it in orange.
+pub struct TimeSpaceDiagram {
+    corridor: Corridor,
+    ticks: Vec<u64>,
+    signals: Vec<Vec<bool>>, // Per sample, whether each corridor intersection shows green
+    trajectories: BTreeMap<u32, Vec<(u64, f32, bool)>>, // Per vehicle: tick, distance, outbound
+}
+
+impl TimeSpaceDiagram {
+    pub fn new(corridor: Corridor) -> Self {
+        TimeSpaceDiagram {
+            corridor,
+            ticks: Vec::new(),
+            signals: Vec::new(),
+            trajectories: BTreeMap::new(),
+        }
+    }
+
+    // Take a sample if one is due on the network's current tick
+    pub fn record(&mut self, network: &Network) {
+        if !network.tick.is_multiple_of(TIME_SPACE_SAMPLE_TICKS) {
+            return;
+        }
+
+        let outbound = self.corridor.outbound();
+        self.ticks.push(network.tick);
+        self.signals.push(
+            self.corridor
+                .intersections
+                .iter()
+                .map(|&index| network.intersections[index].is_green_for(outbound))
+                .collect(),
+        );
+
+        for vehicle in network.vehicles() {
+            let (distance, across) = self.corridor.along(vehicle.position);
+            let direction = vehicle.direction();
+            if self.corridor.serves(direction) && across.abs() < ROAD_WIDTH / 2.0 {
+                self.trajectories.entry(vehicle.id).or_default().push((
+                    network.tick,
+                    distance,
+                    direction == outbound,
+                ));
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_svg())
+    }
+
+    fn to_svg(&self) -> String {
+        let first_tick = self.ticks.first().copied().unwrap_or(0);
+        let last_tick = self.ticks.last().copied().unwrap_or(0).max(first_tick + 1);
+        let half_link = self.corridor.positions.get(1).copied().unwrap_or(0.0) / 2.0;
+        let lowest = -half_link.max(ROAD_WIDTH);
+        let highest = self.corridor.positions.last().copied().unwrap_or(0.0) - lowest;
+
+        let x_of = |tick: u64| MARGIN + (tick - first_tick) as f32 / (last_tick - first_tick) as f32 * PLOT_WIDTH;
+        let y_of = |distance: f32| MARGIN + (highest - distance) / (highest - lowest) * PLOT_HEIGHT;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">\n",
+            PLOT_WIDTH + 2.0 * MARGIN,
+            PLOT_HEIGHT + 2.0 * MARGIN
+        );
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"white\" stroke=\"black\"/>\n",
+            MARGIN, MARGIN, PLOT_WIDTH, PLOT_HEIGHT
+        ));
+
+        // Time axis, labelled at a round number of seconds
+        let duration = (last_tick - first_tick) / TICKS_PER_SECOND;
+        let label_step = [1, 2, 5, 10, 20, 30, 60, 120, 300, 600, 1200, 3600]
+            .into_iter()
+            .find(|step| duration / step <= 12)
+            .unwrap_or(7200);
+        let first_label = first_tick.div_ceil(TICKS_PER_SECOND).div_ceil(label_step) * label_step;
+        for second in (first_label..=last_tick / TICKS_PER_SECOND).step_by(label_step as usize) {
+            let x = x_of(second * TICKS_PER_SECOND);
+            svg.push_str(&format!(
+                "<line x1=\"{x:.1}\" y1=\"{}\" x2=\"{x:.1}\" y2=\"{}\" stroke=\"#dddddd\"/>\n",
+                MARGIN,
+                MARGIN + PLOT_HEIGHT
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{x:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                MARGIN + PLOT_HEIGHT + 16.0,
+                second
+            ));
+        }
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">Time (s)</text>\n",
+            MARGIN + PLOT_WIDTH / 2.0,
+            MARGIN + PLOT_HEIGHT + 36.0
+        ));
+        svg.push_str(&format!(
+            "<text x=\"16\" y=\"{}\" text-anchor=\"middle\" transform=\"rotate(-90 16 {})\">Distance (m)</text>\n",
+            MARGIN + PLOT_HEIGHT / 2.0,
+            MARGIN + PLOT_HEIGHT / 2.0
+        ));
+
+        // Signal bars, one segment per run of the same state
+        for (column, (&index, &position)) in self
+            .corridor
+            .intersections
+            .iter()
+            .zip(&self.corridor.positions)
+            .enumerate()
+        {
+            let y = y_of(position);
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">#{} {:.0}</text>\n",
+                MARGIN - 6.0,
+                y + 4.0,
+                index,
+                position
+            ));
+
+            let mut start = 0;
+            for sample in 1..=self.ticks.len() {
+                let state = self.signals[start][column];
+                if sample < self.ticks.len() && self.signals[sample][column] == state {
+                    continue;
+                }
+                let end_tick = self.ticks.get(sample).copied().unwrap_or(last_tick);
+                svg.push_str(&format!(
+                    "<line x1=\"{:.1}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"{}\" stroke-width=\"4\"/>\n",
+                    x_of(self.ticks[start]),
+                    x_of(end_tick),
+                    if state { GREEN_COLOR } else { RED_COLOR }
+                ));
+                start = sample;
+            }
+        }
+
+        // Trajectories, broken wherever a vehicle left the corridor for a while
+        for samples in self.trajectories.values() {
+            let mut points = Vec::new();
+            for (index, &(tick, distance, outbound)) in samples.iter().enumerate() {
+                points.push(format!("{:.1},{:.1}", x_of(tick), y_of(distance)));
+                let next = samples.get(index + 1);
+                let continues = next.is_some_and(|&(next_tick, _, next_outbound)| {
+                    next_tick == tick + TIME_SPACE_SAMPLE_TICKS && next_outbound == outbound
+                });
+                if !continues {
+                    svg.push_str(&format!(
+                        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1\"/>\n",
+                        points.join(" "),
+                        if outbound { OUTBOUND_COLOR } else { INBOUND_COLOR }
+                    ));
+                    points.clear();
+                }
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
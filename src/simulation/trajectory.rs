@@ -0,0 +1,430 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::constants::TICKS_PER_SECOND;
+use crate::models::vehicle::{StopReason, Vehicle};
+use crate::simulation::network::Network;
+
+// First bytes of a columnar trajectory file, ending in the format version
+const MAGIC: &[u8; 8] = b"RITRAJ01";
+
+// Rows held back before a block of the columnar format is written
+const BLOCK_ROWS: usize = 65536;
+
+// Stretch of its path a vehicle is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Approach, // On the entry leg, before the stop line
+    Junction, // Between the stop lines
+    Exit,     // On the exit leg
+}
+
+impl Segment {
+    fn of(vehicle: &Vehicle) -> Self {
+        if vehicle.is_approaching() {
+            Segment::Approach
+        } else if vehicle.is_in_junction() {
+            Segment::Junction
+        } else {
+            Segment::Exit
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Segment::Approach => "approach",
+            Segment::Junction => "junction",
+            Segment::Exit => "exit",
+        }
+    }
+}
+
+// How trajectories are written, told apart by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    Csv,      // .csv
+    Columnar, // .traj
+}
+
+impl TrajectoryFormat {
+    pub fn of(path: &str) -> Result<Self, String> {
+        match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Ok(TrajectoryFormat::Csv),
+            Some("traj") => Ok(TrajectoryFormat::Columnar),
+            _ => Err(format!("Expected a .csv or .traj trajectory file, got '{}'", path)),
+        }
+    }
+}
+
+// Code of a vehicle's state in the columnar format: 0 while moving,
+// otherwise why it is stopped
+fn state_code(reason: Option<StopReason>) -> u8 {
+    match reason {
+        None => 0,
+        Some(StopReason::RedLight) => 1,
+        Some(StopReason::Leader(_)) => 2,
+        Some(StopReason::Conflict) => 3,
+        Some(StopReason::Spillback) => 4,
+        Some(StopReason::Yield) => 5,
+        Some(StopReason::StopSign) => 6,
+        Some(StopReason::Reacting) => 7,
+    }
+}
+
+// Stop reason coded by `state_code`, if any; the leader's id is not kept
+fn stop_reason_of(code: u8) -> Result<Option<StopReason>, String> {
+    Ok(match code {
+        0 => None,
+        1 => Some(StopReason::RedLight),
+        2 => Some(StopReason::Leader(0)),
+        3 => Some(StopReason::Conflict),
+        4 => Some(StopReason::Spillback),
+        5 => Some(StopReason::Yield),
+        6 => Some(StopReason::StopSign),
+        7 => Some(StopReason::Reacting),
+        _ => return Err(format!("unknown state {}", code)),
+    })
+}
+
+// One vehicle at one sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryRow {
+    pub tick: u32,
+    pub id: u32,
+    pub intersection: u16,
+    pub x: f32,
+    pub y: f32,
+    pub speed: f32,
+    pub heading: f32,
+    pub from: u8,
+    pub to: u8,
+    pub segment: Segment,
+    pub state: Option<StopReason>,
+}
+
+impl TrajectoryRow {
+    fn of(tick: u64, intersection: usize, vehicle: &Vehicle) -> Self {
+        TrajectoryRow {
+            tick: tick as u32,
+            id: vehicle.id,
+            intersection: intersection as u16,
+            x: vehicle.position.0,
+            y: vehicle.position.1,
+            speed: vehicle.speed(),
+            heading: vehicle.heading,
+            from: vehicle.path.movement.from as u8,
+            to: vehicle.path.movement.to as u8,
+            segment: Segment::of(vehicle),
+            state: vehicle.stop_reason,
+        }
+    }
+
+    fn to_csv(self) -> String {
+        format!(
+            "{},{:.3},{},{},{:.3},{:.3},{:.2},{:.4},{},{},{},{}",
+            self.tick,
+            self.tick as f32 / TICKS_PER_SECOND as f32,
+            self.id,
+            self.intersection,
+            self.x,
+            self.y,
+            self.speed,
+            self.heading,
+            self.from,
+            self.to,
+            self.segment.name(),
+            self.state.map_or("moving", StopReason::name)
+        )
+    }
+}
+
+// Samples not yet written to a columnar file, one list per column
+#[derive(Default)]
+struct Block {
+    tick: Vec<u32>,
+    id: Vec<u32>,
+    intersection: Vec<u16>,
+    x: Vec<f32>,
+    y: Vec<f32>,
+    speed: Vec<f32>,
+    heading: Vec<f32>,
+    from: Vec<u8>,
+    to: Vec<u8>,
+    segment: Vec<u8>,
+    state: Vec<u8>,
+}
+
+impl Block {
+    fn push(&mut self, row: TrajectoryRow) {
+        self.tick.push(row.tick);
+        self.id.push(row.id);
+        self.intersection.push(row.intersection);
+        self.x.push(row.x);
+        self.y.push(row.y);
+        self.speed.push(row.speed);
+        self.heading.push(row.heading);
+        self.from.push(row.from);
+        self.to.push(row.to);
+        self.segment.push(row.segment as u8);
+        self.state.push(state_code(row.state));
+    }
+}
+
+// Position, speed, heading, lane and state of every vehicle, written to a
+// file every `every` ticks while the simulation runs
+//
+// A vehicle's lane is its movement through the intersection it is driving
+// through, entry leg to exit leg, and the segment of it the vehicle is on.
+// CSV files get one row per vehicle and sample. Columnar files start with
+// `MAGIC`, then the ticks per second and the sample interval as u32, then
+// blocks of up to `BLOCK_ROWS` rows: the row count as u32 followed by each
+// column in turn, all little-endian: tick u32, id u32, intersection u16,
+// x, y, speed and heading f32, from, to, segment and state u8. Segments are
+// coded 0 approach, 1 junction, 2 exit; states as in `state_code`.
+// `Trajectories::read` reads columnar files back.
+pub struct TrajectoryWriter {
+    pub every: u64,
+    format: TrajectoryFormat,
+    out: BufWriter<File>,
+    block: Block,
+    rows: usize,
+}
+
+impl TrajectoryWriter {
+    pub fn create(path: &str, every: u64) -> Result<Self, String> {
+        let format = TrajectoryFormat::of(path)?;
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let mut writer = TrajectoryWriter {
+            every: every.max(1),
+            format,
+            out: BufWriter::new(file),
+            block: Block::default(),
+            rows: 0,
+        };
+        writer
+            .write_header()
+            .map_err(|e| format!("Failed to write to {}: {}", path, e))?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        match self.format {
+            TrajectoryFormat::Csv => {
+                writeln!(self.out, "tick,time,id,intersection,x,y,speed,heading,from,to,segment,state")
+            }
+            TrajectoryFormat::Columnar => {
+                self.out.write_all(MAGIC)?;
+                self.out.write_all(&(TICKS_PER_SECOND as u32).to_le_bytes())?;
+                self.out.write_all(&(self.every as u32).to_le_bytes())
+            }
+        }
+    }
+
+    // Write a sample of every vehicle if one is due on the network's current tick
+    pub fn record(&mut self, network: &Network) -> io::Result<()> {
+        if !network.tick.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        let tick = network.tick;
+        for (index, intersection) in network.intersections.iter().enumerate() {
+            for vehicle in &intersection.vehicles {
+                let row = TrajectoryRow::of(tick, index, vehicle);
+                match self.format {
+                    TrajectoryFormat::Csv => writeln!(self.out, "{}", row.to_csv())?,
+                    TrajectoryFormat::Columnar => {
+                        self.block.push(row);
+                        self.rows += 1;
+                        if self.rows == BLOCK_ROWS {
+                            self.write_block()?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        let block = std::mem::take(&mut self.block);
+        let out = &mut self.out;
+        out.write_all(&(self.rows as u32).to_le_bytes())?;
+        for value in block.tick.iter().chain(&block.id) {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        for value in &block.intersection {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        for value in block.x.iter().chain(&block.y).chain(&block.speed).chain(&block.heading) {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.write_all(&block.from)?;
+        out.write_all(&block.to)?;
+        out.write_all(&block.segment)?;
+        out.write_all(&block.state)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    // Write the samples still held back and flush the file
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.rows > 0 {
+            self.write_block()?;
+        }
+        self.out.flush()
+    }
+}
+
+// Contents of a columnar trajectory file, for offline tools
+pub struct Trajectories {
+    pub ticks_per_second: u32,
+    pub every: u32, // Ticks between samples
+    pub rows: Vec<TrajectoryRow>,
+}
+
+impl Trajectories {
+    pub fn read(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        Self::decode(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = Cursor {
+            bytes: bytes
+                .strip_prefix(MAGIC.as_slice())
+                .ok_or("not a columnar trajectory file, or another version")?,
+        };
+        let ticks_per_second = cursor.u32s(1)?[0];
+        let every = cursor.u32s(1)?[0];
+
+        let mut rows = Vec::new();
+        while !cursor.bytes.is_empty() {
+            let count = cursor.u32s(1)?[0] as usize;
+            let (tick, id) = (cursor.u32s(count)?, cursor.u32s(count)?);
+            let intersection = cursor.u16s(count)?;
+            let (x, y) = (cursor.f32s(count)?, cursor.f32s(count)?);
+            let (speed, heading) = (cursor.f32s(count)?, cursor.f32s(count)?);
+            let (from, to) = (cursor.take(count)?, cursor.take(count)?);
+            let (segment, state) = (cursor.take(count)?, cursor.take(count)?);
+            for row in 0..count {
+                rows.push(TrajectoryRow {
+                    tick: tick[row],
+                    id: id[row],
+                    intersection: intersection[row],
+                    x: x[row],
+                    y: y[row],
+                    speed: speed[row],
+                    heading: heading[row],
+                    from: from[row],
+                    to: to[row],
+                    segment: match segment[row] {
+                        0 => Segment::Approach,
+                        1 => Segment::Junction,
+                        2 => Segment::Exit,
+                        code => return Err(format!("unknown segment {}", code)),
+                    },
+                    state: stop_reason_of(state[row])?,
+                });
+            }
+        }
+        Ok(Trajectories {
+            ticks_per_second,
+            every,
+            rows,
+        })
+    }
+}
+
+// Little-endian values read off the front of a columnar file
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < count {
+            return Err("file ends in the middle of a block".to_string());
+        }
+        let (head, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u32s(&mut self, count: usize) -> Result<Vec<u32>, String> {
+        let bytes = self.take(count * 4)?;
+        Ok(bytes.chunks_exact(4).map(|value| u32::from_le_bytes(value.try_into().expect("4 bytes"))).collect())
+    }
+
+    fn u16s(&mut self, count: usize) -> Result<Vec<u16>, String> {
+        let bytes = self.take(count * 2)?;
+        Ok(bytes.chunks_exact(2).map(|value| u16::from_le_bytes(value.try_into().expect("2 bytes"))).collect())
+    }
+
+    fn f32s(&mut self, count: usize) -> Result<Vec<f32>, String> {
+        let bytes = self.take(count * 4)?;
+        Ok(bytes.chunks_exact(4).map(|value| f32::from_le_bytes(value.try_into().expect("4 bytes"))).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::direction::Direction;
+    use crate::simulation::network::NetworkLayout;
+    use crate::simulation::replay::InputEvent;
+
+    #[test]
+    fn columnar_file_reads_back_as_the_csv_rows() {
+        let directory = std::env::temp_dir();
+        let path = |extension: &str| {
+            let name = format!("road_intersection_{}.{}", std::process::id(), extension);
+            directory.join(name).to_str().unwrap().to_string()
+        };
+        let (csv, columnar) = (path("csv"), path("traj"));
+
+        let mut network = Network::new(5, NetworkLayout::default());
+        let mut writers = [
+            TrajectoryWriter::create(&csv, 6).unwrap(),
+            TrajectoryWriter::create(&columnar, 6).unwrap(),
+        ];
+        for tick in 0..1800 {
+            if tick % 45 == 0 {
+                let direction = [Direction::North, Direction::East, Direction::South, Direction::West][tick / 45 % 4];
+                network.apply_input(InputEvent::Spawn { direction, route: None, entry: None });
+            }
+            network.update();
+            for writer in &mut writers {
+                writer.record(&network).unwrap();
+            }
+        }
+        for writer in writers {
+            writer.finish().unwrap();
+        }
+
+        let lines = fs::read_to_string(&csv);
+        let trajectories = Trajectories::read(&columnar);
+        fs::remove_file(&csv).unwrap();
+        fs::remove_file(&columnar).unwrap();
+        let lines = lines.unwrap();
+        let trajectories = trajectories.unwrap();
+
+        assert_eq!((trajectories.ticks_per_second, trajectories.every), (TICKS_PER_SECOND as u32, 6));
+        let lines: Vec<&str> = lines.lines().skip(1).collect();
+        assert!(lines.len() > 100);
+        assert_eq!(trajectories.rows.len(), lines.len());
+        for (row, line) in trajectories.rows.iter().zip(lines) {
+            assert_eq!(row.to_csv(), line);
+        }
+        assert!(trajectories.rows.iter().any(|row| row.state.is_none()));
+        assert!(trajectories.rows.iter().any(|row| row.state.is_some()));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(Trajectories::decode(b"RITRAJ00\x3c\0\0\0\x06\0\0\0").is_err());
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([60, 0, 0, 0, 6, 0, 0, 0, 2, 0, 0, 0, 1]);
+        assert!(Trajectories::decode(&bytes).is_err());
+        assert!(Trajectories::decode(&bytes[..16]).unwrap().rows.is_empty());
+    }
+}
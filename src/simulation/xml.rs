@@ -0,0 +1,139 @@
+// Start or end of an XML element, with the attributes of a start
+pub enum Tag<'a> {
+    Start {
+        name: &'a str,
+        attributes: Vec<(&'a str, String)>,
+        empty: bool, // Written as `<name ... />`, so no end follows
+    },
+    End(&'a str),
+}
+
+impl Tag<'_> {
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        match self {
+            Tag::Start { attributes, .. } => attributes
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.as_str()),
+            Tag::End(_) => None,
+        }
+    }
+}
+
+// The tags of an XML document in order, skipping the declaration, comments
+// and text between elements, which neither OSM nor SUMO files use
+pub struct Tags<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tags<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Tags { rest: text }
+    }
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Result<Tag<'a>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.rest.find('<')?;
+            self.rest = &self.rest[start + 1..];
+            if let Some(comment) = self.rest.strip_prefix("!--") {
+                let Some(end) = comment.find("-->") else {
+                    return Some(Err("Unterminated comment".to_string()));
+                };
+                self.rest = &comment[end + 3..];
+                continue;
+            }
+
+            // Attribute values may contain '>', so only a '>' outside quotes ends the tag
+            let mut quote = None;
+            let Some(end) = self.rest.char_indices().find_map(|(index, c)| {
+                match (quote, c) {
+                    (None, '"' | '\'') => quote = Some(c),
+                    (Some(open), _) if c == open => quote = None,
+                    (None, '>') => return Some(index),
+                    _ => {}
+                }
+                None
+            }) else {
+                return Some(Err("Unterminated tag".to_string()));
+            };
+            let inner = &self.rest[..end];
+            self.rest = &self.rest[end + 1..];
+
+            if inner.starts_with('?') || inner.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = inner.strip_prefix('/') {
+                return Some(Ok(Tag::End(name.trim())));
+            }
+            let (inner, empty) = match inner.strip_suffix('/') {
+                Some(inner) => (inner, true),
+                None => (inner, false),
+            };
+            let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+            return Some(parse_attributes(&inner[name_end..]).map(|attributes| Tag::Start {
+                name: &inner[..name_end],
+                attributes,
+                empty,
+            }));
+        }
+    }
+}
+
+fn parse_attributes(mut text: &str) -> Result<Vec<(&str, String)>, String> {
+    let mut attributes = Vec::new();
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Ok(attributes);
+        }
+        let invalid = || format!("Invalid attributes '{}'", text);
+        let (name, rest) = text.split_once('=').ok_or_else(invalid)?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'').ok_or_else(invalid)?;
+        let end = rest[1..].find(quote).ok_or_else(invalid)?;
+        attributes.push((name.trim(), unescape(&rest[1..end + 1])));
+        text = &rest[end + 2..];
+    }
+}
+
+// Replace XML character references and the predefined entities
+pub fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let character = match &rest[1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|decimal| decimal.parse()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                result.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
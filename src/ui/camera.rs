@@ -0,0 +1,128 @@
+use crate::ui::surface::{Point, Rect};
+
+use crate::constants::{APPROACH_LENGTH, MAX_ZOOM, MIN_ZOOM, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::models::geometry::WorldRect;
+
+// Drawable area of the window
+//
+// On high-DPI displays the window is measured in points while rendering
+// happens in physical pixels; `dpi_scale` is the number of pixels per point.
+// Everything drawn is sized in pixels, so mouse positions are converted first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+    pub dpi_scale: f32,
+}
+
+impl Viewport {
+    pub fn new(width: u32, height: u32, dpi_scale: f32) -> Self {
+        Viewport {
+            width,
+            height,
+            dpi_scale,
+        }
+    }
+
+    // Convert a mouse position in window points to pixels
+    pub fn to_pixels(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            (x as f32 * self.dpi_scale).round() as i32,
+            (y as f32 * self.dpi_scale).round() as i32,
+        )
+    }
+
+    // Scale factor for overlays drawn at a base size, so text keeps its
+    // physical size on high-DPI displays
+    pub fn ui_scale(&self, base: u32) -> u32 {
+        ((base as f32 * self.dpi_scale).round() as u32).max(1)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::new(WINDOW_WIDTH, WINDOW_HEIGHT, 1.0)
+    }
+}
+
+// Maps world coordinates in metres to screen pixels
+//
+// The camera looks at a world point, drawn in the middle of the viewport. At
+// a zoom of 1.0 the area within `extent` of the origin (the whole scene) fits
+// the viewport, so resizing the window rescales the scene. The simulation
+// never sees screen pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub center: (f32, f32),
+    pub zoom: f32,
+    pub extent: (f32, f32), // Half width and half height of the scene, in metres
+    pub viewport: Viewport,
+}
+
+impl Camera {
+    pub fn new(viewport: Viewport, extent: (f32, f32)) -> Self {
+        Camera {
+            center: (0.0, 0.0),
+            zoom: 1.0,
+            extent,
+            viewport,
+        }
+    }
+
+    // Pixels per metre at the current zoom and viewport size
+    pub fn scale(&self) -> f32 {
+        let fit_width = self.viewport.width as f32 / (2.0 * self.extent.0);
+        let fit_height = self.viewport.height as f32 / (2.0 * self.extent.1);
+        fit_width.min(fit_height) * self.zoom
+    }
+
+    pub fn to_screen(&self, position: (f32, f32)) -> Point {
+        let scale = self.scale();
+        Point::new(
+            ((position.0 - self.center.0) * scale + self.viewport.width as f32 / 2.0).round() as i32,
+            ((position.1 - self.center.1) * scale + self.viewport.height as f32 / 2.0).round() as i32,
+        )
+    }
+
+    pub fn to_world(&self, x: i32, y: i32) -> (f32, f32) {
+        let scale = self.scale();
+        (
+            (x as f32 - self.viewport.width as f32 / 2.0) / scale + self.center.0,
+            (y as f32 - self.viewport.height as f32 / 2.0) / scale + self.center.1,
+        )
+    }
+
+    // Screen rectangle covering a world rectangle, at least one pixel in size
+    pub fn to_screen_rect(&self, rect: WorldRect) -> Rect {
+        let top_left = self.to_screen((rect.x, rect.y));
+        let bottom_right = self.to_screen((rect.x + rect.width, rect.y + rect.height));
+        Rect::new(
+            top_left.x(),
+            top_left.y(),
+            (bottom_right.x() - top_left.x()).max(1) as u32,
+            (bottom_right.y() - top_left.y()).max(1) as u32,
+        )
+    }
+
+    // Move the view by a distance dragged on screen
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        let scale = self.scale();
+        self.center.0 -= dx as f32 / scale;
+        self.center.1 -= dy as f32 / scale;
+    }
+
+    // Zoom by a factor while keeping the world point under the cursor in place
+    pub fn zoom_at(&mut self, x: i32, y: i32, factor: f32) {
+        let anchor = self.to_world(x, y);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let moved = self.to_world(x, y);
+        self.center.0 += anchor.0 - moved.0;
+        self.center.1 += anchor.1 - moved.1;
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(Viewport::default(), (APPROACH_LENGTH, APPROACH_LENGTH))
+    }
+}
@@ -0,0 +1,161 @@
+use crate::constants::TICKS_PER_SECOND;
+use crate::models::traffic_light::TrafficLightState;
+use crate::models::vehicle::{StopReason, Vehicle};
+use crate::simulation::intersection::Control;
+use crate::simulation::network::Network;
+use crate::ui::camera::{Camera, Viewport};
+use crate::ui::font::{draw_text, text_width, GLYPH_HEIGHT};
+use crate::ui::shapes::{draw_polygon, fill_polygon};
+use crate::ui::surface::{Color, Rect, Surface};
+
+const MOVING_COLOR: Color = Color::RGB(0, 255, 0);
+const RED_LIGHT_COLOR: Color = Color::RGB(255, 0, 0);
+const LEADER_COLOR: Color = Color::RGB(255, 165, 0);
+const CONFLICT_COLOR: Color = Color::RGB(255, 0, 255);
+const SPILLBACK_COLOR: Color = Color::RGB(0, 160, 255);
+const YIELD_COLOR: Color = Color::RGB(255, 255, 0);
+const STOP_SIGN_COLOR: Color = Color::RGB(255, 255, 255);
+const REACTING_COLOR: Color = Color::RGB(0, 128, 0);
+
+fn stop_reason_color(reason: Option<StopReason>) -> Color {
+    match reason {
+        None => MOVING_COLOR,
+        Some(StopReason::RedLight) => RED_LIGHT_COLOR,
+        Some(StopReason::Leader(_)) => LEADER_COLOR,
+        Some(StopReason::Conflict) => CONFLICT_COLOR,
+        Some(StopReason::Spillback) => SPILLBACK_COLOR,
+        Some(StopReason::Yield) => YIELD_COLOR,
+        Some(StopReason::StopSign) => STOP_SIGN_COLOR,
+        Some(StopReason::Reacting) => REACTING_COLOR,
+    }
+}
+
+fn describe(vehicle: &Vehicle) -> Vec<String> {
+    let status = match vehicle.stop_reason {
+        None => "MOVING".to_string(),
+        Some(StopReason::RedLight) => "STOPPED: RED LIGHT".to_string(),
+        Some(StopReason::Leader(id)) => format!("STOPPED: BEHIND #{}", id),
+        Some(StopReason::Conflict) => "STOPPED: CONFLICT".to_string(),
+        Some(StopReason::Spillback) => "STOPPED: SPILLBACK".to_string(),
+        Some(StopReason::Yield) => "STOPPED: YIELD".to_string(),
+        Some(StopReason::StopSign) => "STOPPED: STOP SIGN".to_string(),
+        Some(StopReason::Reacting) => "STOPPED: REACTING".to_string(),
+    };
+
+    let mut lines = vec![
+        format!("VEHICLE #{}", vehicle.id),
+        format!(
+            "LEG {} TO {} {:?}",
+            vehicle.path.movement.from, vehicle.path.movement.to, vehicle.route
+        ),
+        format!("SPEED {:.1} M/S", vehicle.speed()),
+        format!(
+            "WAITED {:.1}S",
+            vehicle.waiting_ticks as f32 / TICKS_PER_SECOND as f32
+        ),
+        format!(
+            "REACTION {:.1}S AGGRESSION {:.2}",
+            vehicle.driver.reaction_ticks as f32 / TICKS_PER_SECOND as f32,
+            vehicle.driver.aggressiveness
+        ),
+        status,
+    ];
+    if vehicle.ran_red {
+        lines.push("RAN A RED LIGHT".to_string());
+    }
+    lines
+}
+
+// Draw a vehicle's details in a small box next to a point on screen
+pub fn render_vehicle_details(
+    canvas: &mut dyn Surface,
+    vehicle: &Vehicle,
+    viewport: &Viewport,
+    x: i32,
+    y: i32,
+) {
+    let lines = describe(vehicle);
+    let scale = viewport.ui_scale(1);
+    let padding = 4 * scale as i32;
+    let line_height = ((GLYPH_HEIGHT + 2) * scale) as i32;
+    let width = lines.iter().map(|line| text_width(line, scale)).max().unwrap_or(0) + 2 * padding as u32;
+    let height = line_height as u32 * lines.len() as u32 + 2 * padding as u32;
+
+    // Keep the box inside the viewport
+    let x = (x + 3 * padding).min(viewport.width as i32 - width as i32);
+    let y = (y + 3 * padding).min(viewport.height as i32 - height as i32);
+
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+    canvas.fill_rect(Rect::new(x, y, width, height));
+    for (index, line) in lines.iter().enumerate() {
+        draw_text(
+            canvas,
+            line,
+            x + padding,
+            y + padding + index as i32 * line_height,
+            scale,
+            Color::RGB(255, 255, 255),
+        );
+    }
+}
+
+// Overlay showing why each vehicle is doing what it does
+//
+// Stop zones by the stop line of each leg are outlined in the colour of
+// their light, and roundabout yield zones and the stop zones of legs with a
+// stop sign in yellow. Each vehicle gets its safe-distance envelope and an
+// outline coloured by its stop reason: green moving, red for a red light,
+// orange behind a leader (with a line to it), magenta for a crossing vehicle
+// in the intersection, blue for a full road beyond it, white while making a
+// full stop, yellow when giving way on a roundabout or at a stop sign and
+// dark green while reacting before moving off.
+// Hovering a vehicle shows its details.
+pub fn render(
+    canvas: &mut dyn Surface,
+    network: &Network,
+    camera: &Camera,
+    mouse: (i32, i32),
+) {
+    for intersection in &network.intersections {
+        for light in &intersection.traffic_lights {
+            let color = match light.state {
+                TrafficLightState::Red => RED_LIGHT_COLOR,
+                TrafficLightState::Green => MOVING_COLOR,
+            };
+            canvas.set_draw_color(color);
+            draw_polygon(canvas, camera, &intersection.stop_zone(light.leg));
+        }
+        let gives_way = |leg: usize| match intersection.control {
+            Control::Signals => false,
+            Control::Roundabout(_) => true,
+            Control::Stop(stop) => stop.stops(leg, &intersection.major),
+        };
+        canvas.set_draw_color(YIELD_COLOR);
+        for leg in (0..intersection.geometry.legs.len()).filter(|&leg| gives_way(leg)) {
+            draw_polygon(canvas, camera, &intersection.stop_zone(leg));
+        }
+    }
+
+    for vehicle in network.vehicles() {
+        let color = stop_reason_color(vehicle.stop_reason);
+
+        fill_polygon(canvas, camera, &vehicle.safe_envelope(), Color::RGBA(255, 255, 255, 60));
+
+        canvas.set_draw_color(color);
+        draw_polygon(canvas, camera, &vehicle.corners());
+
+        if let Some(StopReason::Leader(id)) = vehicle.stop_reason {
+            if let Some(leader) = network.vehicle(id) {
+                canvas.draw_line(
+                    camera.to_screen(vehicle.position),
+                    camera.to_screen(leader.position),
+                );
+            }
+        }
+    }
+
+    let (x, y) = camera.to_world(mouse.0, mouse.1);
+    if let Some(vehicle) = network.vehicle_at(x, y) {
+        render_vehicle_details(canvas, vehicle, &camera.viewport, mouse.0, mouse.1);
+    }
+}
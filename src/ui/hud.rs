@@ -0,0 +1,182 @@
+use crate::constants::{HUD_MARGIN, HUD_SCALE, TICKS_PER_SECOND};
+use crate::models::route::Route;
+use crate::simulation::intersection::{Control, Intersection};
+use crate::simulation::network::Network;
+use crate::simulation::stop_control::{StopControl, StopRule};
+use crate::ui::camera::Viewport;
+use crate::ui::font::{draw_text, text_width, GLYPH_HEIGHT};
+use crate::ui::surface::{Color, Rect, Surface};
+
+// Viewer state shown alongside the simulation state
+pub struct HudStatus {
+    pub is_paused: bool,
+    pub is_rewound: bool,
+    pub simulation_speed: f32,
+    pub debug_mode: bool,
+    pub click_route: Option<Route>,
+    pub focus: usize, // Intersection whose signals and approaches are shown
+}
+
+// Format ticks as mm:ss.t of simulated time
+fn format_time(ticks: u64) -> String {
+    let tenths = ticks * 10 / TICKS_PER_SECOND;
+    format!("{:02}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+fn lines(network: &Network, status: &HudStatus) -> Vec<String> {
+    let mut flags = String::new();
+    if status.is_paused {
+        flags.push_str("  PAUSED");
+    }
+    if status.is_rewound {
+        flags.push_str("  REWOUND");
+    }
+    if status.debug_mode {
+        flags.push_str("  DEBUG");
+    }
+
+    let vehicles = network.vehicles().count();
+    let stopped = network.vehicles().filter(|v| v.is_stopped()).count();
+    let metrics = &network.metrics;
+
+    // In a grid, signal lines are about the intersection in focus
+    let intersection = &network.intersections[status.focus];
+    let junction = if network.intersections.len() > 1 {
+        format!("#{} ", status.focus)
+    } else {
+        String::new()
+    };
+    let seconds = |ticks: u64| ticks as f32 / TICKS_PER_SECOND as f32;
+    let (phase, timing) = match intersection.control {
+        Control::Roundabout(roundabout) => (
+            format!("{}ROUNDABOUT", junction),
+            format!(
+                "{}GAP {:.1}S  FOLLOW-UP {:.1}S",
+                junction,
+                seconds(roundabout.critical_gap),
+                seconds(roundabout.follow_up_headway)
+            ),
+        ),
+        Control::Stop(stop) => stop_lines(intersection, stop, &junction),
+        Control::Signals => signal_lines(intersection, &junction),
+    };
+    let click_route = match status.click_route {
+        Some(route) => format!("{:?}", route),
+        None => "RANDOM".to_string(),
+    };
+
+    vec![
+        format!("TIME {}  TICK {}", format_time(network.tick), network.tick),
+        format!("SPEED {:.1}X{}", status.simulation_speed, flags),
+        phase,
+        timing,
+        format!("{}APPROACH {}", junction, approach_counts(intersection)),
+        format!("VEHICLES {}  STOPPED {}", vehicles, stopped),
+        format!("SPAWNED {}  EXITED {}", metrics.spawned, metrics.exited),
+        format!(
+            "AVG DELAY {:.1}S  THROUGHPUT {:.0} VEH/H",
+            metrics.average_delay(),
+            metrics.throughput(network.tick)
+        ),
+        format!("CLICK ROUTE {}", click_route),
+    ]
+}
+
+// Draw the status panel in the top-left corner
+pub fn render(
+    canvas: &mut dyn Surface,
+    network: &Network,
+    status: &HudStatus,
+    viewport: &Viewport,
+) {
+    let lines = lines(network, status);
+    let scale = viewport.ui_scale(HUD_SCALE);
+    let margin = viewport.ui_scale(HUD_MARGIN);
+    let line_height = (GLYPH_HEIGHT + 3) * scale;
+    let width = lines
+        .iter()
+        .map(|line| text_width(line, scale))
+        .max()
+        .unwrap_or(0);
+
+    // Translucent background so the scene stays visible underneath
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+    canvas.fill_rect(Rect::new(
+        margin as i32,
+        margin as i32,
+        width + 2 * margin,
+        line_height * lines.len() as u32 + margin,
+    ));
+    for (index, line) in lines.iter().enumerate() {
+        draw_text(
+            canvas,
+            line,
+            (2 * margin) as i32,
+            (2 * margin + index as u32 * line_height) as i32,
+            scale,
+            Color::RGB(255, 255, 255),
+        );
+    }
+}
+
+// Phase and timing lines for a signalised intersection
+fn signal_lines(intersection: &Intersection, junction: &str) -> (String, String) {
+    let phase = if intersection.phase_held {
+        format!("{}GREEN {}  HELD", junction, intersection.phase_name())
+    } else {
+        format!(
+            "{}GREEN {}  {:.1}S LEFT",
+            junction,
+            intersection.phase_name(),
+            intersection.phase_remaining_ticks() as f32 / TICKS_PER_SECOND as f32
+        )
+    };
+    let timing = format!(
+        "{}CYCLE {:.1}S  OFFSET {:.1}S",
+        junction,
+        intersection.cycle_ticks as f32 / TICKS_PER_SECOND as f32,
+        intersection.offset_ticks as f32 / TICKS_PER_SECOND as f32
+    );
+    (phase, timing)
+}
+
+// Rule and timing lines for a stop-controlled intersection
+fn stop_lines(intersection: &Intersection, stop: StopControl, junction: &str) -> (String, String) {
+    let seconds = |ticks: u64| ticks as f32 / TICKS_PER_SECOND as f32;
+    match stop.rule {
+        StopRule::AllWay => (
+            format!("{}ALL-WAY STOP", junction),
+            format!("{}FULL STOP {:.1}S", junction, seconds(stop.full_stop)),
+        ),
+        StopRule::TwoWay => {
+            let major: Vec<&str> = intersection
+                .major
+                .iter()
+                .map(|&leg| intersection.geometry.leg_name(leg))
+                .collect();
+            (
+                format!("{}TWO-WAY STOP  MAJOR {}", junction, major.join("-")),
+                format!(
+                    "{}FULL STOP {:.1}S  GAP {:.1}S",
+                    junction,
+                    seconds(stop.full_stop),
+                    seconds(stop.critical_gap)
+                ),
+            )
+        }
+    }
+}
+
+// Vehicles approaching on each leg, labelled by their direction of travel
+fn approach_counts(intersection: &Intersection) -> String {
+    let counts: Vec<String> = (0..intersection.geometry.legs.len())
+        .map(|leg| {
+            format!(
+                "{} {}",
+                intersection.geometry.approach_label(leg),
+                intersection.approach_count(leg)
+            )
+        })
+        .collect();
+    counts.join("  ")
+}
@@ -0,0 +1,6 @@
+pub mod camera;
+pub mod debug;
+pub mod font;
+pub mod hud;
+pub mod shapes;
+pub mod surface;
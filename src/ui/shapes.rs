@@ -0,0 +1,38 @@
+use crate::ui::camera::Camera;
+use crate::ui::surface::{Color, Point, Rect, Surface};
+
+// Filled polygon given by its corners in world coordinates
+pub fn fill_polygon(canvas: &mut dyn Surface, camera: &Camera, corners: &[(f32, f32)], color: Color) {
+    let points: Vec<(f32, f32)> = corners
+        .iter()
+        .map(|&corner| {
+            let point = camera.to_screen(corner);
+            (point.x() as f32, point.y() as f32)
+        })
+        .collect();
+    canvas.set_draw_color(color);
+    canvas.fill_polygon(&points);
+}
+
+// Outline of a polygon given by its corners in world coordinates
+pub fn draw_polygon(canvas: &mut dyn Surface, camera: &Camera, corners: &[(f32, f32)]) {
+    let mut points: Vec<Point> = corners.iter().map(|&corner| camera.to_screen(corner)).collect();
+    if let Some(&first) = points.first() {
+        points.push(first);
+    }
+    canvas.draw_lines(&points[..]);
+}
+
+// Filled circle, drawn as one horizontal span per screen row
+pub fn fill_disc(canvas: &mut dyn Surface, camera: &Camera, center: (f32, f32), radius: f32, color: Color) {
+    let middle = camera.to_screen(center);
+    let radius = radius * camera.scale();
+    canvas.set_draw_color(color);
+    let rows = radius.ceil() as i32;
+    for row in -rows..=rows {
+        let half_width = (radius * radius - (row * row) as f32).max(0.0).sqrt().round() as i32;
+        if half_width > 0 {
+            canvas.fill_rect(Rect::new(middle.x() - half_width, middle.y() + row, 2 * half_width as u32, 1));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(feature = "sdl")]
+use sdl2::render::{BlendMode, Canvas};
+#[cfg(feature = "sdl")]
+use sdl2::video::Window;
+
+// Colour with opacity, as SDL2 has it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    #[allow(non_snake_case)]
+    pub const fn RGB(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    #[allow(non_snake_case)]
+    pub const fn RGBA(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+// Point on screen, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Self {
+        Point { x, y }
+    }
+
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+}
+
+// Rectangle on screen, in pixels, from its top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn contains_point(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.x && x < self.x + self.width as i32 && y >= self.y && y < self.y + self.height as i32
+    }
+}
+
+// Something the scene can be drawn on, in screen pixels
+//
+// Everything is drawn in the colour set last, blended over what is already
+// there when that colour is translucent. The viewer draws on its SDL2 window
+// and the web build on an HTML canvas, so both show the same scene.
+pub trait Surface {
+    fn set_draw_color(&mut self, color: Color);
+
+    fn fill_rect(&mut self, rect: Rect);
+
+    // Connected line segments through the points
+    fn draw_lines(&mut self, points: &[Point]);
+
+    fn draw_line(&mut self, from: Point, to: Point) {
+        self.draw_lines(&[from, to]);
+    }
+
+    // Filled polygon given by its corners, by default drawn as one horizontal
+    // span per row between each pair of edge crossings
+    fn fill_polygon(&mut self, points: &[(f32, f32)]) {
+        if points.len() < 3 {
+            return;
+        }
+        let top = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min).floor() as i32;
+        let bottom = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+        let mut crossings = Vec::new();
+        for row in top..=bottom {
+            let y = row as f32 + 0.5;
+            crossings.clear();
+            for (index, &(x1, y1)) in points.iter().enumerate() {
+                let (x2, y2) = points[(index + 1) % points.len()];
+                if (y1 <= y) != (y2 <= y) {
+                    crossings.push(x1 + (y - y1) / (y2 - y1) * (x2 - x1));
+                }
+            }
+            crossings.sort_by(f32::total_cmp);
+            for pair in crossings.chunks_exact(2) {
+                let (left, right) = (pair[0].round() as i32, pair[1].round() as i32);
+                if right > left {
+                    self.fill_rect(Rect::new(left, row, (right - left) as u32, 1));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl From<Color> for sdl2::pixels::Color {
+    fn from(color: Color) -> Self {
+        sdl2::pixels::Color::RGBA(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl From<Point> for sdl2::rect::Point {
+    fn from(point: Point) -> Self {
+        sdl2::rect::Point::new(point.x, point.y)
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl From<Rect> for sdl2::rect::Rect {
+    fn from(rect: Rect) -> Self {
+        sdl2::rect::Rect::new(rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl Surface for Canvas<Window> {
+    fn set_draw_color(&mut self, color: Color) {
+        Canvas::set_blend_mode(self, if color.a < 255 { BlendMode::Blend } else { BlendMode::None });
+        Canvas::set_draw_color(self, color);
+    }
+
+    fn fill_rect(&mut self, rect: Rect) {
+        Canvas::fill_rect(self, sdl2::rect::Rect::from(rect)).expect("Failed to render");
+    }
+
+    fn draw_lines(&mut self, points: &[Point]) {
+        let points: Vec<sdl2::rect::Point> = points.iter().map(|&point| point.into()).collect();
+        Canvas::draw_lines(self, &points[..]).expect("Failed to render lines");
+    }
+}
@@ -0,0 +1,332 @@
+use rand::Rng;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
+
+use road_intersection::constants::{CLICK_DRAG_THRESHOLD, WINDOW_HEIGHT, WINDOW_WIDTH, ZOOM_STEP};
+use road_intersection::models::direction::Direction;
+use road_intersection::models::route::Route;
+use road_intersection::simulation::history::History;
+use road_intersection::simulation::network::Network;
+use road_intersection::simulation::replay::{InputEvent, Replay};
+use road_intersection::ui::camera::{Camera, Viewport};
+use road_intersection::ui::debug;
+use road_intersection::ui::hud::{self, HudStatus};
+use road_intersection::ui::shapes::draw_polygon;
+
+use crate::Viewer;
+
+// Inputs from the window, acting on the viewer
+impl Viewer {
+    // Record and apply a live input
+    //
+    // Inputs that change the simulation are ignored while a replay is being
+    // fed or while looking at an earlier tick.
+    fn submit(&mut self, input: InputEvent, replaying: bool) {
+        if input.changes_simulation() && replaying {
+            println!("Input ignored during replay");
+        } else if input.changes_simulation() && !self.history.is_live() {
+            println!("Input ignored while rewound, return to the live tick first");
+        } else {
+            self.recorder.record(self.network.tick, input);
+            self.apply_input(input);
+        }
+    }
+
+    // State currently on screen: a snapshot while rewound, otherwise the live simulation
+    fn shown(&self) -> &Network {
+        self.history.view().unwrap_or(&self.network)
+    }
+}
+
+// Keyboard spawn: random route, at a random intersection on the edge of the grid
+fn spawn(direction: Direction) -> InputEvent {
+    InputEvent::Spawn {
+        direction,
+        route: None,
+        entry: None,
+    }
+}
+
+// Current drawable size of the window and its pixel density
+fn viewport_of(canvas: &Canvas<Window>) -> Result<Viewport, String> {
+    let (width, height) = canvas.output_size()?;
+    let (window_width, _) = canvas.window().size();
+    Ok(Viewport::new(width, height, width as f32 / window_width.max(1) as f32))
+}
+
+// Convert mouse positions from window points to pixels, so that all
+// coordinates handled below match what is drawn
+fn to_pixels(event: Event, viewport: &Viewport) -> Event {
+    match event {
+        Event::MouseButtonDown { timestamp, window_id, which, mouse_btn, clicks, x, y } => {
+            let (x, y) = viewport.to_pixels(x, y);
+            Event::MouseButtonDown { timestamp, window_id, which, mouse_btn, clicks, x, y }
+        }
+        Event::MouseButtonUp { timestamp, window_id, which, mouse_btn, clicks, x, y } => {
+            let (x, y) = viewport.to_pixels(x, y);
+            Event::MouseButtonUp { timestamp, window_id, which, mouse_btn, clicks, x, y }
+        }
+        Event::MouseMotion { timestamp, window_id, which, mousestate, x, y, xrel, yrel } => {
+            let (x, y) = viewport.to_pixels(x, y);
+            Event::MouseMotion { timestamp, window_id, which, mousestate, x, y, xrel, yrel }
+        }
+        _ => event,
+    }
+}
+
+// Open the viewer window and run the simulation in it until it is closed
+pub fn run(viewer: &mut Viewer, replay: &mut Option<Replay>, departures: &mut Vec<(u64, InputEvent)>) -> Result<(), String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+
+    let window = video_subsystem
+        .window("Road Intersection", WINDOW_WIDTH, WINDOW_HEIGHT)
+        .position_centered()
+        .resizable()
+        .allow_highdpi()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let mut event_pump = sdl_context.event_pump()?;
+
+    let mut camera = Camera::new(viewport_of(&canvas)?, viewer.network.extent());
+
+    // Enhancement variables
+    let mut debug_mode = false;
+    let mut show_hud = true;
+    let mut confirm_exit = false;
+    let mut scrubbing = false;
+
+    // Mouse interaction state
+    let mut mouse = (0, 0);
+    let mut drag_start: Option<(i32, i32)> = None;
+    let mut panning = false;
+    let mut click_route: Option<Route> = None; // Route for vehicles spawned by clicking
+    let mut selected_vehicle: Option<u32> = None;
+
+    'running: loop {
+        // Handle events
+        for event in event_pump.poll_iter() {
+            let replaying = replay.is_some();
+            match to_pixels(event, &camera.viewport) {
+                Event::Quit { .. } => {
+                    if confirm_exit {
+                        break 'running;
+                    } else {
+                        confirm_exit = true;
+                        println!("Press Escape again to exit");
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    if confirm_exit {
+                        break 'running;
+                    } else {
+                        confirm_exit = true;
+                        println!("Press Escape again to exit");
+                    }
+                }
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    let input = match keycode {
+                        Keycode::Up => Some(spawn(Direction::South)),
+                        Keycode::Down => Some(spawn(Direction::North)),
+                        Keycode::Left => Some(spawn(Direction::East)),
+                        Keycode::Right => Some(spawn(Direction::West)),
+                        Keycode::R => {
+                            let mut rng = rand::thread_rng();
+                            let direction = match rng.gen_range(0..4) {
+                                0 => Direction::North,
+                                1 => Direction::South,
+                                2 => Direction::East,
+                                _ => Direction::West,
+                            };
+                            Some(spawn(direction))
+                        },
+                        // Manual override: switch the traffic lights now
+                        Keycode::L => Some(InputEvent::SwitchLights),
+                        // Enhancement: Pause/Resume
+                        Keycode::Space => Some(if viewer.is_paused { InputEvent::Resume } else { InputEvent::Pause }),
+                        // Enhancement: Step through the timeline while paused
+                        Keycode::Comma if viewer.is_paused => {
+                            viewer.history.step_back();
+                            None
+                        },
+                        Keycode::Period if viewer.is_paused => {
+                            // Replay recorded ticks first, then advance the live simulation
+                            if viewer.history.step_forward() { None } else { Some(InputEvent::Step) }
+                        },
+                        // Enhancement: Debug Mode
+                        Keycode::D => {
+                            debug_mode = !debug_mode;
+                            println!("Debug Mode {}", if debug_mode { "ON" } else { "OFF" });
+                            None
+                        },
+                        // Enhancement: Status panel
+                        Keycode::H => {
+                            show_hud = !show_hud;
+                            None
+                        },
+                        // Enhancement: Speed Control
+                        Keycode::Equals | Keycode::KpPlus => {
+                            Some(InputEvent::Speed(f32::min(viewer.simulation_speed * 1.5, 5.0)))
+                        },
+                        Keycode::Minus | Keycode::KpMinus => {
+                            Some(InputEvent::Speed(f32::max(viewer.simulation_speed / 1.5, 0.25)))
+                        },
+                        // Enhancement: Route for vehicles spawned by clicking an approach
+                        Keycode::Num0 | Keycode::Num1 | Keycode::Num2 | Keycode::Num3 => {
+                            click_route = match keycode {
+                                Keycode::Num1 => Some(Route::Left),
+                                Keycode::Num2 => Some(Route::Straight),
+                                Keycode::Num3 => Some(Route::Right),
+                                _ => None,
+                            };
+                            None
+                        },
+                        // Enhancement: Delete the inspected vehicle
+                        Keycode::Delete | Keycode::Backspace => selected_vehicle.take().map(InputEvent::Remove),
+                        // Enhancement: Reset the view
+                        Keycode::Home => {
+                            camera = Camera::new(camera.viewport, camera.extent);
+                            None
+                        },
+                        // Enhancement: Fullscreen
+                        Keycode::F11 => {
+                            let window = canvas.window_mut();
+                            let fullscreen = match window.fullscreen_state() {
+                                FullscreenType::Off => FullscreenType::Desktop,
+                                _ => FullscreenType::Off,
+                            };
+                            window.set_fullscreen(fullscreen)?;
+                            None
+                        },
+                        _ => None
+                    };
+
+                    if let Some(input) = input {
+                        viewer.submit(input, replaying);
+                    }
+                    confirm_exit = false; // Reset exit confirmation on any other key press
+                }
+                // Enhancement: Drag along the timeline to rewind
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. }
+                    if History::timeline_contains(&camera.viewport, x, y) =>
+                {
+                    if !viewer.is_paused {
+                        viewer.submit(InputEvent::Pause, replaying);
+                    }
+                    viewer.history.seek_to_x(&camera.viewport, x);
+                    scrubbing = true;
+                }
+                // Enhancement: Click to act on the scene, drag to pan it
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    drag_start = Some((x, y));
+                    panning = false;
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    if scrubbing {
+                        viewer.history.seek_to_x(&camera.viewport, x);
+                    } else if let Some((start_x, start_y)) = drag_start {
+                        let distance = (x - start_x).abs().max((y - start_y).abs());
+                        panning = panning || distance > CLICK_DRAG_THRESHOLD;
+                        if panning {
+                            camera.pan(x - mouse.0, y - mouse.1);
+                        }
+                    }
+                    mouse = (x, y);
+                }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    if drag_start.take().is_some() && !panning {
+                        // Clicking a light gives it green, clicking an approach spawns there
+                        let (world_x, world_y) = camera.to_world(x, y);
+                        let shown = viewer.shown();
+                        let input = match shown.traffic_light_at(world_x, world_y) {
+                            Some((index, leg)) => Some(InputEvent::ForceGreen(index, leg)),
+                            None => shown.entry_at(world_x, world_y).map(|(entry, leg)| InputEvent::SpawnAt {
+                                entry,
+                                leg,
+                                route: click_route,
+                            }),
+                        };
+                        if let Some(input) = input {
+                            viewer.submit(input, replaying);
+                        }
+                    }
+                    scrubbing = false;
+                    panning = false;
+                }
+                // Enhancement: Right-click a light to hold its phase, or a vehicle to inspect it
+                Event::MouseButtonDown { mouse_btn: MouseButton::Right, x, y, .. } => {
+                    let (world_x, world_y) = camera.to_world(x, y);
+                    let shown = viewer.shown();
+                    if let Some((index, _)) = shown.traffic_light_at(world_x, world_y) {
+                        viewer.submit(InputEvent::HoldPhase(index), replaying);
+                    } else {
+                        selected_vehicle = shown.vehicle_at(world_x, world_y).map(|v| v.id);
+                    }
+                }
+                // Enhancement: Scroll to zoom
+                Event::MouseWheel { y, .. } => camera.zoom_at(mouse.0, mouse.1, ZOOM_STEP.powi(y)),
+                // Enhancement: Rescale the scene to fit a resized window
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } => {
+                    camera.viewport = viewport_of(&canvas)?;
+                }
+                _ => {}
+            }
+        }
+
+        // Feed recorded inputs and route file departures that are due on this tick
+        viewer.feed(replay, departures);
+
+        // Update simulation if not paused, catching up with the live tick first if rewound
+        if !viewer.is_paused && !viewer.history.step_forward() {
+            viewer.advance();
+        }
+
+        // Render
+        canvas.set_draw_color(Color::RGB(0, 128, 0)); // Green background for grass
+        canvas.clear();
+
+        viewer.shown().render(&mut canvas, &camera);
+
+        // Render the vehicle decision overlay if debug mode is on
+        if debug_mode {
+            debug::render(&mut canvas, viewer.shown(), &camera, mouse);
+        }
+
+        // Render the details of the inspected vehicle next to it
+        if let Some(vehicle) = selected_vehicle.and_then(|id| viewer.shown().vehicle(id)) {
+            canvas.set_draw_color(Color::RGB(255, 255, 255));
+            draw_polygon(&mut canvas, &camera, &vehicle.corners());
+            let position = camera.to_screen(vehicle.position);
+            debug::render_vehicle_details(&mut canvas, vehicle, &camera.viewport, position.x(), position.y());
+        }
+
+        // Render the status panel
+        if show_hud {
+            let (world_x, world_y) = camera.to_world(mouse.0, mouse.1);
+            let status = HudStatus {
+                is_paused: viewer.is_paused,
+                is_rewound: !viewer.history.is_live(),
+                simulation_speed: viewer.simulation_speed,
+                debug_mode,
+                click_route,
+                focus: viewer.shown().nearest_intersection(world_x, world_y),
+            };
+            hud::render(&mut canvas, viewer.shown(), &status, &camera.viewport);
+        }
+
+        viewer.history.render(&mut canvas, &camera.viewport);
+
+        canvas.present();
+
+        // Cap FPS based on simulation speed
+        std::thread::sleep(viewer.frame_time());
+    }
+
+    Ok(())
+}
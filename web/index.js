@@ -0,0 +1,70 @@
+// Runs the simulation at 60 ticks per second of wall time and draws it on
+// the canvas. Query parameters pick the scenario: ?seed=7&grid=2x3&junction=roundabout
+import init, { Simulation } from "./pkg/road_intersection_web.js";
+
+const TICKS_PER_SECOND = 60;
+
+await init();
+
+const params = new URLSearchParams(location.search);
+const simulation = new Simulation(
+    Number(params.get("seed") ?? Math.floor(Math.random() * 2 ** 32)),
+    params.get("grid") ?? "1x1",
+    params.get("junction") ?? "signals",
+);
+
+const canvas = document.getElementById("scene");
+const context = canvas.getContext("2d");
+
+function resize() {
+    const scale = window.devicePixelRatio || 1;
+    canvas.width = Math.round(canvas.clientWidth * scale);
+    canvas.height = Math.round(canvas.clientHeight * scale);
+    simulation.resize(canvas.width, canvas.height, scale);
+}
+window.addEventListener("resize", resize);
+resize();
+
+// Pointer positions in canvas pixels
+const toPixels = (event) => [
+    Math.round(event.offsetX * canvas.width / canvas.clientWidth),
+    Math.round(event.offsetY * canvas.height / canvas.clientHeight),
+];
+canvas.addEventListener("mousemove", (event) => simulation.hover(...toPixels(event)));
+canvas.addEventListener("click", (event) => simulation.click(...toPixels(event)));
+canvas.addEventListener("wheel", (event) => {
+    event.preventDefault();
+    simulation.zoom(...toPixels(event), event.deltaY < 0 ? 1.1 : 1 / 1.1);
+});
+
+let paused = false;
+const headings = { ArrowUp: "south", ArrowDown: "north", ArrowLeft: "east", ArrowRight: "west" };
+window.addEventListener("keydown", (event) => {
+    if (event.key in headings) {
+        simulation.spawn(headings[event.key]);
+    } else if (event.key === "r") {
+        simulation.spawn(Object.values(headings)[Math.floor(Math.random() * 4)]);
+    } else if (event.key === "l") {
+        simulation.switch_lights();
+    } else if (event.key === "d") {
+        simulation.toggle_debug();
+    } else if (event.key === " ") {
+        paused = !paused;
+    }
+});
+
+// Step by the ticks due since the last frame, at most a second's worth
+let due = 0;
+let last = performance.now();
+function frame(now) {
+    if (!paused) {
+        due = Math.min(due + (now - last) / 1000 * TICKS_PER_SECOND, TICKS_PER_SECOND);
+        const ticks = Math.floor(due);
+        simulation.step(ticks);
+        due -= ticks;
+    }
+    last = now;
+    simulation.render(context);
+    requestAnimationFrame(frame);
+}
+requestAnimationFrame(frame);
@@ -0,0 +1,208 @@
+use wasm_bindgen::prelude::*;
+use web_sys::CanvasRenderingContext2d;
+
+use road_intersection::models::direction::Direction;
+use road_intersection::simulation::intersection::Control;
+use road_intersection::simulation::network::{Network, NetworkLayout};
+use road_intersection::simulation::replay::{parse_grid, InputEvent};
+use road_intersection::simulation::roundabout::Roundabout;
+use road_intersection::simulation::stop_control::{StopControl, StopRule};
+use road_intersection::ui::camera::{Camera, Viewport};
+use road_intersection::ui::debug;
+use road_intersection::ui::hud::{self, HudStatus};
+use road_intersection::ui::surface::{Color, Point, Rect, Surface};
+
+// 2D context of an HTML canvas as a surface to draw the scene on
+//
+// Polygons are filled as paths rather than row by row, which the browser
+// does much faster for the same result.
+pub struct CanvasSurface<'a> {
+    context: &'a CanvasRenderingContext2d,
+}
+
+impl<'a> CanvasSurface<'a> {
+    pub fn new(context: &'a CanvasRenderingContext2d) -> Self {
+        context.set_line_width(1.0);
+        CanvasSurface { context }
+    }
+}
+
+impl Surface for CanvasSurface<'_> {
+    fn set_draw_color(&mut self, color: Color) {
+        let style = format!("rgba({},{},{},{:.3})", color.r, color.g, color.b, color.a as f32 / 255.0);
+        self.context.set_fill_style_str(&style);
+        self.context.set_stroke_style_str(&style);
+    }
+
+    fn fill_rect(&mut self, rect: Rect) {
+        self.context.fill_rect(
+            rect.x() as f64,
+            rect.y() as f64,
+            rect.width() as f64,
+            rect.height() as f64,
+        );
+    }
+
+    // Through the middle of the pixels, so one-pixel lines stay sharp
+    fn draw_lines(&mut self, points: &[Point]) {
+        let Some((first, rest)) = points.split_first() else {
+            return;
+        };
+        self.context.begin_path();
+        self.context.move_to(first.x() as f64 + 0.5, first.y() as f64 + 0.5);
+        for point in rest {
+            self.context.line_to(point.x() as f64 + 0.5, point.y() as f64 + 0.5);
+        }
+        self.context.stroke();
+    }
+
+    fn fill_polygon(&mut self, points: &[(f32, f32)]) {
+        let Some((first, rest)) = points.split_first() else {
+            return;
+        };
+        self.context.begin_path();
+        self.context.move_to(first.0 as f64, first.1 as f64);
+        for point in rest {
+            self.context.line_to(point.0 as f64, point.1 as f64);
+        }
+        self.context.close_path();
+        self.context.fill();
+    }
+}
+
+// Simulation in the browser, drawn on a canvas the way the viewer draws it
+//
+// The page owns the animation loop: it calls `step` with the ticks due and
+// then `render`. Clicks and key presses are handed in as the viewer's inputs.
+#[wasm_bindgen]
+pub struct Simulation {
+    network: Network,
+    camera: Camera,
+    mouse: (i32, i32),
+    debug_mode: bool,
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    // Grid as `<rows>x<columns>`; junction one of signals, roundabout,
+    // all-way-stop or two-way-stop
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32, grid: &str, junction: &str) -> Result<Simulation, JsError> {
+        let (rows, columns) = parse_grid(grid).map_err(|e| JsError::new(&e))?;
+        let control = match junction {
+            "signals" => Control::Signals,
+            "roundabout" => Control::Roundabout(Roundabout::default()),
+            "all-way-stop" => Control::Stop(StopControl::new(StopRule::AllWay)),
+            "two-way-stop" => Control::Stop(StopControl::new(StopRule::TwoWay)),
+            junction => return Err(JsError::new(&format!("Unknown junction '{}'", junction))),
+        };
+        let layout = NetworkLayout {
+            rows,
+            columns,
+            control,
+            ..NetworkLayout::default()
+        };
+        let network = Network::new(seed as u64, layout);
+        let camera = Camera::new(Viewport::default(), network.extent());
+        Ok(Simulation {
+            network,
+            camera,
+            mouse: (0, 0),
+            debug_mode: false,
+        })
+    }
+
+    pub fn tick(&self) -> f64 {
+        self.network.tick as f64
+    }
+
+    pub fn vehicle_count(&self) -> usize {
+        self.network.vehicles().count()
+    }
+
+    pub fn step(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.network.update();
+        }
+    }
+
+    // Canvas size in pixels, and pixels per CSS pixel
+    pub fn resize(&mut self, width: u32, height: u32, dpi_scale: f32) {
+        self.camera.viewport = Viewport::new(width, height, dpi_scale);
+    }
+
+    // Pointer position in canvas pixels, for the status panel and the debug overlay
+    pub fn hover(&mut self, x: i32, y: i32) {
+        self.mouse = (x, y);
+    }
+
+    // Click at a point on the canvas: a traffic light gets green, an
+    // approach from the edge of the network a vehicle
+    pub fn click(&mut self, x: i32, y: i32) {
+        let (x, y) = self.camera.to_world(x, y);
+        let input = match self.network.traffic_light_at(x, y) {
+            Some((index, leg)) => Some(InputEvent::ForceGreen(index, leg)),
+            None => self
+                .network
+                .entry_at(x, y)
+                .map(|(entry, leg)| InputEvent::SpawnAt { entry, leg, route: None }),
+        };
+        if let Some(input) = input {
+            self.network.apply_input(input);
+        }
+    }
+
+    // Spawn a vehicle heading north, south, east or west, at a random
+    // intersection on the edge it comes from
+    pub fn spawn(&mut self, heading: &str) -> Result<(), JsError> {
+        let direction = match heading {
+            "north" => Direction::North,
+            "south" => Direction::South,
+            "east" => Direction::East,
+            "west" => Direction::West,
+            heading => return Err(JsError::new(&format!("Unknown heading '{}'", heading))),
+        };
+        self.network.apply_input(InputEvent::Spawn {
+            direction,
+            route: None,
+            entry: None,
+        });
+        Ok(())
+    }
+
+    pub fn switch_lights(&mut self) {
+        self.network.apply_input(InputEvent::SwitchLights);
+    }
+
+    pub fn toggle_debug(&mut self) {
+        self.debug_mode = !self.debug_mode;
+    }
+
+    pub fn zoom(&mut self, x: i32, y: i32, factor: f32) {
+        self.camera.zoom_at(x, y, factor);
+    }
+
+    // Draw the scene, the debug overlay if on and the status panel
+    pub fn render(&self, context: &CanvasRenderingContext2d) {
+        let mut surface = CanvasSurface::new(context);
+        let viewport = self.camera.viewport;
+        surface.set_draw_color(Color::RGB(0, 128, 0)); // Green background for grass
+        surface.fill_rect(Rect::new(0, 0, viewport.width, viewport.height));
+
+        self.network.render(&mut surface, &self.camera);
+        if self.debug_mode {
+            debug::render(&mut surface, &self.network, &self.camera, self.mouse);
+        }
+
+        let (x, y) = self.camera.to_world(self.mouse.0, self.mouse.1);
+        let status = HudStatus {
+            is_paused: false,
+            is_rewound: false,
+            simulation_speed: 1.0,
+            debug_mode: self.debug_mode,
+            click_route: None,
+            focus: self.network.nearest_intersection(x, y),
+        };
+        hud::render(&mut surface, &self.network, &status, &viewport);
+    }
+}
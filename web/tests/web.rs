@@ -0,0 +1,67 @@
+// Run under Node with `wasm-pack test --node web`
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use road_intersection::simulation::network::{Network, NetworkLayout};
+use road_intersection::ui::camera::Camera;
+use road_intersection::ui::surface::{Color, Point, Rect, Surface};
+use road_intersection_web::Simulation;
+
+// Surface that counts what would be drawn, as Node has no canvas
+#[derive(Default)]
+struct Recording {
+    colors: Vec<Color>,
+    rects: usize,
+    lines: usize,
+}
+
+impl Surface for Recording {
+    fn set_draw_color(&mut self, color: Color) {
+        self.colors.push(color);
+    }
+
+    fn fill_rect(&mut self, _rect: Rect) {
+        self.rects += 1;
+    }
+
+    fn draw_lines(&mut self, _points: &[Point]) {
+        self.lines += 1;
+    }
+}
+
+#[wasm_bindgen_test]
+fn vehicles_drive_through_and_leave() {
+    let mut simulation = Simulation::new(7, "1x1", "signals").unwrap();
+    simulation.step(60);
+    for heading in ["north", "south", "east", "west"] {
+        simulation.spawn(heading).unwrap();
+    }
+    assert_eq!(simulation.vehicle_count(), 4);
+    simulation.step(60 * 60);
+    assert_eq!(simulation.vehicle_count(), 0);
+    assert_eq!(simulation.tick(), 61.0 * 60.0);
+}
+
+#[wasm_bindgen_test]
+fn invalid_scenarios_are_rejected() {
+    assert!(Simulation::new(7, "0x2", "signals").is_err());
+    assert!(Simulation::new(7, "1x1", "traffic-circle").is_err());
+}
+
+#[wasm_bindgen_test]
+fn scene_draws_roads_lights_and_vehicles() {
+    let mut network = Network::new(7, NetworkLayout::default());
+    for _ in 0..60 {
+        network.update();
+    }
+    network.spawn_at(0, 0, None);
+    let mut recording = Recording::default();
+    network.render(&mut recording, &Camera::default());
+
+    assert!(recording.rects > 0);
+    assert_eq!(recording.lines, 0);
+    assert!(recording.colors.contains(&Color::RGB(100, 100, 100))); // Roads
+    assert!(recording.colors.contains(&Color::RGB(0, 255, 0))); // A green light
+    assert!(recording.colors.contains(&Color::RGB(255, 0, 0))); // A red light
+}